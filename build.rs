@@ -0,0 +1,71 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Records the build metadata shown by `dessert --version`
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DESSERT_GIT_COMMIT={commit}");
+
+    // Reproducible builds pin the date with SOURCE_DATE_EPOCH
+    let date = match env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<i64>().ok())
+    {
+        Some(epoch) => chrono::DateTime::from_timestamp(epoch, 0).unwrap_or_default(),
+        None => chrono::Utc::now(),
+    };
+    println!(
+        "cargo:rustc-env=DESSERT_BUILD_DATE={}",
+        date.format("%Y-%m-%d")
+    );
+
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+    println!(
+        "cargo:rustc-env=DESSERT_FEATURES={}",
+        match features.as_slice() {
+            [] => "none".to_string(),
+            features => features.join(", "),
+        }
+    );
+
+    let lock = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+    let lock = fs::read_to_string(lock).unwrap_or_default();
+    println!(
+        "cargo:rustc-env=DESSERT_UNRAR_VERSION=unrar {}, unrar_sys {}",
+        locked_version(&lock, "unrar"),
+        locked_version(&lock, "unrar_sys")
+    );
+}
+
+/// The version of the package in the lock file
+fn locked_version(lock: &str, package: &str) -> String {
+    lock.split("[[package]]")
+        .find(|entry| entry.contains(&format!("name = \"{package}\"\n")))
+        .and_then(|entry| {
+            entry
+                .lines()
+                .find_map(|line| line.strip_prefix("version = "))
+        })
+        .map(|version| version.trim_matches('"').to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
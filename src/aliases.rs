@@ -0,0 +1,50 @@
+use crate::release::Release;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeMap;
+
+/// Gives the release the title the library has for it: the title of the `[aliases]` of the
+/// config file matching its parsed title whatever their case and punctuation, like
+/// `"S.W.A.T" = "S.W.A.T."`, or else its title with the words of `[replacements]` replaced,
+/// like `Shippuuden = "Shippuden"`. Albums keep their names
+pub fn apply(
+    release: Release,
+    aliases: &BTreeMap<String, String>,
+    replacements: &BTreeMap<String, String>,
+) -> Result<Release> {
+    if matches!(release, Release::Album { .. }) {
+        return Ok(release);
+    }
+
+    let key = normalize(release.name());
+    if let Some((_, title)) = aliases.iter().find(|(alias, _)| normalize(alias) == key) {
+        return Ok(release.with_name(title.clone()));
+    }
+
+    let mut name = release.name().to_string();
+    for (words, replacement) in replacements {
+        name = replacement_regex(words)?
+            .replace_all(&name, regex::NoExpand(replacement))
+            .into_owned();
+    }
+
+    Ok(release.with_name(name))
+}
+
+/// Matches the words as whole words, ignoring case
+fn replacement_regex(words: &str) -> Result<Regex> {
+    RegexBuilder::new(&format!(r"\b{}\b", regex::escape(words)))
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Failed to compile replacement of {:?}", words))
+}
+
+/// The letters and digits of the title in lowercase, as scene names punctuate titles
+/// differently, e.g. `S W A T` for `S.W.A.T.`
+fn normalize(title: &str) -> String {
+    title
+        .chars()
+        .filter(|character| character.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::extract::check_file_name;
+use dessert::naming::Naming;
+use dessert::release;
+use dessert::{config, library};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct AuditArgs {
+    /// Library directory to check
+    directory: PathBuf,
+
+    /// Rename the files that aren't named by the naming templates, with their subtitles and
+    /// other files of the same name
+    #[clap(long)]
+    fix: bool,
+
+    /// How names that don't match the naming templates are parsed
+    #[clap(long, value_enum, default_value = "auto")]
+    mode: release::Mode,
+}
+
+/// Lists the video files of the library that aren't named the way the naming templates of the
+/// config file name them, renaming them with `--fix`
+pub fn audit(args: &AuditArgs) -> Result<()> {
+    let config = config::current();
+    let naming = &config.naming;
+
+    let videos = library::videos(&args.directory)?;
+
+    let (mut nonconforming, mut unrecognized, mut renamed) = (0, 0, 0);
+    for video in &videos {
+        let name = library::relative_name(&args.directory, video);
+        let Some(expected) = expected_name(naming, &name, args.mode) else {
+            println!("Unrecognized  {name}");
+            unrecognized += 1;
+            continue;
+        };
+        if expected == name {
+            continue;
+        }
+
+        println!("Misnamed      {name}\n          ->  {expected}");
+        nonconforming += 1;
+        if args.fix {
+            match rename(&args.directory, video, &expected) {
+                Ok(()) => renamed += 1,
+                Err(e) => println!("              {e:#}"),
+            }
+        }
+    }
+
+    println!(
+        "{} video files, {nonconforming} misnamed, {unrecognized} unrecognized",
+        videos.len()
+    );
+    if args.fix {
+        println!("Renamed {renamed}");
+    }
+
+    Ok(())
+}
+
+/// The name the naming templates give the video, see [`library::identify`]
+fn expected_name(naming: &Naming, name: &str, mode: release::Mode) -> Option<String> {
+    let release = library::identify(naming, name, mode)?;
+
+    naming
+        .file_name(&release)
+        .ok()
+        .map(|file_name| naming.normalization.apply(&file_name))
+}
+
+/// Renames the video to the expected name, with the files next to it named like it, like
+/// `name.en.srt`
+fn rename(library: &Path, video: &Path, expected: &str) -> Result<()> {
+    check_file_name(Path::new(expected))?;
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    let destination = library.join(expected);
+    let destination_stem = destination
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let destination_directory = destination.parent().unwrap_or(library);
+
+    let mut renames = Vec::new();
+    for file in library::companions(video)? {
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+        let suffix = &file_name[stem.len()..];
+        let target = destination_directory.join(format!("{destination_stem}{suffix}"));
+        if target.exists() {
+            return Err(anyhow!("Not renaming, {:?} already exists", target));
+        }
+        renames.push((file, target));
+    }
+
+    fs::create_dir_all(destination_directory)
+        .with_context(|| format!("Failed to create {:?}", destination_directory))?;
+    for (from, to) in renames {
+        fs::rename(&from, &to).with_context(|| format!("Failed to rename {:?}", from))?;
+    }
+
+    Ok(())
+}
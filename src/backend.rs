@@ -0,0 +1,133 @@
+use crate::config;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where imports are kept once they are extracted to the destination directory
+pub trait Backend {
+    /// Stores the files extracted below the destination directory, giving where each of them
+    /// ended up
+    fn store(&self, destination_directory: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>>;
+
+    /// Deletes the stored files that were kept elsewhere from the destination directory, once
+    /// `--delete-source` no longer compares the source with them
+    fn release(&self, _destination_directory: &Path, _files: &[PathBuf]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps the files in the destination directory, the library being on a local or mounted
+/// filesystem
+pub struct Local;
+
+impl Backend for Local {
+    fn store(&self, _: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        Ok(files.to_vec())
+    }
+}
+
+/// A library on another host reached over SFTP, with the `sftp` feature. The destination
+/// directory is then where imports are staged, and files are uploaded to the same path below
+/// the remote root before they are deleted from it
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Sftp {
+    pub host: String,
+
+    #[serde(default = "default_port")]
+    pub port: u16,
+
+    pub username: String,
+
+    /// The private key to authenticate with, like `/home/me/.ssh/id_ed25519`
+    pub private_key: PathBuf,
+
+    /// The passphrase of the private key, if it has one
+    pub passphrase: Option<String>,
+
+    /// The known hosts file the key of the host is checked against, `~/.ssh/known_hosts` by
+    /// default
+    pub known_hosts: Option<PathBuf>,
+
+    /// The library directory on the host
+    pub remote_root: String,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+/// An S3 compatible bucket, like on MinIO or B2, with the `s3` feature. Like with [`Sftp`], the
+/// destination directory stages the files, which are uploaded below the prefix
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct S3 {
+    /// The URL of the service, like `https://s3.eu-central-003.backblazeb2.com`
+    pub endpoint: String,
+
+    /// The region requests are signed for
+    #[serde(default = "default_region")]
+    pub region: String,
+
+    pub bucket: String,
+
+    /// Prepended to the paths of the files below the destination directory, like `media/`
+    #[serde(default)]
+    pub prefix: String,
+
+    pub access_key_id: String,
+
+    pub secret_access_key: String,
+
+    /// Whether the bucket is in the path of URLs instead of the host name, as MinIO expects
+    #[serde(default = "default_path_style")]
+    pub path_style: bool,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_path_style() -> bool {
+    true
+}
+
+/// The backend of the config file, connecting to the host of `[sftp]` or the bucket of `[s3]`
+/// if it has one of them
+pub fn current() -> Result<Box<dyn Backend>> {
+    let config = config::current();
+    match (&config.sftp, &config.s3) {
+        (Some(_), Some(_)) => Err(anyhow!(
+            "The config file has both an [sftp] and an [s3] section"
+        )),
+        #[cfg(feature = "sftp")]
+        (Some(sftp), None) => Ok(Box::new(crate::sftp::Client::connect(sftp)?)),
+        #[cfg(not(feature = "sftp"))]
+        (Some(_), None) => Err(anyhow!(
+            "The config file has an [sftp] section, but dessert was built without the sftp \
+             feature"
+        )),
+        #[cfg(feature = "s3")]
+        (None, Some(s3)) => Ok(Box::new(crate::s3::Client::connect(s3)?)),
+        #[cfg(not(feature = "s3"))]
+        (None, Some(_)) => Err(anyhow!(
+            "The config file has an [s3] section, but dessert was built without the s3 feature"
+        )),
+        (None, None) => Ok(Box::new(Local)),
+    }
+}
+
+/// Deletes staged files that were stored elsewhere, with the directories they leave empty
+pub fn remove_staged(destination_directory: &Path, files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        fs::remove_file(file).with_context(|| format!("Failed to delete staged {:?}", file))?;
+        for directory in file.ancestors().skip(1) {
+            if directory == destination_directory || fs::remove_dir(directory).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
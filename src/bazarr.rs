@@ -0,0 +1,92 @@
+use crate::secrets;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+const SUBTITLE_EXTENSIONS: [&str; 7] = ["srt", "sub", "idx", "ass", "ssa", "vtt", "sup"];
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Client {
+    /// Bazarr base URL, e.g. `http://localhost:6767`
+    url: String,
+
+    /// Bazarr API key
+    api_key: String,
+}
+
+pub enum Kind {
+    Series,
+    Movies,
+}
+
+impl Client {
+    pub fn init_from_file(path: &Path) -> Result<Client> {
+        let config = fs::read_to_string(path).context("Failed to read Bazarr config file")?;
+
+        let mut config = config
+            .parse::<toml::Table>()
+            .context("Failed to parse Bazarr config file")?;
+        secrets::resolve(&mut config, "api_key")?;
+
+        toml::Value::Table(config)
+            .try_into::<Client>()
+            .context("Failed to parse Bazarr config file")
+    }
+
+    /// Checks that Bazarr answers and accepts the API key
+    pub fn check(&self) -> Result<()> {
+        let url = format!("{}/api/system/status", self.url.trim_end_matches('/'));
+        debug!("GET {url}");
+        let response = reqwest::blocking::Client::new()
+            .get(url)
+            .header("X-API-KEY", &self.api_key)
+            .send()
+            .context("Failed to reach Bazarr")?;
+        debug!("Bazarr responded with {}", response.status());
+
+        if !response.status().is_success() {
+            Err(anyhow!("Bazarr refused the request: {}", response.status()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Asks Bazarr to search for missing subtitles of the given kind
+    pub fn search_missing_subtitles(&self, kind: Kind) -> Result<()> {
+        let task = match kind {
+            Kind::Series => "wanted_search_missing_subtitles_series",
+            Kind::Movies => "wanted_search_missing_subtitles_movies",
+        };
+
+        let url = format!("{}/api/system/tasks", self.url.trim_end_matches('/'));
+        debug!("POST {url} with taskid {task}");
+        let response = reqwest::blocking::Client::new()
+            .post(url)
+            .header("X-API-KEY", &self.api_key)
+            .form(&[("taskid", task)])
+            .send()
+            .context("Failed to trigger Bazarr subtitle search")?;
+        debug!("Bazarr responded with {}", response.status());
+
+        if !response.status().is_success() {
+            Err(anyhow!(
+                "Failed to trigger Bazarr subtitle search: {}",
+                response.text()?
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn has_subtitles(files: &[impl AsRef<Path>]) -> bool {
+    files.iter().any(|file| {
+        file.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| SUBTITLE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+            .unwrap_or(false)
+    })
+}
@@ -0,0 +1,123 @@
+use log::debug;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// Whether destination directories tell apart names differing only in case, detected once
+/// per directory
+static DETECTED: LazyLock<Mutex<HashMap<PathBuf, bool>>> = LazyLock::new(Default::default);
+
+/// How the names in the destination directory are compared, e.g. without case on SMB shares
+/// and exFAT drives
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Case {
+    /// Detected for each destination directory
+    #[default]
+    Auto,
+    Sensitive,
+    Insensitive,
+}
+
+impl Case {
+    pub fn is_insensitive(self, directory: &Path) -> bool {
+        match self {
+            Case::Auto => *DETECTED
+                .lock()
+                .unwrap()
+                .entry(directory.to_path_buf())
+                .or_insert_with(|| detect(directory)),
+            Case::Sensitive => false,
+            Case::Insensitive => true,
+        }
+    }
+}
+
+/// Looks up the directory by its own name in another case, or else by a probe file when its
+/// name has no letters
+fn detect(directory: &Path) -> bool {
+    let Ok(directory) = directory.canonicalize() else {
+        return false;
+    };
+    let name = directory
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let flipped = match name.to_uppercase() {
+        upper if upper != name => upper,
+        _ => name.to_lowercase(),
+    };
+
+    let insensitive = match flipped != name {
+        true => same_file(&directory, &directory.with_file_name(flipped)),
+        false => probe(&directory),
+    };
+    debug!(
+        "Detected {} destination directory {:?}",
+        if insensitive {
+            "case-insensitive"
+        } else {
+            "case-sensitive"
+        },
+        directory
+    );
+
+    insensitive
+}
+
+fn probe(directory: &Path) -> bool {
+    let probe = directory.join(".dessert-case-probe");
+    if fs::File::create(&probe).is_err() {
+        return false;
+    }
+    let insensitive = directory.join(".DESSERT-CASE-PROBE").exists();
+    let _ = fs::remove_file(&probe);
+
+    insensitive
+}
+
+#[cfg(unix)]
+fn same_file(path: &Path, other: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (path.metadata(), other.metadata()) {
+        (Ok(path), Ok(other)) => path.dev() == other.dev() && path.ino() == other.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Path, other: &Path) -> bool {
+    other.exists()
+}
+
+/// The destination file name, without extension, in the case of the files already in the
+/// directory that differ from it only in case, if there are any
+pub fn existing_name(directory: &Path, file_name: &str) -> Option<String> {
+    let lowercase = file_name.to_lowercase();
+    let components = Path::new(&lowercase).iter().collect::<Vec<_>>();
+    let (last, parents) = components.split_last()?;
+
+    let mut existing = directory.to_path_buf();
+    for parent in parents {
+        existing.push(find(&existing, |name| OsStr::new(name) == *parent)?);
+    }
+    let found = find(&existing, |name| Path::new(name).file_stem() == Some(*last))?;
+    existing.push(Path::new(&found).file_stem()?);
+
+    existing
+        .strip_prefix(directory)
+        .ok()?
+        .to_str()
+        .map(str::to_string)
+}
+
+/// The first entry of the directory whose lowercased name matches
+fn find(directory: &Path, matches: impl Fn(&str) -> bool) -> Option<String> {
+    fs::read_dir(directory)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .find(|name| matches(&name.to_lowercase()))
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// How much of the start of a source file [`content_hash`] hashes
+const CONTENT_HASH_BYTES: u64 = 1024 * 1024;
+
+/// Writes the SHA-256 of the file to a `.sha256` file next to it, in the format of `sha256sum`
+/// so the library can be verified later with `sha256sum -c`
+pub fn write(file: &Path) -> Result<PathBuf> {
+    let destination = path(file);
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+
+    fs::write(&destination, format!("{}  {file_name}\n", sha256(file)?))
+        .with_context(|| format!("Failed to write checksum file {:?}", destination))?;
+
+    Ok(destination)
+}
+
+/// Where [`write`] writes the checksum of the file
+pub fn path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".sha256");
+
+    PathBuf::from(path)
+}
+
+/// Identifies the content of a source file by the SHA-256 of its first MiB and its size, so the
+/// same release is recognized under another name without reading all of it
+pub fn content_hash(file: &Path) -> Result<String> {
+    let reader =
+        File::open(file).with_context(|| format!("Failed to open {:?} for checksum", file))?;
+    let size = reader
+        .metadata()
+        .with_context(|| format!("Failed to read metadata of {:?}", file))?
+        .len();
+    let hash = sha256_of(reader.take(CONTENT_HASH_BYTES))
+        .with_context(|| format!("Failed to read {:?} for checksum", file))?;
+
+    Ok(format!("{hash}-{size}"))
+}
+
+fn sha256(file: &Path) -> Result<String> {
+    let reader =
+        File::open(file).with_context(|| format!("Failed to open {:?} for checksum", file))?;
+
+    sha256_of(reader).with_context(|| format!("Failed to read {:?} for checksum", file))
+}
+
+fn sha256_of(mut reader: impl Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
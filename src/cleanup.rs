@@ -0,0 +1,209 @@
+use crate::compare::Compare;
+use crate::extract::{self, Layout};
+use crate::pipeline;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Removes the source of an import once the destination files are verified, unless it was
+/// modified within the grace period `keep_for`, leaving it for a later run to remove. Gives how
+/// long verifying took
+pub fn delete_source(
+    source: &Path,
+    source_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+    keep_for: Option<Duration>,
+) -> Result<Duration> {
+    let started = Instant::now();
+    verify(
+        source_file,
+        destination_directory,
+        file_name,
+        compare,
+        layout,
+    )?;
+    let verification = started.elapsed();
+
+    let destination = destination_directory
+        .canonicalize()
+        .context("Failed to resolve destination directory")?;
+    let canonical_source = source
+        .canonicalize()
+        .context("Failed to resolve source directory")?;
+    if destination.starts_with(&canonical_source) {
+        return Err(anyhow!(
+            "Not deleting the source, the destination directory is inside it"
+        ));
+    }
+
+    if let Some(keep_for) = keep_for {
+        let age = modified(source)?.elapsed().unwrap_or(Duration::ZERO);
+        if age < keep_for {
+            let left = Duration::from_secs((keep_for - age).as_secs());
+            info!(
+                "Keeping source for another {}: {:?}",
+                humantime::format_duration(left),
+                source
+            );
+            return Ok(verification);
+        }
+    }
+
+    for path in targets(source, source_file)? {
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        }
+        .with_context(|| format!("Failed to delete {path:?}"))?;
+        info!("Deleted source: {:?}", path);
+    }
+
+    Ok(verification)
+}
+
+/// Checks that every file of the source is in the destination, matching the source by the
+/// comparison
+fn verify(
+    source_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+) -> Result<()> {
+    let plan = match extract::Kind::of(source_file) {
+        extract::Kind::Rar => {
+            return extract::verify_rar_file(
+                source_file,
+                destination_directory,
+                file_name,
+                compare,
+                layout,
+            )
+            .context("Not deleting the source")
+        }
+        extract::Kind::Iso => {
+            let min_video_size = layout.min_video_size();
+            extract::plan_iso_file(
+                source_file,
+                destination_directory,
+                file_name,
+                compare,
+                min_video_size,
+            )?
+        }
+        extract::Kind::Split => {
+            extract::plan_split_file(source_file, destination_directory, file_name, compare)?
+        }
+        extract::Kind::Video => {
+            extract::plan_video_file(source_file, destination_directory, file_name, compare)?
+        }
+        extract::Kind::Disc => {
+            let plan = extract::plan_disc(
+                source_file,
+                destination_directory,
+                file_name,
+                compare,
+                layout.min_video_size(),
+            )?;
+            if plan
+                .iter()
+                .any(|(_, action)| !matches!(action, extract::Action::Skip))
+            {
+                return Err(anyhow!(
+                    "Not deleting the source, a file of the disc structure is missing or differs"
+                ));
+            }
+            return Ok(());
+        }
+    };
+    match plan.first() {
+        Some((_, extract::Action::Skip)) => Ok(()),
+        _ => Err(anyhow!(
+            "Not deleting the source, the destination file is missing or differs"
+        )),
+    }
+}
+
+/// The source directory, or when the source is a file or a directory holding other releases,
+/// the file with the other volumes or parts of its archive
+pub(crate) fn targets(source: &Path, source_file: &Path) -> Result<Vec<PathBuf>> {
+    if source.is_dir() && !pipeline::is_shared(source) {
+        return Ok(vec![source.to_path_buf()]);
+    }
+
+    archive_files(source_file)
+}
+
+/// The files of the source file's archive, the volumes of a rar archive, the parts of a split
+/// file or the directories of a disc structure
+pub(crate) fn archive_files(source_file: &Path) -> Result<Vec<PathBuf>> {
+    match extract::Kind::of(source_file) {
+        extract::Kind::Rar => {}
+        extract::Kind::Split => return extract::split_parts(source_file),
+        extract::Kind::Iso | extract::Kind::Video => return Ok(vec![source_file.to_path_buf()]),
+        extract::Kind::Disc => return Ok(extract::disc_trees(source_file)),
+    }
+
+    let name = archive_name(source_file)?;
+    let directory = source_file.parent().unwrap_or(Path::new("."));
+
+    Ok(directory
+        .read_dir()
+        .context("Failed to read source directory")?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| extract::is_volume(path))
+        .filter(|path| {
+            path.file_stem()
+                .and_then(OsStr::to_str)
+                .is_some_and(|stem| {
+                    stem == name
+                        || stem
+                            .strip_prefix(name)
+                            .is_some_and(|rest| rest.starts_with(".part"))
+                })
+        })
+        .collect())
+}
+
+/// The name of the archive without its extension, or the volume number of `name.part1.rar`
+pub(crate) fn archive_name(source_file: &Path) -> Result<&str> {
+    // Volumes are named `name.rar, name.r00, ...` or `name.part1.rar, name.part2.rar, ...`
+    let stem = source_file
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .ok_or(anyhow!("Failed to get rar file stem"))?;
+
+    Ok(match stem.rsplit_once(".part") {
+        Some((name, part)) if part.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => stem,
+    })
+}
+
+/// When the source was last modified, the newest of the files directly in it for a directory
+fn modified(source: &Path) -> Result<SystemTime> {
+    let mut modified = source
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .context("Failed to read modification time of source")?;
+
+    if source.is_dir() {
+        for entry in source
+            .read_dir()
+            .context("Failed to read source directory")?
+            .flatten()
+        {
+            if let Ok(time) = entry.metadata().and_then(|metadata| metadata.modified()) {
+                modified = modified.max(time);
+            }
+        }
+    }
+
+    Ok(modified)
+}
@@ -0,0 +1,118 @@
+use crate::extract::Action;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// How an existing destination file is compared with the file it would be written from, to
+/// skip it when they match or else replace it
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Compare {
+    /// Files of the same size match
+    #[default]
+    Size,
+    /// Files of the same size and modification time match
+    #[value(name = "size+mtime")]
+    SizeMtime,
+    /// Files of the same size and CRC32 checksum match, reading the whole existing file
+    Checksum,
+}
+
+/// What a destination file is written from
+pub struct Source<'a> {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub checksum: Checksum<'a>,
+}
+
+pub enum Checksum<'a> {
+    /// The CRC32 recorded in the archive
+    Crc(u32),
+    /// The file itself, checksummed when compared
+    File(&'a Path),
+    /// The contents of the file, checksummed when compared, e.g. from a disc image
+    Reader(Box<dyn Read + 'a>),
+}
+
+/// Archive timestamps are stored with a resolution of two seconds
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// What to do with the destination file, to have it match the source
+pub fn action(source: Source, destination: &Path, compare: Compare) -> Result<Action> {
+    // Written as a file of its own instead of through the link, which could point anywhere
+    if destination.is_symlink() {
+        return Ok(Action::Replace);
+    }
+    if !destination.exists() {
+        return Ok(Action::Extract);
+    }
+
+    let metadata = destination
+        .metadata()
+        .context("Failed to read metadata of existing destination file")?;
+    let matches = metadata.len() == source.size
+        && match compare {
+            Compare::Size => true,
+            Compare::SizeMtime => match (metadata.modified().ok(), source.modified) {
+                (Some(existing), Some(modified)) => {
+                    existing
+                        .duration_since(modified)
+                        .unwrap_or_else(|e| e.duration())
+                        <= MTIME_TOLERANCE
+                }
+                _ => false,
+            },
+            Compare::Checksum => {
+                let expected = match source.checksum {
+                    Checksum::Crc(crc) => crc,
+                    Checksum::File(file) => crc32(file)?,
+                    Checksum::Reader(reader) => {
+                        crc32_of(reader).context("Failed to read source for checksum")?
+                    }
+                };
+                crc32(destination)? == expected
+            }
+        };
+
+    match matches {
+        true => Ok(Action::Skip),
+        false => Ok(Action::Replace),
+    }
+}
+
+fn crc32(file: &Path) -> Result<u32> {
+    let reader =
+        File::open(file).with_context(|| format!("Failed to open {:?} for checksum", file))?;
+
+    crc32_of(reader).with_context(|| format!("Failed to read {:?} for checksum", file))
+}
+
+fn crc32_of(mut reader: impl Read) -> std::io::Result<u32> {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 1024 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// The time of an archive entry, stored as a DOS timestamp in local time
+pub fn dos_time(time: u32) -> Option<SystemTime> {
+    let date = chrono::NaiveDate::from_ymd_opt(
+        1980 + (time >> 25) as i32,
+        (time >> 21) & 0xf,
+        (time >> 16) & 0x1f,
+    )?;
+    let time = date.and_hms_opt((time >> 11) & 0x1f, (time >> 5) & 0x3f, (time & 0x1f) * 2)?;
+
+    time.and_local_timezone(chrono::Local)
+        .earliest()
+        .map(SystemTime::from)
+}
@@ -0,0 +1,991 @@
+use crate::naming::Naming;
+use crate::profile::{Category, Profile};
+use crate::quality::Quality;
+use crate::release::Release;
+use crate::rules::Rule;
+use crate::{backend, bazarr, email, mqtt, naming, retention, secrets, space, tmdb, trace};
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgAction, Command};
+use log::LevelFilter;
+use log::{error, info};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::SystemTime;
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Check the config file given with --config and list every problem found
+    Validate,
+}
+
+static CURRENT: LazyLock<RwLock<Arc<Config>>> = LazyLock::new(Default::default);
+
+/// The contents of a `dessert.toml` configuration file.
+///
+/// Top level keys are named after the long command line flags, e.g.
+/// `source_directory = ["/downloads"]` or `write_nfo = true`, and become the
+/// defaults of those flags, so anything given on the command line wins.
+/// Tables added here belong in [`SECTIONS`] too.
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub naming: Naming,
+
+    pub email: Option<email::Client>,
+
+    pub mqtt: Option<mqtt::Config>,
+
+    pub bazarr: Option<bazarr::Client>,
+
+    pub tmdb: Option<tmdb::Client>,
+
+    pub sftp: Option<backend::Sftp>,
+
+    pub s3: Option<backend::S3>,
+
+    pub tracing: Option<trace::Config>,
+
+    #[serde(default)]
+    pub logging: Logging,
+
+    /// Library titles by the parsed titles they replace, see [`aliases::apply`]
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+
+    /// Replacements of words in parsed titles, see [`aliases::apply`]
+    #[serde(default)]
+    pub replacements: BTreeMap<String, String>,
+
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+
+    #[serde(default, rename = "profile")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    #[serde(default, rename = "category")]
+    pub categories: BTreeMap<String, Category>,
+
+    #[serde(default)]
+    pub sources: Vec<Source>,
+
+    #[serde(default)]
+    pub retention: Vec<retention::Retention>,
+
+    #[serde(default)]
+    pub quality: Quality,
+
+    #[serde(default)]
+    pub plugins: Plugins,
+
+    #[serde(flatten)]
+    options: toml::Table,
+
+    /// The file the config was loaded from
+    #[serde(skip)]
+    path: Option<PathBuf>,
+
+    /// Every file read, the config file and the files it includes
+    #[serde(skip)]
+    files: Vec<PathBuf>,
+}
+
+/// A source directory with the destination directory its releases go to, used when no source
+/// directories are given with flags or the `source_directory` option
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    pub source_directory: PathBuf,
+    pub destination_directory: PathBuf,
+}
+
+/// WebAssembly plugins, with the `plugins` feature
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Plugins {
+    /// Where the `.wasm` plugins are loaded from, instead of the plugins directory next to the
+    /// default config file
+    pub directory: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    /// The most verbose level that is logged to files, e.g. `"debug"`
+    pub level: LevelFilter,
+
+    /// The most verbose level that is logged to the terminal, when there is one
+    pub console_level: LevelFilter,
+
+    /// Directory of a log file kept after the process exits, none by default
+    pub directory: Option<PathBuf>,
+
+    /// How records are written to the log file in the log directory
+    pub format: Format,
+
+    /// Whether each job also gets a log file of its own in the log directory
+    pub job_logs: bool,
+
+    /// The system log records are also sent to, none by default
+    pub system: Option<crate::syslog::System>,
+
+    /// Value of the `UNIT` field of journal records
+    pub unit: Option<String>,
+
+    /// Size in bytes the log file is rotated at
+    pub max_size: u64,
+
+    /// Age in days the log file is rotated at, regardless of its size
+    pub max_age_days: Option<u64>,
+
+    /// Number of rotated log files kept
+    pub keep: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Lines of time, level and message
+    Text,
+
+    /// One JSON object per line, with `timestamp`, `level`, `target`, `job`, `phase` and
+    /// `message` fields
+    Json,
+}
+
+impl Default for Logging {
+    fn default() -> Logging {
+        Logging {
+            level: LevelFilter::Info,
+            console_level: LevelFilter::Info,
+            directory: None,
+            format: Format::Text,
+            job_logs: false,
+            system: None,
+            unit: None,
+            max_size: 10 * 1024 * 1024,
+            max_age_days: None,
+            keep: 5,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file, if any, with the environment variable overrides of the options of
+    /// the command layered on top
+    pub fn load(path: Option<&Path>, command: &Command) -> Result<Config> {
+        let mut files = Vec::new();
+        let mut table = match path {
+            Some(path) => read(path, &mut Vec::new(), &mut files)?,
+            None => toml::Table::new(),
+        };
+        apply_environment(&mut table, std::env::vars(), command);
+        secrets::resolve_config(&mut table)?;
+
+        let mut config = toml::Value::Table(table)
+            .try_into::<Config>()
+            .context("Failed to parse config file")?;
+        config.path = path.map(Path::to_path_buf);
+        config.files = files;
+
+        Ok(config)
+    }
+
+    /// Makes the options of the config file the defaults of the matching command line flags
+    pub fn apply_to(&self, mut command: Command) -> Result<Command> {
+        // A bare `dessert` runs what the config file or environment describes
+        if !self.options.is_empty() || !self.sources.is_empty() {
+            command = command.arg_required_else_help(false);
+        }
+
+        for (key, value) in &self.options {
+            let values = match value {
+                toml::Value::Array(items) => items.iter().map(scalar).collect(),
+                value => scalar(value).map(|value| vec![value]),
+            }
+            .ok_or(anyhow!("Invalid value for {key:?} in config file"))?;
+
+            if EARLY_FLAGS.contains(&key.as_str()) {
+                return Err(anyhow!("Option {key:?} can't be set in the config file"));
+            }
+            let mut known = false;
+            command = set_defaults(command, key, &values, &mut known);
+            if !known {
+                return Err(anyhow!("Unknown option {key:?} in config file"));
+            }
+        }
+
+        Ok(command)
+    }
+}
+
+/// Something wrong with a config file, at a key path like `rule[0].match`
+struct Problem {
+    location: String,
+    message: String,
+}
+
+impl Config {
+    fn problems(&self, command: &Command) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut problem =
+            |location: String, message: String| problems.push(Problem { location, message });
+
+        for (key, value) in &self.options {
+            let Some(values) = (match value {
+                toml::Value::Array(items) => items.iter().map(scalar).collect(),
+                value => scalar(value).map(|value| vec![value]),
+            }) else {
+                problem(key.clone(), "Invalid value".to_string());
+                continue;
+            };
+
+            if EARLY_FLAGS.contains(&key.as_str()) {
+                problem(key.clone(), "Can't be set in the config file".to_string());
+                continue;
+            }
+            let Some(arg) = find_arg(command, key) else {
+                problem(key.clone(), "Unknown option".to_string());
+                continue;
+            };
+
+            for value in &values {
+                if !is_valid(arg, value) {
+                    let possible_values = arg
+                        .get_possible_values()
+                        .iter()
+                        .map(|value| value.get_name().to_string())
+                        .collect::<Vec<_>>();
+                    problem(
+                        key.clone(),
+                        match possible_values.as_slice() {
+                            [] => format!("Invalid value {value:?}"),
+                            possible_values => format!(
+                                "Invalid value {value:?}, expected one of {}",
+                                possible_values.join(", ")
+                            ),
+                        },
+                    );
+                }
+                if let Some(message) = path_problem(key, Path::new(value)) {
+                    problem(key.clone(), message);
+                }
+            }
+        }
+
+        for (kind, template) in [
+            ("episode", &self.naming.episode),
+            ("movie", &self.naming.movie),
+            ("album", &self.naming.album),
+        ] {
+            if let Err(e) = naming::render(template, &sample(kind)) {
+                problem(format!("naming.{kind}"), e.to_string());
+            }
+        }
+        if let Err(e) = self.naming.track_name(1, "Title") {
+            problem("naming.track".to_string(), e.to_string());
+        }
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(&rule.pattern) {
+                let message = e.to_string();
+                let message = message.lines().last().unwrap_or_default();
+                problem(
+                    format!("rule[{index}].match"),
+                    format!("Invalid regex: {}", message.trim_start_matches("error: ")),
+                );
+            }
+            if let Some(template) = &rule.template {
+                let episode = naming::render(template, &sample("episode"));
+                let movie = naming::render(template, &sample("movie"));
+                if let (Err(e), Err(_)) = (episode, movie) {
+                    problem(format!("rule[{index}].template"), e.to_string());
+                }
+            }
+            if let Some(message) = rule
+                .destination_directory
+                .as_deref()
+                .and_then(|path| path_problem("destination_directory", path))
+            {
+                problem(format!("rule[{index}].destination_directory"), message);
+            }
+        }
+
+        if !self.sources.is_empty() && self.options.contains_key("source_directory") {
+            problem(
+                "sources".to_string(),
+                "Can't be used with the source_directory option".to_string(),
+            );
+        }
+        for (index, source) in self.sources.iter().enumerate() {
+            for (key, path) in [
+                ("source_directory", &source.source_directory),
+                ("destination_directory", &source.destination_directory),
+            ] {
+                if let Some(message) = path_problem(key, path) {
+                    problem(format!("sources[{index}].{key}"), message);
+                }
+            }
+        }
+
+        if let Some(directory) = self.plugins.directory.as_deref() {
+            if !directory.is_dir() {
+                problem(
+                    "plugins.directory".to_string(),
+                    format!("{} is not a directory", directory.display()),
+                );
+            }
+        }
+        #[cfg(not(feature = "plugins"))]
+        if self.plugins.directory.is_some() {
+            problem(
+                "plugins".to_string(),
+                "dessert was built without the plugins feature".to_string(),
+            );
+        }
+
+        #[cfg(not(feature = "sftp"))]
+        if self.sftp.is_some() {
+            problem(
+                "sftp".to_string(),
+                "dessert was built without the sftp feature".to_string(),
+            );
+        }
+        if let Some(sftp) = &self.sftp {
+            if !sftp.private_key.is_file() {
+                problem(
+                    "sftp.private_key".to_string(),
+                    format!("{} is not a file", sftp.private_key.display()),
+                );
+            }
+            if !sftp.remote_root.starts_with('/') {
+                problem(
+                    "sftp.remote_root".to_string(),
+                    "Expected an absolute path".to_string(),
+                );
+            }
+        }
+        #[cfg(not(feature = "s3"))]
+        if self.s3.is_some() {
+            problem(
+                "s3".to_string(),
+                "dessert was built without the s3 feature".to_string(),
+            );
+        }
+        if self.sftp.is_some() && self.s3.is_some() {
+            problem(
+                "s3".to_string(),
+                "Can't be used with an [sftp] section".to_string(),
+            );
+        }
+        if let Some(s3) = &self.s3 {
+            if reqwest::Url::parse(&s3.endpoint).is_err() {
+                problem("s3.endpoint".to_string(), "Invalid URL".to_string());
+            }
+        }
+
+        for (name, profile) in &self.profiles {
+            if let Some(profile_naming) = &profile.naming {
+                for (kind, template) in [
+                    ("episode", &profile_naming.episode),
+                    ("movie", &profile_naming.movie),
+                    ("album", &profile_naming.album),
+                ] {
+                    if let Err(e) = naming::render(template, &sample(kind)) {
+                        problem(format!("profile.{name}.naming.{kind}"), e.to_string());
+                    }
+                }
+                if let Err(e) = profile_naming.track_name(1, "Title") {
+                    problem(format!("profile.{name}.naming.track"), e.to_string());
+                }
+            }
+            if let Some(message) = profile
+                .destination_directory
+                .as_deref()
+                .and_then(|path| path_problem("destination_directory", path))
+            {
+                problem(format!("profile.{name}.destination_directory"), message);
+            }
+        }
+
+        for (name, category) in &self.categories {
+            if let Some(profile) = &category.profile {
+                if !self.profiles.contains_key(profile) {
+                    problem(
+                        format!("category.{name}.profile"),
+                        format!("No profile named {profile:?}"),
+                    );
+                }
+            }
+            if let Some(message) = category
+                .destination_directory
+                .as_deref()
+                .and_then(|path| path_problem("destination_directory", path))
+            {
+                problem(format!("category.{name}.destination_directory"), message);
+            }
+        }
+
+        // Freeing space deletes the expendable directory's contents, which mustn't be a library
+        // or downloads
+        if let Some(expendable) = self.options.get("expendable_directory").and_then(scalar) {
+            let expendable = Path::new(&expendable);
+            for (key, directory) in self.directories() {
+                if space::overlaps(expendable, &directory) {
+                    problem(
+                        "expendable_directory".to_string(),
+                        format!(
+                            "Overlaps {key} {}, freeing space would delete it",
+                            directory.display()
+                        ),
+                    );
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// The source and destination directories of the config file, with their keys
+    fn directories(&self) -> Vec<(String, PathBuf)> {
+        let mut directories = Vec::new();
+        for key in ["source_directory", "destination_directory"] {
+            let values = match self.options.get(key) {
+                Some(toml::Value::Array(items)) => items.iter().filter_map(scalar).collect(),
+                Some(value) => scalar(value).into_iter().collect(),
+                None => Vec::new(),
+            };
+            directories.extend(
+                values
+                    .into_iter()
+                    .map(|value| (key.to_string(), value.into())),
+            );
+        }
+        for (index, source) in self.sources.iter().enumerate() {
+            directories.push((
+                format!("sources[{index}].source_directory"),
+                source.source_directory.clone(),
+            ));
+            directories.push((
+                format!("sources[{index}].destination_directory"),
+                source.destination_directory.clone(),
+            ));
+        }
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Some(directory) = &rule.destination_directory {
+                directories.push((
+                    format!("rule[{index}].destination_directory"),
+                    directory.clone(),
+                ));
+            }
+        }
+        for (name, profile) in &self.profiles {
+            if let Some(directory) = &profile.destination_directory {
+                directories.push((
+                    format!("profile.{name}.destination_directory"),
+                    directory.clone(),
+                ));
+            }
+        }
+        for (name, category) in &self.categories {
+            if let Some(directory) = &category.destination_directory {
+                directories.push((
+                    format!("category.{name}.destination_directory"),
+                    directory.clone(),
+                ));
+            }
+        }
+
+        directories
+    }
+}
+
+/// Finds the argument with the id in the command or any of its subcommands
+fn find_arg<'a>(command: &'a Command, id: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .or_else(|| {
+            command
+                .get_subcommands()
+                .find_map(|subcommand| find_arg(subcommand, id))
+        })
+}
+
+/// Parses the value the way the argument's flag would be parsed on the command line
+fn is_valid(arg: &Arg, value: &str) -> bool {
+    if matches!(arg.get_action(), ArgAction::SetTrue) {
+        return value == "true" || value == "false";
+    }
+
+    Command::new("dessert")
+        .arg(
+            Arg::new("value")
+                .long("value")
+                .value_parser(arg.get_value_parser().clone()),
+        )
+        .try_get_matches_from(["dessert", "--value", value])
+        .is_ok()
+}
+
+/// Checks that paths referenced by options named `*_directory`, `*_file` or `*_database` exist
+fn path_problem(key: &str, path: &Path) -> Option<String> {
+    if key.ends_with("_directory") && !path.is_dir() {
+        Some(format!("{} is not a directory", path.display()))
+    } else if key.ends_with("_file") && !path.is_file() {
+        Some(format!("{} is not a file", path.display()))
+    } else if key.ends_with("_database")
+        && !path
+            .parent()
+            .is_some_and(|parent| parent.as_os_str().is_empty() || parent.is_dir())
+    {
+        Some(format!(
+            "The directory of {} does not exist",
+            path.display()
+        ))
+    } else {
+        None
+    }
+}
+
+fn sample(kind: &str) -> Release {
+    match kind {
+        "episode" => Release::Episode {
+            name: "Name".to_string(),
+            season: 1,
+            episode: 1,
+        },
+        "album" => Release::Album {
+            artist: "Artist".to_string(),
+            name: "Name".to_string(),
+            year: 2000,
+        },
+        _ => Release::Movie {
+            name: "Name".to_string(),
+            year: 2000,
+        },
+    }
+}
+
+pub fn run(command: &ConfigCommand, path: Option<&Path>, cli: Command) -> Result<()> {
+    match command {
+        ConfigCommand::Validate => validate(path, cli),
+    }
+}
+
+fn validate(path: Option<&Path>, cli: Command) -> Result<()> {
+    let path = path.ok_or(anyhow!("Missing --config"))?;
+    let config = fs::read_to_string(path).context("Failed to read config file")?;
+
+    // Includes are merged and secrets resolved first, as the sections expect the resolved fields
+    let problems = match config.parse::<toml::Table>() {
+        Err(e) => vec![spanned_problem(&config, &e)],
+        Ok(table) => match include(table, path, &mut vec![canonical(path)], &mut Vec::new())
+            .map_err(|e| ("include", e))
+            .and_then(|mut table| {
+                secrets::resolve_config(&mut table).map_err(|e| ("secrets", e))?;
+                Ok(table)
+            }) {
+            Err((location, e)) => vec![Problem {
+                location: location.to_string(),
+                message: format!("{e:#}"),
+            }],
+            Ok(table) => match toml::Value::Table(table).try_into::<Config>() {
+                Ok(config) => config.problems(&cli),
+                // Parsing the text again finds the line of the problem
+                Err(e) => vec![match toml::from_str::<Config>(&config) {
+                    Err(spanned) if spanned.message() == e.message() => {
+                        spanned_problem(&config, &spanned)
+                    }
+                    _ => Problem {
+                        location: String::new(),
+                        message: e.message().to_string(),
+                    },
+                }],
+            },
+        },
+    };
+
+    for problem in &problems {
+        println!(
+            "{}: {}: {}",
+            path.display(),
+            problem.location,
+            problem.message
+        );
+    }
+
+    match problems.len() {
+        0 => {
+            println!("{} is valid", path.display());
+            Ok(())
+        }
+        1 => Err(anyhow!("Found 1 problem in the config file")),
+        count => Err(anyhow!("Found {count} problems in the config file")),
+    }
+}
+
+fn spanned_problem(config: &str, e: &toml::de::Error) -> Problem {
+    Problem {
+        location: e
+            .span()
+            .map(|span| format!("line {}", config[..span.start].matches('\n').count() + 1))
+            .unwrap_or_default(),
+        message: e.message().to_string(),
+    }
+}
+
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value.clone()),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn set_defaults(mut command: Command, id: &str, values: &[String], known: &mut bool) -> Command {
+    let multiple = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .map(|arg| matches!(arg.get_action(), ArgAction::Append));
+
+    if let Some(multiple) = multiple {
+        *known = true;
+
+        if multiple || values.len() == 1 {
+            command = command.mut_arg(id, |arg| {
+                arg.default_values(values.to_vec()).required(false)
+            });
+        }
+    }
+
+    let subcommands = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect::<Vec<_>>();
+    for subcommand in subcommands {
+        command = command.mut_subcommand(subcommand, |subcommand| {
+            set_defaults(subcommand, id, values, known)
+        });
+    }
+
+    command
+}
+
+/// Layers `DESSERT_*` environment variables over the config file.
+///
+/// `DESSERT_JOBS=2` sets the `jobs` option and double underscores separate
+/// nested keys, as in `DESSERT_EMAIL__API_KEY`. Values that read as TOML
+/// numbers, booleans, arrays or quoted strings get that type, anything else
+/// is taken as a plain string. Variables not naming an option or section are
+/// ignored, as dessert also sets `DESSERT_*` variables for its hooks.
+fn apply_environment(
+    table: &mut toml::Table,
+    vars: impl Iterator<Item = (String, String)>,
+    command: &Command,
+) {
+    for (name, value) in vars {
+        let Some(name) = name.strip_prefix("DESSERT_") else {
+            continue;
+        };
+        let keys = name.split("__").map(str::to_lowercase).collect::<Vec<_>>();
+
+        let known = match keys.as_slice() {
+            [key] => !EARLY_FLAGS.contains(&key.as_str()) && find_arg(command, key).is_some(),
+            [section, ..] => SECTIONS.contains(&section.as_str()),
+            [] => false,
+        };
+        if !known {
+            continue;
+        }
+
+        let value = match toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+        {
+            Some(
+                value @ (toml::Value::String(_)
+                | toml::Value::Integer(_)
+                | toml::Value::Float(_)
+                | toml::Value::Boolean(_)
+                | toml::Value::Array(_)),
+            ) => value,
+            _ => toml::Value::String(value),
+        };
+
+        insert(table, &keys, value);
+    }
+}
+
+/// Reads a config file with the files it includes layered below it.
+///
+/// `include = ["notifications.toml", "shows.d/*.toml"]` lists files, or glob patterns, relative
+/// to the including file. They are merged in order before the including file itself, so later
+/// files override earlier ones: tables are merged key by key, arrays of tables like `[[rule]]`
+/// are appended to, and any other value is replaced.
+fn read(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<toml::Table> {
+    let table = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {path:?}"))?
+        .parse::<toml::Table>()
+        .with_context(|| format!("Failed to parse config file {path:?}"))?;
+    files.push(path.to_path_buf());
+
+    if including.contains(&canonical(path)) {
+        return Err(anyhow!("Config file {path:?} includes itself"));
+    }
+    including.push(canonical(path));
+    let table = include(table, path, including, files);
+    including.pop();
+
+    table
+}
+
+/// Merges the files listed by the `include` key of the table below it, see [`read`]
+fn include(
+    mut table: toml::Table,
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<toml::Table> {
+    let patterns = match table.remove("include") {
+        None => return Ok(table),
+        Some(toml::Value::String(pattern)) => vec![pattern],
+        Some(toml::Value::Array(patterns)) => patterns
+            .into_iter()
+            .map(|pattern| match pattern {
+                toml::Value::String(pattern) => Ok(pattern),
+                _ => Err(anyhow!("Expected include to list file names")),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(_) => return Err(anyhow!("Expected include to list file names")),
+    };
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut merged = toml::Table::new();
+    for pattern in patterns {
+        let pattern = directory.join(pattern);
+        let pattern = pattern
+            .to_str()
+            .ok_or(anyhow!("Invalid include {pattern:?}"))?;
+
+        let matches = glob::glob(pattern)
+            .with_context(|| format!("Invalid include pattern {pattern:?}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read included files")?;
+        // A plain file name has to exist, a pattern may match nothing
+        if matches.is_empty() && !pattern.contains(['*', '?', '[']) {
+            return Err(anyhow!("Included config file {pattern:?} does not exist"));
+        }
+
+        for file in matches {
+            merge(&mut merged, read(&file, including, files)?);
+        }
+    }
+    merge(&mut merged, table);
+
+    Ok(merged)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn merge(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => merge(base, layer),
+            (Some(toml::Value::Array(base)), toml::Value::Array(layer))
+                if base.iter().chain(&layer).all(toml::Value::is_table) =>
+            {
+                base.extend(layer)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Flags read before the config file is loaded, so they can't be set in it
+pub const EARLY_FLAGS: [&str; 2] = ["config", "log_level"];
+
+/// The tables of a config file, as opposed to options, in the order of the fields of [`Config`]
+pub const SECTIONS: [&str; 18] = [
+    "naming",
+    "email",
+    "mqtt",
+    "bazarr",
+    "tmdb",
+    "sftp",
+    "s3",
+    "tracing",
+    "logging",
+    "aliases",
+    "replacements",
+    "rule",
+    "profile",
+    "category",
+    "sources",
+    "retention",
+    "quality",
+    "plugins",
+];
+
+fn insert(table: &mut toml::Table, keys: &[String], value: toml::Value) {
+    match keys {
+        [] => {}
+        [key] => {
+            table.insert(key.clone(), value);
+        }
+        [key, rest @ ..] => {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::Table::new());
+            }
+            if let toml::Value::Table(nested) = entry {
+                insert(nested, rest, value);
+            }
+        }
+    }
+}
+
+/// Finds the value of `--config`, or else of `DESSERT_CONFIG`, or else the config file in
+/// the platform's config directory, before the command line is parsed, as the config
+/// file decides the defaults the command line is parsed with
+pub fn path_from_args(args: &[OsString]) -> Option<PathBuf> {
+    value_from_args(args, "--config")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("DESSERT_CONFIG").map(PathBuf::from))
+        .or_else(|| crate::dirs::config_file().filter(|path| path.is_file()))
+}
+
+/// The level given with `--log-level`, otherwise `RUST_LOG`, overriding the config file's
+pub fn log_level_from_args(args: &[OsString]) -> Option<LevelFilter> {
+    let from_args =
+        value_from_args(args, "--log-level").and_then(|level| level.to_str()?.parse().ok());
+
+    // RUST_LOG may hold directives for several crates, like `dessert=debug,reqwest=info`
+    let from_env = std::env::var("RUST_LOG").ok().and_then(|directives| {
+        directives
+            .rsplit(',')
+            .find_map(|directive| match directive.split_once('=') {
+                Some(("dessert", level)) => level.parse().ok(),
+                Some(_) => None,
+                None => directive.parse().ok(),
+            })
+    });
+
+    from_args.or(from_env)
+}
+
+/// Finds the value of a flag before the arguments are parsed, as `--flag value` or `--flag=value`
+fn value_from_args(args: &[OsString], flag: &str) -> Option<OsString> {
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix(flag)?.strip_prefix('='))
+        {
+            return Some(OsString::from(value));
+        }
+    }
+
+    None
+}
+
+/// The configuration the process was started with
+pub fn current() -> Arc<Config> {
+    CURRENT.read().unwrap().clone()
+}
+
+pub fn set(config: Config) {
+    *CURRENT.write().unwrap() = Arc::new(config);
+}
+
+/// Reloads the config file of a daemon when it changes on disk or the process receives SIGHUP.
+///
+/// Jobs started after a reload use its naming, rules, profiles and
+/// notification settings, while options from the file keep the values the
+/// daemon was started with.
+pub struct Reloader {
+    command: Command,
+    modified: Vec<Option<SystemTime>>,
+    hangup: Arc<AtomicBool>,
+}
+
+impl Reloader {
+    /// Reloads the config of the command, whose flags the options of the file are checked against
+    pub fn new(command: Command) -> Result<Reloader> {
+        let hangup = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        signal_hook::flag::register(signal_hook::consts::SIGHUP, hangup.clone())
+            .context("Failed to install SIGHUP handler")?;
+
+        Ok(Reloader {
+            command,
+            modified: modified(&current().files),
+            hangup,
+        })
+    }
+
+    /// Reloads the config on SIGHUP or when the config file or one it includes has changed
+    pub fn check(&mut self) {
+        let config = current();
+        let Some(path) = &config.path else {
+            return;
+        };
+
+        let times = modified(&config.files);
+        let hangup = self.hangup.swap(false, Ordering::SeqCst);
+        if !hangup && times == self.modified {
+            return;
+        }
+
+        match Config::load(Some(path), &self.command).and_then(|config| {
+            config.apply_to(self.command.clone())?;
+            Ok(config)
+        }) {
+            Ok(config) => {
+                self.modified = modified(&config.files);
+                set(config);
+                crate::status::set_config_error(None);
+                info!("Reloaded config file {:?}", path);
+            }
+            Err(e) => {
+                self.modified = times;
+                crate::status::set_config_error(Some(format!("{e:#}")));
+                error!("Failed to reload config file, keeping the previous one: {e:#}")
+            }
+        }
+    }
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
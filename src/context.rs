@@ -0,0 +1,234 @@
+use crate::events::{self, Event};
+use crate::trace::Trace;
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+/// Job log files opened since the last notification
+static LOGS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT: RefCell<State> = const {
+        RefCell::new(State {
+            current: Current {
+                job: None,
+                phase: None,
+            },
+            trace: None,
+            log: None,
+            warnings: Vec::new(),
+        })
+    };
+}
+
+struct State {
+    current: Current,
+    trace: Option<Trace>,
+    log: Option<JobLog>,
+    /// Warnings and errors logged in the job, for its result
+    warnings: Vec<String>,
+}
+
+struct JobLog {
+    directory: PathBuf,
+    /// Name of the file if the job ends before the release is known
+    fallback_name: String,
+    pending: Vec<String>,
+    path: Option<PathBuf>,
+    file: Option<File>,
+}
+
+impl JobLog {
+    fn open(&mut self, release: &str) -> Result<()> {
+        let name = release
+            .chars()
+            .map(|c| match c {
+                c if c.is_alphanumeric() || "-_.".contains(c) => c,
+                _ => '-',
+            })
+            .collect::<String>();
+        let path = self.directory.join(format!(
+            "{}_{name}.log",
+            chrono::Local::now().format("%Y-%m-%d")
+        ));
+        fs::create_dir_all(&self.directory).context("Failed to create log directory")?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context("Failed to open job log file")?;
+
+        for line in self.pending.drain(..) {
+            let _ = writeln!(file, "{line}");
+        }
+        self.file = Some(file);
+        LOGS.lock().unwrap().push(path.clone());
+        self.path = Some(path);
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) {
+        match &mut self.file {
+            Some(file) => {
+                let _ = writeln!(file, "{line}");
+            }
+            None => self.pending.push(line.to_string()),
+        }
+    }
+}
+
+/// What the current thread is working on, for structured log records
+#[derive(Clone, Copy)]
+pub struct Current {
+    pub job: Option<u64>,
+    pub phase: Option<&'static str>,
+}
+
+pub fn current() -> Current {
+    CURRENT.with_borrow(|state| state.current)
+}
+
+/// Marks the thread as working on the job, the one already entered or a new one, until the
+/// guard is dropped. Entering a new job starts its trace, which is exported when it is left
+pub fn enter_job(id: Option<u64>) -> JobGuard {
+    CURRENT.with_borrow_mut(|state| {
+        let previous = state.current;
+        if previous.job.is_some() {
+            return JobGuard { previous };
+        }
+
+        let job = id.unwrap_or_else(|| NEXT_JOB.fetch_add(1, Ordering::Relaxed));
+        state.current = Current {
+            job: Some(job),
+            phase: None,
+        };
+        state.warnings.clear();
+        if crate::config::current().tracing.is_some() {
+            state.trace = Some(Trace::start(job));
+        }
+
+        JobGuard { previous }
+    })
+}
+
+/// Marks the step of the job the thread is at, e.g. `"extract"`
+pub fn phase(phase: &'static str) {
+    CURRENT.with_borrow_mut(|state| {
+        state.current.phase = Some(phase);
+        if let Some(job) = state.current.job {
+            events::emit(Event::Phase { job, phase });
+        }
+        if let Some(trace) = &mut state.trace {
+            trace.phase(phase);
+        }
+    });
+}
+
+/// Records that the job failed, in its trace
+pub fn failed(error: &str) {
+    CURRENT.with_borrow_mut(|state| {
+        if let Some(trace) = &mut state.trace {
+            trace.fail(error);
+        }
+    });
+}
+
+/// Starts the job's own log file in the log directory. Records are held back until the
+/// release is known and the file is named after it, see [`name_log`]
+pub fn start_log(directory: &Path, fallback_name: &str) {
+    CURRENT.with_borrow_mut(|state| {
+        state.log = Some(JobLog {
+            directory: directory.to_path_buf(),
+            fallback_name: fallback_name.to_string(),
+            pending: Vec::new(),
+            path: None,
+            file: None,
+        })
+    });
+}
+
+/// Opens the log file of the job, named after the date and the release, e.g.
+/// `2024-05-01_Show.Name.S01E02.log`, and writes the records held back so far to it
+pub fn name_log(release: &str) -> Result<()> {
+    let log = CURRENT.with_borrow_mut(|state| state.log.take());
+    let Some(mut log) = log else {
+        return Ok(());
+    };
+
+    // Errors are logged by the caller, which needs the state to not be borrowed
+    let opened = log.open(release);
+    CURRENT.with_borrow_mut(|state| state.log = Some(log));
+    opened
+}
+
+/// Path of the log file of the job the thread is working on, once it is named
+pub fn log_path() -> Option<PathBuf> {
+    CURRENT.with_borrow(|state| state.log.as_ref()?.path.clone())
+}
+
+/// Writes a line to the log of the job the thread is working on, if it has one
+pub fn write_log(line: &str) {
+    // Records logged while the state is borrowed are only missing from the job log
+    let _ = CURRENT.try_with(|state| {
+        if let Ok(mut state) = state.try_borrow_mut() {
+            if let Some(log) = &mut state.log {
+                log.write(line);
+            }
+        }
+    });
+}
+
+/// Records a warning or error logged in the job the thread is working on
+pub fn warned(message: &str) {
+    let _ = CURRENT.try_with(|state| {
+        if let Ok(mut state) = state.try_borrow_mut() {
+            if state.current.job.is_some() {
+                state.warnings.push(message.to_string());
+            }
+        }
+    });
+}
+
+/// The warnings and errors logged in the last job the thread worked on
+pub fn take_warnings() -> Vec<String> {
+    CURRENT.with_borrow_mut(|state| std::mem::take(&mut state.warnings))
+}
+
+/// The job log files opened since the last call, for the notification
+pub fn take_logs() -> Vec<PathBuf> {
+    std::mem::take(&mut *LOGS.lock().unwrap())
+}
+
+pub struct JobGuard {
+    previous: Current,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        let trace = CURRENT.with_borrow_mut(|state| {
+            state.current = self.previous;
+            match self.previous.job {
+                Some(_) => None,
+                None => {
+                    if let Some(mut log) = state.log.take() {
+                        if log.file.is_none() {
+                            let name = log.fallback_name.clone();
+                            let _ = log.open(&name);
+                        }
+                    }
+                    state.trace.take()
+                }
+            }
+        });
+
+        if let (Some(trace), Some(config)) = (trace, &crate::config::current().tracing) {
+            trace.finish(config);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Buffer size of copies when none is given, large enough to keep network filesystems busy
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+/// Copies the file with its permissions, like [`std::fs::copy`]. On Linux the copy is a reflink
+/// sharing the data where the filesystem supports it, like btrfs and XFS, else the kernel copies
+/// it with `copy_file_range` where the filesystems support that, otherwise it goes through a
+/// buffer of `buffer_size` bytes
+pub fn copy(source: &Path, destination: &Path, buffer_size: usize) -> io::Result<u64> {
+    let mut reader = File::open(source)?;
+    let metadata = reader.metadata()?;
+    let mut writer = File::create(destination)?;
+
+    #[cfg(target_os = "linux")]
+    let copied = match reflink(&reader, &writer) {
+        true => metadata.len(),
+        false => match copy_in_kernel(&reader, &writer, metadata.len())? {
+            Some(copied) => copied,
+            None => copy_buffered(&mut reader, &mut writer, buffer_size)?,
+        },
+    };
+    #[cfg(not(target_os = "linux"))]
+    let copied = copy_buffered(&mut reader, &mut writer, buffer_size)?;
+
+    writer.set_permissions(metadata.permissions())?;
+    // Kept like hard links keep it, for comparing with --compare size+mtime
+    writer.set_modified(metadata.modified()?)?;
+    Ok(copied)
+}
+
+/// Joins the files into the destination in order, copying each like [`copy`]. The destination
+/// gets the permissions of the first file and the modification time of the newest
+pub fn concatenate(sources: &[PathBuf], destination: &Path, buffer_size: usize) -> io::Result<u64> {
+    let mut writer = File::create(destination)?;
+    let mut copied = 0;
+
+    for source in sources {
+        let mut reader = File::open(source)?;
+        #[cfg(target_os = "linux")]
+        let in_kernel = copy_in_kernel(&reader, &writer, reader.metadata()?.len())?;
+        #[cfg(not(target_os = "linux"))]
+        let in_kernel = None;
+
+        copied += match in_kernel {
+            Some(copied) => copied,
+            None => copy_buffered(&mut reader, &mut writer, buffer_size)?,
+        };
+    }
+
+    if let Some(first) = sources.first() {
+        writer.set_permissions(first.metadata()?.permissions())?;
+    }
+    writer.set_modified(newest_modified(sources)?)?;
+    Ok(copied)
+}
+
+/// The modification time of the newest of the files
+pub fn newest_modified(files: &[PathBuf]) -> io::Result<SystemTime> {
+    let mut newest = SystemTime::UNIX_EPOCH;
+    for file in files {
+        newest = newest.max(file.metadata()?.modified()?);
+    }
+
+    Ok(newest)
+}
+
+fn copy_buffered(reader: &mut File, writer: &mut File, buffer_size: usize) -> io::Result<u64> {
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut copied = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+
+    Ok(copied)
+}
+
+#[cfg(target_os = "linux")]
+nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+/// Clones the data of the file without copying it, or is false if the filesystem can't, e.g.
+/// if the files are on different filesystems
+#[cfg(target_os = "linux")]
+fn reflink(reader: &File, writer: &File) -> bool {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: FICLONE only reads the source descriptor passed as its argument
+    let cloned = unsafe { ficlone(writer.as_raw_fd(), reader.as_raw_fd() as _) };
+    if let Err(e) = cloned {
+        log::debug!("Failed to reflink, copying instead: {e}");
+    }
+    cloned.is_ok()
+}
+
+/// Copies the file in the kernel, or is `None` if the filesystems can't
+#[cfg(target_os = "linux")]
+fn copy_in_kernel(reader: &File, writer: &File, len: u64) -> io::Result<Option<u64>> {
+    use nix::errno::Errno;
+
+    let mut copied = 0;
+    while copied < len {
+        let chunk = (len - copied).min(1 << 30) as usize;
+        match nix::fcntl::copy_file_range(reader, None, writer, None, chunk) {
+            Ok(0) => break,
+            Ok(written) => copied += written as u64,
+            Err(Errno::EINTR) => {}
+            Err(
+                Errno::ENOSYS | Errno::EXDEV | Errno::EOPNOTSUPP | Errno::EINVAL | Errno::EPERM,
+            ) if copied == 0 => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(Some(copied))
+}
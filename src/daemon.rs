@@ -0,0 +1,399 @@
+use anyhow::{Context, Result};
+use dessert::pipeline::{self, find_other_file, find_rar_file};
+use dessert::queue::Pool;
+use dessert::summary::format_bytes;
+use dessert::summary::Summary;
+use dessert::{context, metrics, retention, space, status, Job, Options, Pipeline};
+use log::{error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Left in a release directory once it is imported, so scans skip it, also after a restart.
+/// Archives directly in the root are marked by a `.<archive>.dessert-done` next to them instead
+const DONE_MARKER: &str = ".dessert-done";
+
+/// Keeps track of the releases a daemon has already handled and runs new ones on a pool
+pub struct Processed {
+    /// The watched or polled directory, whose archives directly in it are processed one by one
+    root: PathBuf,
+    seen: HashSet<PathBuf>,
+    /// Processes releases marked as done again
+    force: bool,
+    /// Sends a notification for every job, else their log is dropped
+    notify: bool,
+    pool: Pool,
+    log_file: Arc<Mutex<File>>,
+    started: Instant,
+    totals: Arc<Mutex<Summary>>,
+    /// Sends the notifications while the pool goes on with the next jobs
+    #[cfg(feature = "async")]
+    runtime: tokio::runtime::Runtime,
+    #[cfg(feature = "async")]
+    sending: Arc<Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+}
+
+impl Processed {
+    pub fn new(
+        root: &Path,
+        jobs: usize,
+        log_file: File,
+        force: bool,
+        notify: bool,
+    ) -> Result<Processed> {
+        Ok(Processed {
+            root: root.to_path_buf(),
+            seen: HashSet::new(),
+            force,
+            notify,
+            pool: Pool::new(jobs),
+            log_file: Arc::new(Mutex::new(log_file)),
+            started: Instant::now(),
+            totals: Arc::default(),
+            #[cfg(feature = "async")]
+            runtime: tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .enable_all()
+                .build()
+                .context("Failed to start the async runtime")?,
+            #[cfg(feature = "async")]
+            sending: Arc::default(),
+        })
+    }
+
+    /// Whether the release was handled, or for the root, every archive directly in it
+    pub fn contains(&self, release: &Path) -> bool {
+        match release == self.root {
+            true => pipeline::archives(release)
+                .iter()
+                .all(|archive| self.handled(archive)),
+            false => self.handled(release),
+        }
+    }
+
+    fn handled(&self, source: &Path) -> bool {
+        self.seen.contains(source) || (!self.force && done_marker(source).exists())
+    }
+
+    /// Runs the pipeline for a release and remembers it, regardless of the outcome. The
+    /// archives directly in the root run as a job each, those not handled yet
+    pub fn process(&mut self, release: &Path, destination_directory: &Path, options: &Options) {
+        if release != self.root {
+            return self.process_source(release, destination_directory, options);
+        }
+        for archive in pipeline::archives(release) {
+            if !self.handled(&archive) {
+                self.process_source(&archive, destination_directory, options);
+            }
+        }
+    }
+
+    fn process_source(&mut self, release: &Path, destination_directory: &Path, options: &Options) {
+        self.seen.insert(release.to_path_buf());
+        if !options.selects(release) {
+            return;
+        }
+
+        let job = Job {
+            source_directory: release.to_path_buf(),
+            destination_directory: destination_directory.to_path_buf(),
+        };
+        let mark = !options.dry_run;
+        let notify = self.notify;
+        let pipeline = Pipeline::new(options.clone()).observe(status::Registry);
+        let log_file = self.log_file.clone();
+        let totals = self.totals.clone();
+        #[cfg(feature = "async")]
+        let (runtime, sending) = (self.runtime.handle().clone(), self.sending.clone());
+
+        metrics::job_queued();
+        self.pool.spawn(move || {
+            let _running = metrics::job_started();
+            let _job = context::enter_job(None);
+            let started = Instant::now();
+            let source = job.source_directory.clone();
+            let imports = Pipeline::jobs(job)
+                .iter()
+                .map(|job| pipeline.run(job))
+                .collect::<Vec<_>>();
+            // A season pack is done once every episode of it is
+            if mark && imports.iter().all(Result::is_ok) {
+                mark_done(&source);
+            }
+
+            let summary = Summary::new(&imports, started.elapsed());
+            totals.lock().unwrap().add(&summary);
+            if !notify {
+                if let Err(e) = dessert::pipeline::read_log(&mut log_file.lock().unwrap()) {
+                    error!("Failed to read log: {e}");
+                }
+                return;
+            }
+
+            #[cfg(feature = "async")]
+            match dessert::pipeline::read_log(&mut log_file.lock().unwrap()) {
+                Ok((log, job_logs)) => sending.lock().unwrap().push(runtime.spawn(async move {
+                    if let Err(e) = pipeline.notify_async(&summary, &log, &job_logs).await {
+                        error!("Failed to send notification: {e}");
+                    }
+                })),
+                Err(e) => error!("Failed to send notification: {e}"),
+            }
+            #[cfg(not(feature = "async"))]
+            if let Err(e) = pipeline.notify(&summary, &mut log_file.lock().unwrap()) {
+                error!("Failed to send notification: {e}");
+            }
+        });
+    }
+
+    /// Waits for the running jobs and their notifications to finish, then reports the totals
+    pub fn finish(&self) {
+        self.pool.wait();
+        #[cfg(feature = "async")]
+        for sending in self.sending.lock().unwrap().drain(..) {
+            let _ = self.runtime.block_on(sending);
+        }
+
+        let mut totals = self.totals.lock().unwrap().clone();
+        totals.elapsed = self.started.elapsed();
+        totals.report();
+    }
+}
+
+/// How often daemons apply the retention policies of the config file
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Applies the retention policies when started and every [`RETENTION_INTERVAL`] after
+pub struct Retention {
+    next: Instant,
+}
+
+impl Retention {
+    pub fn new() -> Retention {
+        Retention {
+            next: Instant::now(),
+        }
+    }
+
+    pub fn check(&mut self, dry_run: bool) {
+        if Instant::now() < self.next {
+            return;
+        }
+
+        self.next = Instant::now() + RETENTION_INTERVAL;
+        retention::apply_all(dry_run);
+    }
+}
+
+/// How often daemons check the free space of the destination for [`SpaceAlert`]
+const SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Warns by email when the destination has less free space than the threshold, once when it
+/// falls below it and then at most once per interval while it stays there
+pub struct SpaceAlert {
+    threshold: u64,
+    interval: Duration,
+    next_check: Instant,
+    /// When the last alert was sent, cleared once there is enough space again
+    alerted: Option<Instant>,
+}
+
+impl SpaceAlert {
+    pub fn new(threshold: u64, interval: Duration) -> SpaceAlert {
+        SpaceAlert {
+            threshold,
+            interval,
+            next_check: Instant::now(),
+            alerted: None,
+        }
+    }
+
+    pub fn check(&mut self, destination_directory: &Path, options: &Options) {
+        if Instant::now() < self.next_check {
+            return;
+        }
+        self.next_check = Instant::now() + SPACE_CHECK_INTERVAL;
+
+        // A destination created by the first import has the free space of its parent
+        let existing = destination_directory
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(destination_directory);
+        let free = match space::available(existing) {
+            Ok(free) => free,
+            Err(e) => {
+                error!(
+                    "Failed to get free space of {:?}: {e}",
+                    destination_directory
+                );
+                return;
+            }
+        };
+        if free >= self.threshold {
+            if self.alerted.take().is_some() {
+                info!(
+                    "{:?} has {} free again",
+                    destination_directory,
+                    format_bytes(free)
+                );
+            }
+            return;
+        }
+        if self
+            .alerted
+            .is_some_and(|alerted| alerted.elapsed() < self.interval)
+        {
+            return;
+        }
+
+        self.alerted = Some(Instant::now());
+        let subject = format!(
+            "Dessert is running out of space: {} free",
+            format_bytes(free)
+        );
+        let text = format!(
+            "{} has {} free, less than the {} of --space-alert. Imports start failing once it \
+             has less than an import needs.",
+            destination_directory.display(),
+            format_bytes(free),
+            format_bytes(self.threshold)
+        );
+        warn!("{text}");
+        if let Some(email) = options.email_client() {
+            match email.and_then(|email| email.send_report(&subject, &text)) {
+                Ok(()) => info!("Sent low space alert"),
+                Err(e) => error!("Failed to send low space alert: {e:#}"),
+            }
+        }
+    }
+}
+
+/// Marks the release as imported, unless it was deleted with its source
+fn mark_done(release: &Path) {
+    if !release.exists() {
+        return;
+    }
+    if let Err(e) = File::create(done_marker(release)) {
+        error!("Failed to mark {:?} as done: {e}", release);
+    }
+}
+
+/// The file marking the release as imported, see [`DONE_MARKER`]
+pub fn done_marker(release: &Path) -> PathBuf {
+    match (release.is_file(), release.parent(), release.file_name()) {
+        (true, Some(directory), Some(name)) => {
+            directory.join(format!(".{}{DONE_MARKER}", name.to_string_lossy()))
+        }
+        _ => release.join(DONE_MARKER),
+    }
+}
+
+/// Maps a changed path below the watched root to the release it belongs to.
+///
+/// Every directory directly inside the root is a release of its own, while
+/// archives placed directly in the root make the root itself the release.
+pub fn release_for(root: &Path, path: &Path) -> Option<PathBuf> {
+    let first = path.strip_prefix(root).ok()?.components().next();
+
+    match first {
+        Some(Component::Normal(name)) if root.join(name).is_dir() => Some(root.join(name)),
+        _ => Some(root.to_path_buf()),
+    }
+}
+
+pub fn is_release(path: &Path) -> bool {
+    path.is_dir() && (find_rar_file(path).is_ok() || find_other_file(path).is_some())
+}
+
+/// Lists every candidate release in the root, see [`release_for`]
+pub fn releases(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut releases = root
+        .read_dir()
+        .context("Failed to read source directory")?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    releases.push(root.to_path_buf());
+
+    Ok(releases)
+}
+
+/// Sums the sizes of the files belonging to a release.
+///
+/// The root only owns the files directly inside it, every other release owns its whole tree.
+pub fn size_of(root: &Path, release: &Path) -> u64 {
+    let Ok(entries) = release.read_dir() else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                (release != root).then(|| size_of(root, &entry.path()))
+            } else {
+                Some(metadata.len())
+            }
+        })
+        .sum()
+}
+
+/// Remembers the candidates a scan found settled without being a release, so later scans skip
+/// them until a directory in them changes, e.g. by an archive being added or renamed into place
+#[derive(Default)]
+pub struct ScanCache {
+    ignored: HashMap<PathBuf, SystemTime>,
+}
+
+impl ScanCache {
+    pub fn new() -> ScanCache {
+        ScanCache::default()
+    }
+
+    /// Whether the candidate was ignored and hasn't changed since. Changed ones are forgotten
+    pub fn unchanged(&mut self, root: &Path, release: &Path) -> bool {
+        let Some(ignored) = self.ignored.get(release) else {
+            return false;
+        };
+        if modified(root, release).is_some_and(|modified| modified == *ignored) {
+            return true;
+        }
+
+        self.ignored.remove(release);
+        false
+    }
+
+    pub fn ignore(&mut self, root: &Path, release: &Path) {
+        if let Some(modified) = modified(root, release) {
+            self.ignored.insert(release.to_path_buf(), modified);
+        }
+    }
+
+    /// Forgets the candidates that are gone from the source directory
+    pub fn retain(&mut self, releases: &[PathBuf]) {
+        let releases = releases.iter().collect::<HashSet<_>>();
+        self.ignored.retain(|release, _| releases.contains(release));
+    }
+}
+
+/// The latest modification time of the release directory and the directories below it, which
+/// change whenever an entry is added, removed or renamed. Files aren't looked at, so this stays
+/// cheap for releases of many files
+fn modified(root: &Path, release: &Path) -> Option<SystemTime> {
+    let mut latest = release.metadata().ok()?.modified().ok()?;
+    if release == root {
+        return Some(latest);
+    }
+
+    for entry in release.read_dir().ok()?.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            latest = latest.max(modified(root, &entry.path())?);
+        }
+    }
+
+    Some(latest)
+}
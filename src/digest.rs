@@ -0,0 +1,136 @@
+use crate::schedule::Schedule;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use dessert::history::{self, Entry, History, Outcome};
+use dessert::release::{Mode, Release};
+use dessert::summary::format_bytes;
+use dessert::Options;
+use log::{error, info};
+use std::collections::HashMap;
+
+/// Number of shows listed in a digest
+const TOP_SHOWS: usize = 5;
+
+/// Sends a digest of the jobs in the history on a schedule, each covering the time since the
+/// one before. The first covers the time since the schedule last fired, also across restarts
+pub struct Digest {
+    schedule: Schedule,
+    since: DateTime<Local>,
+}
+
+impl Digest {
+    pub fn new(expression: &str) -> Result<Digest> {
+        let schedule = Schedule::parse(expression)?;
+        let since = schedule.previous().unwrap_or_else(Local::now);
+
+        Ok(Digest { schedule, since })
+    }
+
+    /// Sends the digest once the schedule is due
+    pub fn check(&mut self, options: &Options) {
+        if !self.schedule.due() {
+            return;
+        }
+
+        let now = Local::now();
+        if let Err(e) = send(options, self.since) {
+            error!("Failed to send digest: {e:#}");
+        }
+        self.since = now;
+    }
+}
+
+fn send(options: &Options, since: DateTime<Local>) -> Result<()> {
+    let history = History::open(&history::database(options.history_database.as_deref())?)?;
+    let entries = history.since(since.with_timezone(&Utc))?;
+    if entries.is_empty() {
+        info!("No jobs since the last digest, skipping it");
+        return Ok(());
+    }
+    let (subject, text) = message(&entries, since);
+
+    match options.email_client() {
+        Some(email) if !options.dry_run => {
+            email
+                .context("Failed to initialize email client")?
+                .send_report(&subject, &text)
+                .context("Failed to send digest email")?;
+            info!("Sent digest of {} jobs", entries.len());
+        }
+        _ => info!("{subject}\n{text}"),
+    }
+
+    Ok(())
+}
+
+/// The subject and text of the digest of the jobs
+fn message(entries: &[Entry], since: DateTime<Local>) -> (String, String) {
+    let (imported, failed): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .filter(|entry| entry.outcome != Outcome::Undone)
+        .partition(|entry| entry.outcome == Outcome::Succeeded);
+    let subject = match failed.len() {
+        0 => format!("Dessert digest: {} served", imported.len()),
+        failures => format!(
+            "Dessert digest: {} served, {failures} ruined",
+            imported.len()
+        ),
+    };
+
+    let mut text = format!(
+        "Since {}\n\n{:<16}{}\n{:<16}{}\n{:<16}{}\n",
+        since.format("%Y-%m-%d %H:%M"),
+        "Imported",
+        imported.len(),
+        "Failed",
+        failed.len(),
+        "Total size",
+        format_bytes(imported.iter().map(|entry| entry.unpacked_size).sum()),
+    );
+
+    let shows = top_shows(&imported);
+    if !shows.is_empty() {
+        text.push_str("\nTop shows\n");
+        for (show, episodes) in shows {
+            text.push_str(&format!("{episodes:>4}  {show}\n"));
+        }
+    }
+    if !imported.is_empty() {
+        text.push_str("\nImported\n");
+        for entry in &imported {
+            text.push_str(&format!("{}\n", entry.name.as_deref().unwrap_or("-")));
+        }
+    }
+    if !failed.is_empty() {
+        text.push_str("\nFailed\n");
+        for entry in &failed {
+            text.push_str(&format!(
+                "{}: {}\n",
+                entry.source_directory.display(),
+                entry.error.as_deref().unwrap_or("-")
+            ));
+        }
+    }
+
+    (subject, text)
+}
+
+/// The shows with the most imported episodes, by the names their releases parse as
+fn top_shows(imported: &[&Entry]) -> Vec<(String, usize)> {
+    let mut episodes = HashMap::<String, usize>::new();
+    for entry in imported {
+        let release = entry
+            .source_directory
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| Release::parse(name, Mode::Episode).ok());
+        if let Some(Release::Episode { name, .. }) = release {
+            *episodes.entry(name).or_default() += 1;
+        }
+    }
+
+    let mut shows = episodes.into_iter().collect::<Vec<_>>();
+    shows.sort_by(|(a, a_episodes), (b, b_episodes)| b_episodes.cmp(a_episodes).then(a.cmp(b)));
+    shows.truncate(TOP_SHOWS);
+    shows
+}
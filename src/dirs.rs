@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Context, Result};
+use directories::ProjectDirs;
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn project() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "dessert")
+}
+
+/// The config file looked for when none is given, e.g. `$XDG_CONFIG_HOME/dessert/config.toml`
+pub fn config_file() -> Option<PathBuf> {
+    project().map(|project| project.config_dir().join("config.toml"))
+}
+
+/// The directory plugins are loaded from when none is configured, e.g.
+/// `$XDG_CONFIG_HOME/dessert/plugins`
+pub fn plugin_dir() -> Option<PathBuf> {
+    project().map(|project| project.config_dir().join("plugins"))
+}
+
+/// The history database used when none is given, e.g. `$XDG_DATA_HOME/dessert/history.db`
+pub fn history_database() -> Result<PathBuf> {
+    let project = project().ok_or(anyhow!(
+        "Failed to find a data directory, pass --history-database"
+    ))?;
+    fs::create_dir_all(project.data_dir()).context("Failed to create data directory")?;
+
+    Ok(project.data_dir().join("history.db"))
+}
+
+/// The directory of the emails that are yet to be sent, e.g. `$XDG_DATA_HOME/dessert/spool`
+pub fn spool_dir() -> Result<PathBuf> {
+    let project = project().ok_or(anyhow!("Failed to find a data directory"))?;
+    let spool = project.data_dir().join("spool");
+    fs::create_dir_all(&spool).context("Failed to create spool directory")?;
+
+    Ok(spool)
+}
+
+/// Directory for files that may be deleted, like the log of a running process
+pub fn cache_dir() -> Option<PathBuf> {
+    let project = project()?;
+    fs::create_dir_all(project.cache_dir()).ok()?;
+
+    Some(project.cache_dir().to_path_buf())
+}
+
+/// Creates the directory and its missing parents, with the permissions of the octal mode on
+/// Unix, or else the defaults of the umask
+pub fn create(directory: &Path, mode: Option<u32>) -> Result<()> {
+    let missing = directory
+        .ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .collect::<Vec<_>>();
+
+    for directory in missing.into_iter().rev() {
+        fs::create_dir(directory)
+            .with_context(|| format!("Failed to create directory {directory:?}"))?;
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(directory, fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set permissions of directory {directory:?}"))?;
+        }
+        info!("Created directory: {:?}", directory);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    Ok(())
+}
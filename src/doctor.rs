@@ -0,0 +1,218 @@
+use crate::RunArgs;
+use anyhow::{anyhow, Context, Result};
+use dessert::history::{self, History};
+use dessert::summary::format_bytes;
+use dessert::{backend, config, extract, mqtt, redact, space};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The checks run so far, printed as they finish
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+}
+
+impl Report {
+    /// Prints the outcome of a check, with the details of a passed one
+    fn check(&mut self, name: &str, result: Result<String>) {
+        match result {
+            Ok(details) if details.is_empty() => {
+                println!("PASS  {name}");
+                self.passed += 1;
+            }
+            Ok(details) => {
+                println!("PASS  {name}: {details}");
+                self.passed += 1;
+            }
+            Err(e) => {
+                println!("FAIL  {name}: {}", redact::redact(&format!("{e:#}")));
+                self.failed += 1;
+            }
+        }
+    }
+
+    fn skip(&self, name: &str, reason: &str) {
+        println!("SKIP  {name}: {reason}");
+    }
+}
+
+/// Checks what `dessert run` with the same flags and config file depends on: that its
+/// directories exist and are writable, that the unrar library works, that the notification
+/// targets and metadata APIs answer with the configured keys and that the destinations have
+/// enough free space
+pub fn doctor(args: &RunArgs) -> Result<()> {
+    let options = &args.options;
+    let config = config::current();
+    let mut report = Report::default();
+
+    let jobs = args.sources.jobs().and_then(|jobs| {
+        jobs.into_iter()
+            .map(|job| options.map_source(job))
+            .collect::<Result<Vec<_>>>()
+    });
+    let jobs = match jobs {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            report.check("Source directories", Err(e));
+            Vec::new()
+        }
+    };
+
+    let mut destinations = BTreeSet::new();
+    for job in &jobs {
+        report.check(
+            &format!("Source {}", job.source_directory.display()),
+            readable(&job.source_directory),
+        );
+        destinations.insert(job.destination_directory.clone());
+    }
+    for destination in &destinations {
+        report.check(
+            &format!("Destination {}", destination.display()),
+            destination_writable(destination, options.create_destination),
+        );
+        report.check(
+            &format!("Free space on {}", destination.display()),
+            free_space(destination, options.min_free_space),
+        );
+    }
+    if let Some(expendable) = &options.expendable_directory {
+        report.check(
+            &format!("Expendable directory {}", expendable.display()),
+            readable(expendable),
+        );
+    }
+
+    report.check(
+        "History database",
+        history::database(options.history_database.as_deref()).and_then(|database| {
+            History::open(&database)?;
+            Ok(database.display().to_string())
+        }),
+    );
+    if let Some(directory) = &config.logging.directory {
+        report.check(
+            &format!("Log directory {}", directory.display()),
+            writable(directory).map(|()| String::new()),
+        );
+    }
+
+    report.check(
+        "Unrar library",
+        extract::check_unrar().map(|()| env!("DESSERT_UNRAR_VERSION").to_string()),
+    );
+
+    match options.email_client() {
+        Some(email) => report.check(
+            "Email",
+            email
+                .and_then(|email| email.check())
+                .map(|()| String::new()),
+        ),
+        None => report.skip("Email", "not configured"),
+    }
+    let mqtt = match &options.mqtt_config_file {
+        Some(path) => Some(mqtt::Config::from_file(path)),
+        None => config.mqtt.clone().map(Ok),
+    };
+    match mqtt {
+        Some(mqtt) => report.check(
+            "MQTT",
+            mqtt.and_then(mqtt::Client::check).map(|()| String::new()),
+        ),
+        None => report.skip("MQTT", "not configured"),
+    }
+    match options.bazarr_client() {
+        Some(bazarr) => report.check(
+            "Bazarr",
+            bazarr
+                .and_then(|bazarr| bazarr.check())
+                .map(|()| String::new()),
+        ),
+        None => report.skip("Bazarr", "not configured"),
+    }
+    let remote = match (&config.sftp, &config.s3) {
+        (Some(sftp), _) => Some(format!("SFTP {}", sftp.host)),
+        (_, Some(s3)) => Some(format!("S3 bucket {}", s3.bucket)),
+        (None, None) => None,
+    };
+    match remote {
+        Some(remote) => report.check(&remote, backend::current().map(|_| String::new())),
+        None => report.skip("Remote destination", "not configured"),
+    }
+    match &config.tmdb {
+        Some(tmdb) => report.check("TMDB", tmdb.check().map(|()| String::new())),
+        None => report.skip("TMDB", "not configured"),
+    }
+
+    println!("{} passed, {} failed", report.passed, report.failed);
+    if report.failed > 0 {
+        return Err(anyhow!("{} checks failed", report.failed));
+    }
+
+    Ok(())
+}
+
+/// Whether the source, a directory or a file, can be read
+fn readable(path: &Path) -> Result<String> {
+    if path.is_dir() {
+        fs::read_dir(path).with_context(|| format!("Failed to read {:?}", path))?;
+    } else {
+        fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    }
+
+    Ok(String::new())
+}
+
+/// Whether files can be created in the directory
+fn writable(directory: &Path) -> Result<()> {
+    if !directory.is_dir() {
+        return Err(anyhow!("{:?} is not a directory", directory));
+    }
+    tempfile::NamedTempFile::new_in(directory)
+        .with_context(|| format!("Failed to create a file in {:?}", directory))?;
+
+    Ok(())
+}
+
+/// Whether the destination is writable, or with `--create-destination` whether it can be
+/// created in the closest directory that exists
+fn destination_writable(destination: &Path, create: bool) -> Result<String> {
+    if destination.exists() {
+        return writable(destination).map(|()| String::new());
+    }
+    if !create {
+        return Err(anyhow!(
+            "{:?} does not exist, create it or pass --create-destination",
+            destination
+        ));
+    }
+
+    let parent = existing_ancestor(destination);
+    writable(&parent)?;
+    Ok(format!("created in {} when needed", parent.display()))
+}
+
+/// The free space of the filesystem of the destination, compared to `--min-free-space`
+fn free_space(destination: &Path, min_free: Option<u64>) -> Result<String> {
+    let free = space::available(&existing_ancestor(destination))
+        .context("Failed to get free space of destination directory")?;
+
+    match min_free {
+        Some(min_free) if free < min_free => Err(anyhow!(
+            "only {} free, less than the {} of --min-free-space",
+            format_bytes(free),
+            format_bytes(min_free)
+        )),
+        _ => Ok(format!("{} free", format_bytes(free))),
+    }
+}
+
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."))
+        .to_path_buf()
+}
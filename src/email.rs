@@ -1,9 +1,12 @@
+use crate::summary::Summary;
+use crate::{redact, secrets};
 use anyhow::{anyhow, Context, Result};
-use serde::Deserialize;
+use log::debug;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Client {
     /// The address of the recipient
     to: String,
@@ -18,45 +21,157 @@ pub struct Client {
     api_key: String,
 }
 
+/// An email ready to be sent, which is what the spool keeps
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+    pub attachments: Vec<PathBuf>,
+}
+
+impl Message {
+    /// The email about a run, with the log files of its jobs attached
+    pub fn of_run(summary: &Summary, log: &str, job_logs: &[PathBuf]) -> Message {
+        let (subject, text) = message(summary, log);
+        Message {
+            html: html(summary, &text),
+            subject,
+            text,
+            attachments: job_logs.to_vec(),
+        }
+    }
+}
+
 impl Client {
     pub fn init_from_file(path: &Path) -> Result<Client> {
         let config = fs::read_to_string(path).context("Failed to read email config file")?;
 
-        toml::from_str::<Client>(&config).context("Failed to parse email config file")
+        let mut config = config
+            .parse::<toml::Table>()
+            .context("Failed to parse email config file")?;
+        secrets::resolve(&mut config, "api_key")?;
+
+        toml::Value::Table(config)
+            .try_into::<Client>()
+            .context("Failed to parse email config file")
+    }
+
+    /// Sends the summary and log of a run, attaching the log files of its jobs
+    pub fn send_email(&self, summary: &Summary, log: &str, job_logs: &[PathBuf]) -> Result<()> {
+        self.send_message(&Message::of_run(summary, log, job_logs))
+    }
+
+    pub fn send_message(&self, message: &Message) -> Result<()> {
+        self.send(
+            &message.subject,
+            &message.text,
+            message.html.as_deref(),
+            &message.attachments,
+        )
+    }
+
+    /// Like [`Client::send_email`], without blocking the thread
+    #[cfg(feature = "async")]
+    pub async fn send_email_async(
+        &self,
+        summary: &Summary,
+        log: &str,
+        job_logs: &[PathBuf],
+    ) -> Result<()> {
+        let (subject, text) = message(summary, log);
+        self.send_async(&subject, &text, html(summary, &text).as_deref(), job_logs)
+            .await
+    }
+
+    /// Sends a report of its own, like the digest of a daemon
+    pub fn send_report(&self, subject: &str, text: &str) -> Result<()> {
+        self.send(subject, text, None, &[])
+    }
+
+    /// Sends an email confirming that the settings work
+    pub fn send_test(&self) -> Result<()> {
+        self.send(
+            "Dessert is ready",
+            "This is a test email sent while setting up dessert.",
+            None,
+            &[],
+        )
     }
 
-    pub fn send_email(&self, file: Option<&str>, log: &str) -> Result<()> {
+    /// Checks that Mailgun accepts the API key for the domain, without sending an email
+    pub fn check(&self) -> Result<()> {
         let url = format!(
-            "{}/{}/messages",
+            "{}/domains/{}",
             self.api_base_path.trim_end_matches('/'),
             self.domain
         );
+        debug!("GET {url}");
+        let response = reqwest::blocking::Client::new()
+            .get(url)
+            .basic_auth("api", Some(&self.api_key))
+            .send()
+            .context("Failed to reach Mailgun")?;
+        debug!("Mailgun responded with {}", response.status());
+
+        if !response.status().is_success() {
+            Err(anyhow!(
+                "Mailgun refused the request: {}",
+                response.status()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/{}/messages",
+            self.api_base_path.trim_end_matches('/'),
+            self.domain
+        )
+    }
+
+    fn fields(&self, subject: &str, text: &str, html: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("from", "Dessert <dessert@mg.jonstodle.no>".to_string()),
+            ("to", self.to.clone()),
+            ("subject", redact::redact(subject).into_owned()),
+            ("text", redact::redact(text).into_owned()),
+        ];
+        if let Some(html) = html {
+            fields.push(("html", redact::redact(html).into_owned()));
+        }
+        fields
+    }
 
-        let subject = match file {
-            Some(file) => format!("Dessert has been served: {}", file),
-            None => "Dessert is ruined".to_string(),
-        };
+    fn send(
+        &self,
+        subject: &str,
+        text: &str,
+        html: Option<&str>,
+        attachments: &[PathBuf],
+    ) -> Result<()> {
+        let url = self.url();
 
+        let mut form = reqwest::blocking::multipart::Form::new();
+        for (name, value) in self.fields(subject, text, html) {
+            form = form.text(name, value);
+        }
+        for attachment in attachments {
+            form = form
+                .file("attachment", attachment)
+                .with_context(|| format!("Failed to attach {attachment:?}"))?;
+        }
+
+        debug!("POST {url} to {} with subject {subject:?}", self.to);
         let response = reqwest::blocking::Client::new()
             .post(url)
             .basic_auth("api", Some(&self.api_key))
-            .multipart(
-                reqwest::blocking::multipart::Form::new()
-                    .text("from", "Dessert <dessert@mg.jonstodle.no>")
-                    .text("to", self.to.clone())
-                    .text("subject", subject)
-                    .text(
-                        "text",
-                        format!(
-                            r"{}
-
-                            {log}",
-                            file.unwrap_or(""),
-                        ),
-                    ),
-            )
+            .multipart(form)
             .send()
             .context("Failed to send email")?;
+        debug!("Mailgun responded with {}", response.status());
 
         if !response.status().is_success() {
             Err(anyhow!("Failed to send email: {}", response.text()?))
@@ -64,4 +179,138 @@ impl Client {
             Ok(())
         }
     }
+
+    #[cfg(feature = "async")]
+    async fn send_async(
+        &self,
+        subject: &str,
+        text: &str,
+        html: Option<&str>,
+        attachments: &[PathBuf],
+    ) -> Result<()> {
+        let url = self.url();
+
+        let mut form = reqwest::multipart::Form::new();
+        for (name, value) in self.fields(subject, text, html) {
+            form = form.text(name, value);
+        }
+        for attachment in attachments {
+            let contents = tokio::fs::read(attachment)
+                .await
+                .with_context(|| format!("Failed to attach {attachment:?}"))?;
+            let part = reqwest::multipart::Part::bytes(contents).file_name(
+                attachment
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            );
+            form = form.part("attachment", part);
+        }
+
+        debug!("POST {url} to {} with subject {subject:?}", self.to);
+        let response = reqwest::Client::new()
+            .post(url)
+            .basic_auth("api", Some(&self.api_key))
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to send email")?;
+        debug!("Mailgun responded with {}", response.status());
+
+        if !response.status().is_success() {
+            Err(anyhow!("Failed to send email: {}", response.text().await?))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The subject and text of the email about a run
+fn message(summary: &Summary, log: &str) -> (String, String) {
+    let files = summary
+        .imported
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let subject = match (files.as_slice(), summary.failed) {
+        ([], _) => "Dessert is ruined".to_string(),
+        (files, 0) => format!("Dessert has been served: {}", files.join(", ")),
+        (files, failures) => format!(
+            "Dessert has been served: {} ({} ruined)",
+            files.join(", "),
+            failures
+        ),
+    };
+
+    let notes = summary
+        .release_notes
+        .iter()
+        .map(|(file_name, notes)| format!("{file_name}\n\n{notes}\n\n"))
+        .collect::<String>();
+    let mut details = summary
+        .upgrades
+        .iter()
+        .map(|(file_name, from, to)| format!("{file_name}: upgraded from {from} to {to}\n"))
+        .collect::<String>();
+    details.extend(
+        summary
+            .repaired
+            .iter()
+            .map(|file_name| format!("{file_name}: repaired from recovery volumes\n")),
+    );
+    details.extend(
+        summary
+            .stats
+            .iter()
+            .map(|(file_name, stats)| format!("{file_name}: {stats}\n")),
+    );
+    if !details.is_empty() {
+        details.push('\n');
+    }
+    let text = format!(
+        r"{}
+
+                {}
+                {details}{notes}{log}",
+        files.join("\n"),
+        summary.table(),
+    );
+
+    (subject, text)
+}
+
+/// The email as HTML with a card of the poster of each import that has one, or `None` if none
+/// has, leaving the plain text on its own
+fn html(summary: &Summary, text: &str) -> Option<String> {
+    if summary.artwork.is_empty() {
+        return None;
+    }
+
+    let cards = summary
+        .artwork
+        .iter()
+        .map(|(file_name, artwork)| {
+            format!(
+                r#"<div style="display: inline-block; margin: 0 16px 16px 0; max-width: 250px; vertical-align: top">
+<img src="{}" alt="" width="250" style="border-radius: 4px">
+<p><strong>{}</strong></p>
+</div>
+"#,
+                escape(artwork),
+                escape(file_name),
+            )
+        })
+        .collect::<String>();
+
+    Some(format!(
+        "<html>\n<body>\n{cards}<pre>{}</pre>\n</body>\n</html>\n",
+        escape(text)
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
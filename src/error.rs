@@ -0,0 +1,65 @@
+use crate::exit::{self, Code};
+use crate::summary::Skipped;
+
+/// Why a job or notification of a [`crate::Pipeline`] failed, for matching on. Each keeps the
+/// error with its context, which it displays as
+#[derive(Debug, thiserror::Error)]
+pub enum DessertError {
+    /// The source or destination directory is missing, or has no archive or video file
+    #[error(transparent)]
+    NotFound(anyhow::Error),
+    /// The release name isn't named the way the parse mode expects
+    #[error(transparent)]
+    ParseFailed(anyhow::Error),
+    #[error(transparent)]
+    ExtractionFailed(anyhow::Error),
+    /// Another dessert process is holding the lock of the destination
+    #[error(transparent)]
+    DestinationConflict(anyhow::Error),
+    #[error(transparent)]
+    NotificationFailed(anyhow::Error),
+    /// The job or the run took longer than `--job-timeout` or `--timeout`
+    #[error(transparent)]
+    TimedOut(anyhow::Error),
+    /// The user chose to skip the release when prompted, or it is already in the library with
+    /// `--on-duplicate skip`
+    #[error("Skipped")]
+    Skipped,
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl DessertError {
+    /// The error with its context, unless the job was skipped
+    pub fn error(&self) -> Option<&anyhow::Error> {
+        match self {
+            DessertError::NotFound(error)
+            | DessertError::ParseFailed(error)
+            | DessertError::ExtractionFailed(error)
+            | DessertError::DestinationConflict(error)
+            | DessertError::NotificationFailed(error)
+            | DessertError::TimedOut(error)
+            | DessertError::Other(error) => Some(error),
+            DessertError::Skipped => None,
+        }
+    }
+}
+
+/// Classifies the error by the exit code it was marked with
+impl From<anyhow::Error> for DessertError {
+    fn from(error: anyhow::Error) -> DessertError {
+        if error.is::<Skipped>() {
+            return DessertError::Skipped;
+        }
+
+        match exit::failure_code(&error) {
+            Some(Code::NoArchive | Code::Verification) => DessertError::NotFound(error),
+            Some(Code::Parse) => DessertError::ParseFailed(error),
+            Some(Code::Extraction) => DessertError::ExtractionFailed(error),
+            Some(Code::Conflict) => DessertError::DestinationConflict(error),
+            Some(Code::Notification) => DessertError::NotificationFailed(error),
+            Some(Code::Timeout) => DessertError::TimedOut(error),
+            None => DessertError::Other(error),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+
+thread_local! {
+    /// The observers of the pipeline running a job on this thread
+    static CURRENT: RefCell<Observers> = RefCell::default();
+}
+
+/// What a job is doing, for progress reporting. `job` is the id of the job in the logs
+#[derive(Debug, Clone)]
+pub enum Event {
+    JobStarted {
+        job: u64,
+        source: PathBuf,
+    },
+    /// The job moved on to the next step, e.g. `"extract"`
+    Phase {
+        job: u64,
+        phase: &'static str,
+    },
+    /// A file of the release was written to the destination
+    EntryExtracted {
+        job: u64,
+        file: PathBuf,
+        bytes: u64,
+    },
+    /// Bytes written to the destination so far, of the total unpacked size of the archive
+    Progress {
+        job: u64,
+        bytes: u64,
+        total: u64,
+    },
+    JobFinished {
+        job: u64,
+        succeeded: bool,
+    },
+}
+
+/// Receives the events of the jobs of a [`crate::Pipeline`], on the threads running them
+pub trait Observer: Send + Sync {
+    fn observe(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> Observer for F {
+    fn observe(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Sends the events to a channel, for receiving them on another thread
+impl Observer for mpsc::Sender<Event> {
+    fn observe(&self, event: &Event) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Observers(Vec<Arc<dyn Observer>>);
+
+impl Observers {
+    pub fn add(&mut self, observer: Arc<dyn Observer>) {
+        self.0.push(observer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn emit(&self, event: &Event) {
+        for observer in &self.0 {
+            observer.observe(event);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+/// Sends the events emitted on this thread to the observers until the guard is dropped
+pub fn observe(observers: Observers) -> ObserveGuard {
+    ObserveGuard {
+        previous: CURRENT.replace(observers),
+    }
+}
+
+/// The observers of the job running on this thread, for emitting from other threads
+pub fn current() -> Observers {
+    CURRENT.with_borrow(Observers::clone)
+}
+
+pub fn emit(event: Event) {
+    CURRENT.with_borrow(|observers| observers.emit(&event));
+}
+
+pub struct ObserveGuard {
+    previous: Observers,
+}
+
+impl Drop for ObserveGuard {
+    fn drop(&mut self) {
+        CURRENT.replace(std::mem::take(&mut self.previous));
+    }
+}
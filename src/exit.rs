@@ -0,0 +1,121 @@
+use crate::error::DessertError;
+use crate::Outcome;
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// Exit codes of the process besides 0 for success, 1 for other errors and 2 for invalid
+/// arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    NoArchive = 3,
+    Parse = 4,
+    Extraction = 5,
+    Verification = 6,
+    Notification = 7,
+    Conflict = 8,
+    Timeout = 9,
+}
+
+/// Every exit code with what it means, for the documentation
+pub const CODES: [(u8, &str); 10] = [
+    (0, "Success"),
+    (1, "Other error"),
+    (2, "Invalid arguments"),
+    (
+        Code::NoArchive as u8,
+        "No archive found in the source directory",
+    ),
+    (Code::Parse as u8, "Failed to parse the release name"),
+    (Code::Extraction as u8, "Failed to extract the archive"),
+    (
+        Code::Verification as u8,
+        "Failed to verify the source and destination directories",
+    ),
+    (
+        Code::Notification as u8,
+        "Failed to send the notification of `dessert notify`, other commands only log failures",
+    ),
+    (
+        Code::Conflict as u8,
+        "Another dessert process is holding the lock of the destination",
+    ),
+    (Code::Timeout as u8, "A job or the run timed out"),
+];
+
+/// Documentation of the exit codes, for the help text
+pub fn help() -> String {
+    CODES
+        .iter()
+        .fold("Exit codes:".to_string(), |help, (code, meaning)| {
+            format!("{help}\n  {code}  {meaning}")
+        })
+}
+
+/// An error that makes the process exit with the code of what failed
+#[derive(Debug)]
+pub struct Failure {
+    pub code: Code,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{:#}", self.error)
+        } else {
+            write!(f, "{}", self.error)
+        }
+    }
+}
+
+impl std::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+pub trait ExitCode<T> {
+    /// Marks the error as the failure of the code
+    fn exit_code(self, code: Code) -> Result<T>;
+}
+
+impl<T> ExitCode<T> for Result<T> {
+    fn exit_code(self, code: Code) -> Result<T> {
+        self.map_err(|error| Failure { code, error }.into())
+    }
+}
+
+/// The code the error was marked with, also through a [`DessertError`] wrapping it
+pub fn failure_code(error: &anyhow::Error) -> Option<Code> {
+    error
+        .chain()
+        .find_map(|error| match error.downcast_ref::<DessertError>() {
+            Some(error) => error.error().and_then(failure_code),
+            None => error.downcast_ref::<Failure>().map(|failure| failure.code),
+        })
+}
+
+/// The exit code of the error, 1 unless it is a [`Failure`]
+pub fn code(error: &anyhow::Error) -> u8 {
+    failure_code(error).map(|code| code as u8).unwrap_or(1)
+}
+
+/// Fails with the code of the first failed job, if any, ignoring skipped jobs
+pub fn check(outcomes: &[Outcome]) -> Result<()> {
+    let failures = outcomes
+        .iter()
+        .filter_map(|outcome| outcome.as_ref().err())
+        .filter_map(DessertError::error)
+        .collect::<Vec<_>>();
+
+    match failures.first() {
+        None => Ok(()),
+        Some(first) => {
+            let error = anyhow!("{} of {} jobs failed", failures.len(), outcomes.len());
+            match failure_code(first) {
+                Some(code) => Err(error).exit_code(code),
+                None => Err(error),
+            }
+        }
+    }
+}
@@ -0,0 +1,1459 @@
+use crate::compare::{self, Checksum, Compare};
+use crate::events::{self, Event};
+use crate::naming::Naming;
+use crate::release::Release;
+use crate::summary::format_bytes;
+use crate::{context, pipe, shutdown, timeout};
+use anyhow::{anyhow, Context, Result};
+use iso9660::{DirectoryEntry, ISODirectory, ISOFile, ISO9660};
+use log::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use unrar::{Archive, FileHeader};
+
+/// A rar archive of `check.txt` holding `dessert`, extracted by [`check_unrar`]
+const CHECK_RAR: &[u8] = include_bytes!("check.rar");
+
+/// How often the bytes written so far are published while a file is written
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What a source file is, deciding how it is imported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Rar,
+    Iso,
+    /// The first part of a video file split into numbered parts
+    Split,
+    Video,
+    /// A directory with the `VIDEO_TS` or `BDMV` directory of a DVD or Blu-ray
+    Disc,
+}
+
+impl Kind {
+    pub fn of(source_file: &Path) -> Kind {
+        if source_file.is_dir() {
+            Kind::Disc
+        } else if is_video(source_file) {
+            Kind::Video
+        } else if is_iso(source_file) {
+            Kind::Iso
+        } else if is_split(source_file) {
+            Kind::Split
+        } else {
+            Kind::Rar
+        }
+    }
+}
+
+/// What happens to the video files of an archive besides the main one, its largest, e.g. the
+/// featurettes of a movie
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Extras {
+    /// Only the main video file is imported
+    #[default]
+    Skip,
+    /// The others are imported with their own names into an `extras` directory next to it
+    Import,
+}
+
+/// How the files of an archive are laid out in the destination
+#[derive(Debug, Clone, Copy)]
+pub enum Layout<'a> {
+    /// The main video file is named by the file name, the others as the [`VideoLayout`] says
+    Video(VideoLayout),
+    /// Every file goes into the album directory named by the file name, the tracks named by
+    /// the track template of the naming and the others by their own names
+    Album(&'a Naming),
+}
+
+/// How the files of an archive besides its main video file are imported
+#[derive(Debug, Clone, Copy)]
+pub struct VideoLayout {
+    pub extras: Extras,
+    /// Video files smaller than this size in bytes are never imported
+    pub min_size: u64,
+    /// Whether the other files keep their paths in the archive, next to the main video file,
+    /// instead of being named by the file name or going into the `extras` directory
+    pub preserve_structure: bool,
+}
+
+impl<'a> Layout<'a> {
+    pub fn of(release: &Release, naming: &'a Naming, video: VideoLayout) -> Layout<'a> {
+        match release {
+            Release::Album { .. } => Layout::Album(naming),
+            _ => Layout::Video(video),
+        }
+    }
+
+    /// The size in bytes video files need to be imported
+    pub fn min_video_size(&self) -> u64 {
+        match self {
+            Layout::Video(video) => video.min_size,
+            Layout::Album(_) => 0,
+        }
+    }
+
+    /// Whether every file of the archive is checked for an existing destination file. Otherwise
+    /// the first existing one means the archive was extracted before
+    fn checks_each_file(&self) -> bool {
+        match self {
+            Layout::Video(video) => video.preserve_structure,
+            Layout::Album(_) => true,
+        }
+    }
+}
+
+/// Extensions of the audio files of albums
+const AUDIO_EXTENSIONS: [&str; 9] = [
+    "flac", "mp3", "m4a", "ogg", "opus", "wav", "aiff", "ape", "wv",
+];
+
+/// What happens to an archive entry's destination file
+pub enum Action {
+    Extract,
+    Replace,
+    Skip,
+}
+
+impl Action {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Action::Extract => "extract",
+            Action::Replace => "replace",
+            Action::Skip => "skip existing",
+        }
+    }
+}
+
+/// Extracts the archive, removing the files it created when extraction fails or is aborted.
+/// With a pipe command, each file is piped to it instead of written, see [`pipe::run`]
+pub fn extract_rar_file(
+    rar_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+    pipe_to: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+
+    let destination_directory = &long_path(destination_directory)?;
+    let result = extract_entries(
+        rar_file,
+        destination_directory,
+        file_name,
+        compare,
+        layout,
+        pipe_to,
+        &mut created,
+    );
+    if result.is_err() {
+        remove_partial(created);
+    }
+
+    result
+}
+
+fn remove_partial(created: Vec<PathBuf>) {
+    for file in created {
+        match std::fs::remove_file(&file) {
+            Ok(()) => info!("Removed partially extracted file: {:?}", file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => error!("Failed to remove partially extracted file {:?}: {e}", file),
+        }
+    }
+}
+
+fn extract_entries(
+    rar_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+    pipe_to: Option<&str>,
+    created: &mut Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    let job = context::current().job.unwrap_or_default();
+    let videos = Videos::of(rar_file, layout)?;
+    let total = unpacked_size(rar_file, &videos).unwrap_or(0);
+    let mut done = 0;
+    let mut files = Vec::new();
+    let mut extra_files = Vec::new();
+    let mut largest_disc_video = None;
+
+    let mut archive = Archive::new(rar_file)
+        .open_for_processing()
+        .context("Failed to open rar file for processing")?;
+
+    while let Some(header) = archive.read_header().context("Failed to read rar")? {
+        interrupted()?;
+        debug!("Read rar header: {:?}", header.entry());
+
+        archive = if is_symlink(header.entry()) {
+            warn!("Skipping symlink in archive: {:?}", header.entry().filename);
+            header.skip().context("Failed to skip rar file header")?
+        } else if header.entry().is_file() {
+            let Some(destination) =
+                target(header.entry(), destination_directory, file_name, &videos)?
+            else {
+                info!("Skipping file not imported: {:?}", header.entry().filename);
+                archive = header.skip().context("Failed to skip rar file header")?;
+                continue;
+            };
+            let bytes = header.entry().unpacked_size as u64;
+            let disc_video =
+                videos.in_disc(header.entry()).is_some() && is_disc_video(&header.entry().filename);
+
+            match action_for(header.entry(), &destination, compare)? {
+                Action::Extract => {
+                    if let Some(directory) = destination.parent().filter(|parent| !parent.is_dir())
+                    {
+                        std::fs::create_dir_all(directory)
+                            .context("Failed to create destination directory")?;
+                    }
+                }
+                Action::Replace => {
+                    std::fs::remove_file(&destination)
+                        .context("Failed to remove existing destination file")?;
+                    info!("Removed existing destination file: {:?}", destination)
+                }
+                Action::Skip => {
+                    info!("Skipping existing destination file: {:?}", destination);
+                    if disc_video {
+                        note_largest(&mut largest_disc_video, files.len(), bytes);
+                    }
+                    files.push(destination);
+                    if !videos.checks_each_file() {
+                        break;
+                    }
+                    archive = header.skip().context("Failed to skip rar file header")?;
+                    continue;
+                }
+            }
+
+            created.push(destination.clone());
+            let extra = videos.is_extra(header.entry());
+            let archive = with_progress(job, done, total, &destination, || match pipe_to {
+                Some(command) => pipe::run_through_path(command, &destination, |path| {
+                    header
+                        .extract_to(path)
+                        .context("Failed to extract rar file")
+                }),
+                None => header
+                    .extract_to(&destination)
+                    .context("Failed to extract rar file"),
+            })?;
+            done += bytes;
+            events::emit(Event::EntryExtracted {
+                job,
+                file: destination.clone(),
+                bytes,
+            });
+            // Piped files are only imported when the command wrote them to the destination
+            match extra {
+                _ if pipe_to.is_some() && !destination.exists() => {}
+                true => extra_files.push(destination),
+                false => {
+                    if disc_video {
+                        note_largest(&mut largest_disc_video, files.len(), bytes);
+                    }
+                    files.push(destination)
+                }
+            }
+            archive
+        } else {
+            header.skip().context("Failed to skip rar file header")?
+        };
+    }
+    // The largest video of a disc stands for the import, as the files are in archive order
+    if let Some((index, _)) = largest_disc_video {
+        let main = files.remove(index);
+        files.insert(0, main);
+    }
+    // After the main video file, which is the destination of the import
+    files.append(&mut extra_files);
+
+    Ok(files)
+}
+
+/// Fails once a shutdown aborts the extraction or `--timeout` expired, between the files
+fn interrupted() -> Result<()> {
+    if shutdown::aborted() {
+        return Err(anyhow!("Extraction aborted by shutdown"));
+    }
+    if let Some(expired) = timeout::expired() {
+        return Err(anyhow!(
+            "Extraction timed out after {}",
+            humantime::format_duration(expired)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Keeps the index and size of the file if it is larger than the largest so far
+fn note_largest(largest: &mut Option<(usize, u64)>, index: usize, size: u64) {
+    if largest.is_none_or(|(_, largest)| size > largest) {
+        *largest = Some((index, size));
+    }
+}
+
+/// Writes the destination file, publishing the bytes written so far while observed
+fn with_progress<T>(
+    job: u64,
+    done: u64,
+    total: u64,
+    destination: &Path,
+    write: impl FnOnce() -> T,
+) -> T {
+    let observers = events::current();
+    if observers.is_empty() {
+        return write();
+    }
+
+    let (finished, finishing) = mpsc::channel::<()>();
+    let observers = &observers;
+    thread::scope(|scope| {
+        scope.spawn(move || loop {
+            let written = destination.metadata().map_or(0, |metadata| metadata.len());
+            observers.emit(&Event::Progress {
+                job,
+                bytes: done + written,
+                total,
+            });
+            if finishing.recv_timeout(PROGRESS_INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                break;
+            }
+        });
+
+        let result = write();
+        drop(finished);
+        result
+    })
+}
+
+/// The files of a piped import, the destination if the pipe command wrote it
+fn piped(destination: PathBuf, job: u64, bytes: u64) -> Vec<PathBuf> {
+    events::emit(Event::EntryExtracted {
+        job,
+        file: destination.clone(),
+        bytes,
+    });
+
+    match destination.exists() {
+        true => vec![destination],
+        false => Vec::new(),
+    }
+}
+
+/// Whether the file is a volume of a rar archive, `.rar` or `.r00` and on
+pub fn is_volume(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(|ext| {
+            ext == "rar"
+                || (ext.len() == 3
+                    && ext.starts_with('r')
+                    && ext[1..].chars().all(|c| c.is_ascii_digit()))
+        })
+        .unwrap_or(false)
+}
+
+/// Extensions of the video files a source can be instead of an archive
+const VIDEO_EXTENSIONS: [&str; 8] = ["mkv", "mp4", "avi", "m4v", "mov", "wmv", "ts", "webm"];
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Hard links the video file into the destination, copying it where linking isn't possible,
+/// e.g. across filesystems, or pipes it to the pipe command
+pub fn link_video_file(
+    video_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    buffer_size: usize,
+    pipe_to: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let destination = video_destination(video_file, &long_path(destination_directory)?, file_name)?;
+    let size = video_file
+        .metadata()
+        .context("Failed to read file size of video file")?
+        .len();
+
+    match video_action(video_file, &destination, compare)? {
+        Action::Extract => {}
+        Action::Replace => {
+            std::fs::remove_file(&destination)
+                .context("Failed to remove existing destination file")?;
+            info!("Removed existing destination file: {:?}", destination)
+        }
+        Action::Skip => {
+            info!("Skipping existing destination file: {:?}", destination);
+            return Ok(vec![destination]);
+        }
+    }
+
+    let job = context::current().job.unwrap_or_default();
+    if let Some(command) = pipe_to {
+        pipe::run(command, &destination, |stdin| {
+            io::copy(&mut File::open(video_file)?, stdin).map(|_| ())
+        })?;
+        return Ok(piped(destination, job, size));
+    }
+    match std::fs::hard_link(video_file, &destination) {
+        Ok(()) => info!("Linked video file to {:?}", destination),
+        Err(e) => {
+            debug!("Failed to hard link video file, copying it: {e}");
+            let copied = with_progress(job, 0, size, &destination, || {
+                crate::copy::copy(video_file, &destination, buffer_size)
+            });
+            if let Err(e) = copied {
+                let _ = std::fs::remove_file(&destination);
+                return Err(e).context("Failed to copy video file");
+            }
+            info!("Copied video file to {:?}", destination);
+        }
+    }
+    events::emit(Event::EntryExtracted {
+        job,
+        file: destination.clone(),
+        bytes: size,
+    });
+
+    Ok(vec![destination])
+}
+
+/// Lists what [`link_video_file`] would do, without touching the filesystem
+pub fn plan_video_file(
+    video_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+) -> Result<Vec<(PathBuf, Action)>> {
+    let destination = video_destination(video_file, destination_directory, file_name)?;
+    let action = video_action(video_file, &destination, compare)?;
+
+    Ok(vec![(destination, action)])
+}
+
+fn video_destination(
+    video_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+) -> Result<PathBuf> {
+    let file_extension = video_file
+        .extension()
+        .ok_or(anyhow!("Failed to get file extension of video file"))?;
+
+    destination(destination_directory, file_name, file_extension)
+}
+
+fn video_action(video_file: &Path, destination: &Path, compare: Compare) -> Result<Action> {
+    let metadata = video_file
+        .metadata()
+        .context("Failed to read metadata of video file")?;
+    let source = compare::Source {
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        checksum: Checksum::File(video_file),
+    };
+
+    compare::action(source, destination, compare)
+}
+
+/// Whether the file is a part of a video file split into numbered parts, like `movie.mkv.001`
+pub fn is_split(path: &Path) -> bool {
+    let numbered = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.len() == 3 && ext.chars().all(|c| c.is_ascii_digit()));
+
+    numbered && path.file_stem().map(Path::new).is_some_and(is_video)
+}
+
+/// The parts of the split video file, in order
+pub fn split_parts(split_file: &Path) -> Result<Vec<PathBuf>> {
+    let stem = split_file.file_stem();
+    let directory = split_file.parent().unwrap_or(Path::new("."));
+
+    let mut parts = directory
+        .read_dir()
+        .context("Failed to read source directory")?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_split(path) && path.file_stem() == stem)
+        .collect::<Vec<_>>();
+    parts.sort();
+
+    // Parts are numbered from 000 or 001 on
+    let first = parts
+        .first()
+        .and_then(|part| part_number(part))
+        .unwrap_or(0);
+    for (index, part) in parts.iter().enumerate() {
+        let expected = first + index as u32;
+        if part_number(part) != Some(expected) {
+            return Err(anyhow!(
+                "Part {expected:03} of split file {:?} is missing",
+                stem.unwrap_or_default()
+            ));
+        }
+    }
+
+    Ok(parts)
+}
+
+fn part_number(part: &Path) -> Option<u32> {
+    part.extension()?.to_str()?.parse().ok()
+}
+
+/// Joins the parts of the split video file into the destination, or pipes them to the pipe
+/// command
+pub fn join_split_file(
+    split_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    buffer_size: usize,
+    pipe_to: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let parts = split_parts(split_file)?;
+    info!("Found {} parts of split file", parts.len());
+    let destination = split_destination(split_file, &long_path(destination_directory)?, file_name)?;
+
+    match split_action(&parts, &destination, compare)? {
+        Action::Extract => {}
+        Action::Replace => {
+            std::fs::remove_file(&destination)
+                .context("Failed to remove existing destination file")?;
+            info!("Removed existing destination file: {:?}", destination)
+        }
+        Action::Skip => {
+            info!("Skipping existing destination file: {:?}", destination);
+            return Ok(vec![destination]);
+        }
+    }
+
+    let job = context::current().job.unwrap_or_default();
+    let size = parts_size(&parts)?;
+    if let Some(command) = pipe_to {
+        pipe::run(command, &destination, |stdin| {
+            for part in &parts {
+                io::copy(&mut File::open(part)?, stdin)?;
+            }
+            Ok(())
+        })?;
+        return Ok(piped(destination, job, size));
+    }
+    let joined = with_progress(job, 0, size, &destination, || {
+        crate::copy::concatenate(&parts, &destination, buffer_size)
+    });
+    if let Err(e) = joined {
+        let _ = std::fs::remove_file(&destination);
+        return Err(e).context("Failed to join split file");
+    }
+    info!("Joined split file to {:?}", destination);
+    events::emit(Event::EntryExtracted {
+        job,
+        file: destination.clone(),
+        bytes: size,
+    });
+
+    Ok(vec![destination])
+}
+
+/// Lists what [`join_split_file`] would do, without touching the filesystem
+pub fn plan_split_file(
+    split_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+) -> Result<Vec<(PathBuf, Action)>> {
+    let parts = split_parts(split_file)?;
+    let destination = split_destination(split_file, destination_directory, file_name)?;
+    let action = split_action(&parts, &destination, compare)?;
+
+    Ok(vec![(destination, action)])
+}
+
+fn split_destination(
+    split_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+) -> Result<PathBuf> {
+    let file_extension = split_file
+        .file_stem()
+        .map(Path::new)
+        .and_then(Path::extension)
+        .ok_or(anyhow!("Failed to get file extension of split file"))?;
+
+    destination(destination_directory, file_name, file_extension)
+}
+
+fn parts_size(parts: &[PathBuf]) -> Result<u64> {
+    parts
+        .iter()
+        .map(|part| part.metadata().map(|metadata| metadata.len()))
+        .sum::<io::Result<u64>>()
+        .context("Failed to read file size of split file")
+}
+
+fn split_action(parts: &[PathBuf], destination: &Path, compare: Compare) -> Result<Action> {
+    let mut reader: Box<dyn io::Read> = Box::new(io::empty());
+    for part in parts {
+        let file = File::open(part).context("Failed to open part of split file")?;
+        reader = Box::new(reader.chain(file));
+    }
+
+    let source = compare::Source {
+        size: parts_size(parts)?,
+        modified: crate::copy::newest_modified(parts).ok(),
+        checksum: Checksum::Reader(reader),
+    };
+
+    compare::action(source, destination, compare)
+}
+
+/// Extensions of the video files looked for in disc images, besides the ones of [`is_video`]
+const DISC_VIDEO_EXTENSIONS: [&str; 2] = ["m2ts", "vob"];
+
+pub fn is_iso(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("iso"))
+}
+
+/// Extracts the main video file of the disc image, its largest one, or pipes it to the pipe
+/// command
+pub fn extract_iso_file(
+    iso_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    min_video_size: u64,
+    pipe_to: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let video = main_video(iso_file, min_video_size)?;
+    info!("Found video file in ISO image: {:?}", video.identifier);
+    let destination = iso_destination(&video, &long_path(destination_directory)?, file_name)?;
+
+    match iso_action(&video, &destination, compare)? {
+        Action::Extract => {}
+        Action::Replace => {
+            std::fs::remove_file(&destination)
+                .context("Failed to remove existing destination file")?;
+            info!("Removed existing destination file: {:?}", destination)
+        }
+        Action::Skip => {
+            info!("Skipping existing destination file: {:?}", destination);
+            return Ok(vec![destination]);
+        }
+    }
+
+    let job = context::current().job.unwrap_or_default();
+    let size = video.size() as u64;
+    if let Some(command) = pipe_to {
+        pipe::run(command, &destination, |stdin| {
+            io::copy(&mut video.read(), stdin).map(|_| ())
+        })?;
+        return Ok(piped(destination, job, size));
+    }
+    let written = with_progress(job, 0, size, &destination, || {
+        let mut writer = File::create(&destination)?;
+        io::copy(&mut video.read(), &mut writer)?;
+        writer.set_modified(video.time().into())
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&destination);
+        return Err(e).context("Failed to extract video file from ISO image");
+    }
+    info!("Extracted video file to {:?}", destination);
+    events::emit(Event::EntryExtracted {
+        job,
+        file: destination.clone(),
+        bytes: size,
+    });
+
+    Ok(vec![destination])
+}
+
+/// Lists what [`extract_iso_file`] would do, without touching the filesystem
+pub fn plan_iso_file(
+    iso_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    min_video_size: u64,
+) -> Result<Vec<(PathBuf, Action)>> {
+    let video = main_video(iso_file, min_video_size)?;
+    let destination = iso_destination(&video, destination_directory, file_name)?;
+    let action = iso_action(&video, &destination, compare)?;
+
+    Ok(vec![(destination, action)])
+}
+
+/// The largest video file of the image, e.g. the movie rather than its extras, if it is at least
+/// the minimum size in bytes
+fn main_video(iso_file: &Path, min_size: u64) -> Result<ISOFile<File>> {
+    let file = File::open(iso_file).context("Failed to open ISO image")?;
+    let image = ISO9660::new(file).context(
+        "Failed to read ISO image, only ISO9660 and UDF images with an ISO9660 bridge are supported",
+    )?;
+
+    let mut videos = Vec::new();
+    disc_videos(&image.root, &mut videos).context("Failed to read ISO image")?;
+
+    let video = videos
+        .into_iter()
+        .max_by_key(ISOFile::size)
+        .ok_or(anyhow!("Failed to find a video file in ISO image"))?;
+    if (video.size() as u64) < min_size {
+        return Err(anyhow!(
+            "No video file in the ISO image is at least {}",
+            format_bytes(min_size)
+        ));
+    }
+
+    Ok(video)
+}
+
+fn disc_videos(
+    directory: &ISODirectory<File>,
+    videos: &mut Vec<ISOFile<File>>,
+) -> iso9660::Result<()> {
+    for entry in directory.contents() {
+        match entry? {
+            DirectoryEntry::Directory(directory) => {
+                if directory.identifier != "." && directory.identifier != ".." {
+                    disc_videos(&directory, videos)?;
+                }
+            }
+            DirectoryEntry::File(file) => {
+                let path = Path::new(&file.identifier);
+                if is_disc_video(path) || is_video(path) {
+                    videos.push(file);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn iso_destination(
+    video: &ISOFile<File>,
+    destination_directory: &Path,
+    file_name: &str,
+) -> Result<PathBuf> {
+    // Names in disc images are usually upper case
+    let file_extension = Path::new(&video.identifier)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .ok_or(anyhow!(
+            "Failed to get file extension of video file in ISO image"
+        ))?;
+
+    destination(
+        destination_directory,
+        file_name,
+        OsStr::new(&file_extension),
+    )
+}
+
+fn iso_action(video: &ISOFile<File>, destination: &Path, compare: Compare) -> Result<Action> {
+    let source = compare::Source {
+        size: video.size() as u64,
+        modified: Some(video.time().into()),
+        checksum: Checksum::Reader(Box::new(video.read())),
+    };
+
+    compare::action(source, destination, compare)
+}
+
+/// Directories of DVD and Blu-ray structures, which make a source a disc
+const DISC_DIRECTORIES: [&str; 2] = ["VIDEO_TS", "BDMV"];
+
+/// Directories of disc structures that are imported, along with [`DISC_DIRECTORIES`]
+const DISC_TREES: [&str; 4] = ["VIDEO_TS", "AUDIO_TS", "BDMV", "CERTIFICATE"];
+
+/// The directory of the archive entry's disc structure, the path before its `VIDEO_TS` or `BDMV`
+fn disc_directory(path: &Path) -> Option<PathBuf> {
+    let components = path.components().collect::<Vec<_>>();
+    let position = components[..components.len().saturating_sub(1)]
+        .iter()
+        .position(|component| {
+            matches!(component, Component::Normal(name) if is_named(name, &DISC_DIRECTORIES))
+        })?;
+
+    Some(components[..position].iter().collect())
+}
+
+fn is_named(name: &OsStr, names: &[&str]) -> bool {
+    names.iter().any(|other| name.eq_ignore_ascii_case(other))
+}
+
+fn is_disc_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| DISC_VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Whether the directory has the `VIDEO_TS` or `BDMV` directory of a DVD or Blu-ray
+pub fn is_disc(directory: &Path) -> bool {
+    directory.read_dir().is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| is_named(&entry.file_name(), &DISC_DIRECTORIES) && entry.path().is_dir())
+    })
+}
+
+/// The directories of the disc structures of the directory, like its `VIDEO_TS`
+pub fn disc_trees(disc: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = disc.read_dir() else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| is_named(&entry.file_name(), &DISC_TREES) && entry.path().is_dir())
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Links the disc structure of the directory into the directory `file_name` of the
+/// destination, copying files where linking isn't possible, like `Movie (2020)/VIDEO_TS/...`
+pub fn copy_disc(
+    disc: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    min_video_size: u64,
+    buffer_size: usize,
+    pipe_to: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    if pipe_to.is_some() {
+        return Err(anyhow!(
+            "Disc structures can't be piped, as they are more than one file"
+        ));
+    }
+    let destination_directory = &long_path(destination_directory)?;
+    let files = disc_files(disc, min_video_size)?;
+    let total = files.iter().map(|(_, size)| size).sum();
+
+    let job = context::current().job.unwrap_or_default();
+    let mut done = 0;
+    let mut created = Vec::new();
+    let mut copied = Vec::new();
+    for (relative, size) in files {
+        let source = disc.join(&relative);
+        let destination = disc_destination(&relative, destination_directory, file_name)?;
+        let result = interrupted().and_then(|()| {
+            copy_disc_file(&source, &destination, compare, &mut created, || {
+                with_progress(job, done, total, &destination, || {
+                    crate::copy::copy(&source, &destination, buffer_size)
+                })
+            })
+        });
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Skipping existing destination file: {:?}", destination);
+                copied.push(destination);
+                continue;
+            }
+            Err(e) => {
+                remove_partial(created);
+                return Err(e);
+            }
+        }
+        done += size;
+        events::emit(Event::EntryExtracted {
+            job,
+            file: destination.clone(),
+            bytes: size,
+        });
+        copied.push(destination);
+    }
+    info!(
+        "Linked or copied {} files of disc structure to {:?}",
+        copied.len(),
+        destination_directory.join(file_name)
+    );
+
+    Ok(copied)
+}
+
+/// Links the file of a disc, or copies it, giving whether it did or the destination already
+/// has it. The destination is added to `created` before it is written, so a partial copy is
+/// removed
+fn copy_disc_file(
+    source: &Path,
+    destination: &Path,
+    compare: Compare,
+    created: &mut Vec<PathBuf>,
+    copy: impl FnOnce() -> io::Result<u64>,
+) -> Result<bool> {
+    match video_action(source, destination, compare)? {
+        Action::Extract => {
+            if let Some(directory) = destination.parent().filter(|parent| !parent.is_dir()) {
+                std::fs::create_dir_all(directory)
+                    .context("Failed to create destination directory")?;
+            }
+        }
+        Action::Replace => {
+            std::fs::remove_file(destination)
+                .context("Failed to remove existing destination file")?;
+            info!("Removed existing destination file: {:?}", destination)
+        }
+        Action::Skip => return Ok(false),
+    }
+
+    created.push(destination.to_path_buf());
+    if let Err(e) = std::fs::hard_link(source, destination) {
+        debug!("Failed to hard link {:?}, copying it: {e}", source);
+        copy().with_context(|| format!("Failed to copy {:?}", source))?;
+    }
+
+    Ok(true)
+}
+
+/// Lists what [`copy_disc`] would do, without touching the filesystem
+pub fn plan_disc(
+    disc: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    min_video_size: u64,
+) -> Result<Vec<(PathBuf, Action)>> {
+    disc_files(disc, min_video_size)?
+        .into_iter()
+        .map(|(relative, _)| {
+            let destination = disc_destination(&relative, destination_directory, file_name)?;
+            let action = video_action(&disc.join(relative), &destination, compare)?;
+            Ok((destination, action))
+        })
+        .collect()
+}
+
+/// The size of the files of the disc structures of the directory
+pub fn disc_size(disc: &Path) -> u64 {
+    disc_files(disc, 0).map_or(0, |files| files.iter().map(|(_, size)| size).sum())
+}
+
+/// The files of the disc structures of the directory, by their paths below it with their
+/// sizes, the largest video file first so it stands for the import
+fn disc_files(disc: &Path, min_video_size: u64) -> Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    for entry in disc
+        .read_dir()
+        .context("Failed to read source directory")?
+        .flatten()
+    {
+        if is_named(&entry.file_name(), &DISC_TREES) && entry.path().is_dir() {
+            list_files(disc, &entry.path(), &mut files)?;
+        }
+    }
+    files.sort();
+
+    let main = files
+        .iter()
+        .enumerate()
+        .filter(|(_, (file, _))| is_disc_video(file))
+        .max_by_key(|(_, (_, size))| *size)
+        .map(|(index, (_, size))| (index, *size))
+        .ok_or(anyhow!("Failed to find a video file in the disc structure"))?;
+    if main.1 < min_video_size {
+        return Err(anyhow!(
+            "No video file of the disc structure is at least {}",
+            format_bytes(min_video_size)
+        ));
+    }
+    let main = files.remove(main.0);
+    files.insert(0, main);
+
+    Ok(files)
+}
+
+fn list_files(disc: &Path, directory: &Path, files: &mut Vec<(PathBuf, u64)>) -> Result<()> {
+    for entry in directory
+        .read_dir()
+        .with_context(|| format!("Failed to read {:?}", directory))?
+        .flatten()
+    {
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        if file_type.is_symlink() {
+            warn!("Skipping symlink in disc structure: {:?}", path);
+        } else if file_type.is_dir() {
+            list_files(disc, &path, files)?;
+        } else {
+            let size = entry
+                .metadata()
+                .with_context(|| format!("Failed to read metadata of {:?}", path))?
+                .len();
+            let relative = path.strip_prefix(disc).unwrap_or(&path).to_path_buf();
+            files.push((relative, size));
+        }
+    }
+
+    Ok(())
+}
+
+/// Where a file of a disc goes, at its path below the directory of the disc in the directory
+/// `file_name`
+fn disc_destination(
+    relative: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+) -> Result<PathBuf> {
+    let file = Path::new(file_name).join(relative);
+    check_file_name(&file)?;
+
+    Ok(destination_directory.join(file))
+}
+
+/// The size of the files in the archive once extracted
+fn unpacked_size(rar_file: &Path, videos: &Videos) -> Result<u64> {
+    let archive = Archive::new(rar_file)
+        .open_for_listing()
+        .context("Failed to open rar file for listing")?;
+
+    // Files split across volumes are listed once per volume
+    let mut sizes = HashMap::new();
+    for entry in archive {
+        let entry = entry.context("Failed to read rar")?;
+        if entry.is_file() && !is_symlink(&entry) && videos.imports(&entry) {
+            sizes.insert(entry.filename.clone(), entry.unpacked_size as u64);
+        }
+    }
+
+    Ok(sizes.values().sum())
+}
+
+/// Lists what [`extract_rar_file`] would do, without touching the filesystem
+pub fn plan_rar_file(
+    rar_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+) -> Result<Vec<(PathBuf, Action)>> {
+    let videos = Videos::of(rar_file, layout)?;
+    let mut plan = Vec::new();
+    let archive = Archive::new(rar_file)
+        .open_for_listing()
+        .context("Failed to open rar file for listing")?;
+
+    for entry in archive {
+        let entry = entry.context("Failed to read rar")?;
+        debug!("Listed rar entry: {entry:?}");
+        if !entry.is_file() || is_symlink(&entry) {
+            continue;
+        }
+
+        let Some(destination) = target(&entry, destination_directory, file_name, &videos)? else {
+            continue;
+        };
+        let action = action_for(&entry, &destination, compare)?;
+        let skip = matches!(action, Action::Skip) && !videos.checks_each_file();
+        plan.push((destination, action));
+
+        if skip {
+            break;
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Checks that the unrar library extracts archives, by extracting a small one in a temporary
+/// directory
+pub fn check_unrar() -> Result<()> {
+    let directory = tempfile::tempdir().context("Failed to create temporary directory")?;
+    let rar_file = directory.path().join("check.rar");
+    std::fs::write(&rar_file, CHECK_RAR).context("Failed to write test archive")?;
+
+    let mut archive = Archive::new(&rar_file)
+        .open_for_processing()
+        .context("Failed to open test archive")?;
+    while let Some(header) = archive
+        .read_header()
+        .context("Failed to read test archive")?
+    {
+        archive = header
+            .extract_with_base(directory.path())
+            .context("Failed to extract test archive")?;
+    }
+
+    let contents = std::fs::read_to_string(directory.path().join("check.txt"))
+        .context("Failed to read extracted test file")?;
+    if contents != "dessert" {
+        return Err(anyhow!(
+            "The extracted test file differs from the archived one"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that every file of the archive is in the destination with its unpacked size
+pub fn verify_rar_file(
+    rar_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    compare: Compare,
+    layout: Layout,
+) -> Result<()> {
+    let videos = Videos::of(rar_file, layout)?;
+    let archive = Archive::new(rar_file)
+        .open_for_listing()
+        .context("Failed to open rar file for listing")?;
+
+    for entry in archive {
+        let entry = entry.context("Failed to read rar")?;
+        if !entry.is_file() || is_symlink(&entry) {
+            continue;
+        }
+
+        let Some(destination) = target(&entry, destination_directory, file_name, &videos)? else {
+            continue;
+        };
+        if !matches!(action_for(&entry, &destination, compare)?, Action::Skip) {
+            return Err(anyhow!("{destination:?} is missing or differs"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the entry is a symlink, which is never extracted as it could point anywhere. Archives
+/// made on Unix keep the file mode, others the Windows attributes with reparse points
+fn is_symlink(entry: &FileHeader) -> bool {
+    match entry.file_attr & 0o170000 {
+        0 => entry.file_attr & 0x400 != 0,
+        file_type => file_type == 0o120000,
+    }
+}
+
+/// The video files of an archive, telling the main one from its extras
+struct Videos<'a> {
+    main: Option<PathBuf>,
+    /// The directory every file of the archive is in, left out of the paths it preserves
+    root: Option<PathBuf>,
+    /// The directory of the disc structure of an archive of a DVD or Blu-ray, which is imported
+    /// instead of a main video file
+    disc: Option<PathBuf>,
+    layout: Layout<'a>,
+}
+
+impl<'a> Videos<'a> {
+    fn of(rar_file: &Path, layout: Layout<'a>) -> Result<Videos<'a>> {
+        let archive = Archive::new(rar_file)
+            .open_for_listing()
+            .context("Failed to open rar file for listing")?;
+
+        let mut main: Option<FileHeader> = None;
+        let mut too_small = false;
+        let mut root: Option<Option<PathBuf>> = None;
+        // With the size of its largest video file
+        let mut disc: Option<(PathBuf, u64)> = None;
+        for entry in archive {
+            let entry = entry.context("Failed to read rar")?;
+            if !entry.is_file() || is_symlink(&entry) {
+                continue;
+            }
+            let directory = match entry.filename.components().next() {
+                Some(Component::Normal(first)) if entry.filename.components().count() > 1 => {
+                    Some(PathBuf::from(first))
+                }
+                _ => None,
+            };
+            root = match root {
+                Some(root) if root != directory => Some(None),
+                root => Some(root.unwrap_or(directory)),
+            };
+            if matches!(layout, Layout::Video(_)) && is_disc_video(&entry.filename) {
+                if let Some(directory) = disc_directory(&entry.filename) {
+                    let size = entry.unpacked_size as u64;
+                    disc = match disc {
+                        Some((disc, largest)) if disc == directory => {
+                            Some((disc, largest.max(size)))
+                        }
+                        None => Some((directory, size)),
+                        disc => disc,
+                    };
+                }
+            }
+            if !is_video(&entry.filename) {
+                continue;
+            }
+            let largest = main
+                .as_ref()
+                .is_none_or(|main| entry.unpacked_size > main.unpacked_size);
+            if (entry.unpacked_size as u64) < layout.min_video_size() {
+                too_small = true;
+            } else if largest {
+                main = Some(entry);
+            }
+        }
+        // Samples of the disc next to its structure are smaller than the disc's videos
+        let disc = match disc {
+            Some((_, size)) if size < layout.min_video_size() => {
+                too_small = true;
+                None
+            }
+            Some((disc, size))
+                if main
+                    .as_ref()
+                    .is_none_or(|main| size > main.unpacked_size as u64) =>
+            {
+                main = None;
+                Some(disc)
+            }
+            _ => None,
+        };
+        if main.is_none() && disc.is_none() && too_small {
+            return Err(anyhow!(
+                "No video file in the archive is at least {}",
+                format_bytes(layout.min_video_size())
+            ));
+        }
+
+        Ok(Videos {
+            main: main.map(|main| main.filename),
+            root: root.flatten(),
+            disc,
+            layout,
+        })
+    }
+
+    /// Whether every file is checked for an existing destination file, see
+    /// [`Layout::checks_each_file`]
+    fn checks_each_file(&self) -> bool {
+        self.disc.is_some() || self.layout.checks_each_file()
+    }
+
+    /// The path of the entry below the directory of the disc, if it is in its structure
+    fn in_disc<'b>(&self, entry: &'b FileHeader) -> Option<&'b Path> {
+        let relative = entry.filename.strip_prefix(self.disc.as_ref()?).ok()?;
+        match relative.components().next() {
+            Some(Component::Normal(tree)) if is_named(tree, &DISC_TREES) => Some(relative),
+            _ => None,
+        }
+    }
+
+    fn is_main(&self, entry: &FileHeader) -> bool {
+        self.main.as_ref() == Some(&entry.filename)
+    }
+
+    fn is_extra(&self, entry: &FileHeader) -> bool {
+        matches!(self.layout, Layout::Video(_))
+            && self.disc.is_none()
+            && is_video(&entry.filename)
+            && !self.is_main(entry)
+    }
+
+    fn imports(&self, entry: &FileHeader) -> bool {
+        match self.layout {
+            Layout::Album(_) => true,
+            Layout::Video(_) if self.disc.is_some() => self.in_disc(entry).is_some(),
+            Layout::Video(_) if !self.is_extra(entry) => true,
+            Layout::Video(video) => {
+                video.extras == Extras::Import && entry.unpacked_size as u64 >= video.min_size
+            }
+        }
+    }
+
+    /// The path of the entry in the archive, without the directory every file is in
+    fn relative<'b>(&self, entry: &'b FileHeader) -> &'b Path {
+        self.root
+            .as_ref()
+            .and_then(|root| entry.filename.strip_prefix(root).ok())
+            .unwrap_or(&entry.filename)
+    }
+}
+
+/// Where the entry is extracted to, or `None` for extras that aren't imported
+fn target(
+    entry: &FileHeader,
+    destination_directory: &Path,
+    file_name: &str,
+    videos: &Videos,
+) -> Result<Option<PathBuf>> {
+    if let Layout::Album(naming) = videos.layout {
+        return album_target(entry, destination_directory, file_name, naming).map(Some);
+    }
+    if !videos.imports(entry) {
+        return Ok(None);
+    }
+    if let Some(relative) = videos.in_disc(entry) {
+        return disc_destination(relative, destination_directory, file_name).map(Some);
+    }
+    let preserve_structure =
+        matches!(videos.layout, Layout::Video(video) if video.preserve_structure);
+    if videos.is_main(entry) || (!preserve_structure && !videos.is_extra(entry)) {
+        return destination_for(entry, destination_directory, file_name).map(Some);
+    }
+    if preserve_structure {
+        // Checked like the file name, as entries like `../../file` would leave the destination
+        let file = Path::new(file_name).with_file_name(videos.relative(entry));
+        check_file_name(&file)?;
+        return Ok(Some(destination_directory.join(file)));
+    }
+
+    let name = entry
+        .filename
+        .file_name()
+        .ok_or(anyhow!("Failed to get file name from rar header"))?;
+    let extra = Path::new(file_name).with_file_name("extras").join(name);
+    check_file_name(&extra)?;
+
+    Ok(Some(destination_directory.join(extra)))
+}
+
+/// Where the entry of an album is extracted to, in the album directory `file_name`. Tracks
+/// named like `01 - Title` or `01. Title` are renamed by the track template, other files like
+/// the cover keep their names
+fn album_target(
+    entry: &FileHeader,
+    destination_directory: &Path,
+    file_name: &str,
+    naming: &Naming,
+) -> Result<PathBuf> {
+    let name = entry
+        .filename
+        .file_name()
+        .map(Path::new)
+        .ok_or(anyhow!("Failed to get file name from rar header"))?;
+
+    let track = name
+        .extension()
+        .and_then(OsStr::to_str)
+        .filter(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .zip(name.file_stem().and_then(OsStr::to_str).and_then(track));
+    let file = match track {
+        Some((extension, (number, title))) => {
+            PathBuf::from(format!("{}.{extension}", naming.track_name(number, title)?))
+        }
+        None => name.to_path_buf(),
+    };
+    let file = Path::new(file_name).join(file);
+    check_file_name(&file)?;
+
+    Ok(destination_directory.join(file))
+}
+
+/// The number and title of a track named like `01 - Title`, `01. Title` or `01 Title`
+fn track(stem: &str) -> Option<(u32, &str)> {
+    let digits = stem.find(|c: char| !c.is_ascii_digit())?;
+    let number = stem[..digits].parse().ok()?;
+    let title = stem[digits..].trim_start_matches(|c: char| c.is_whitespace() || "-._".contains(c));
+    // Without a separator the digits are part of the title, like in `2Pac`
+    let separated = title.len() < stem.len() - digits;
+
+    match separated && !title.is_empty() {
+        true => Some((number, title)),
+        false => None,
+    }
+}
+
+fn destination_for(
+    entry: &FileHeader,
+    destination_directory: &Path,
+    file_name: &str,
+) -> Result<PathBuf> {
+    // Only the extension of the entry's name is used, so entries named like `../../file` or
+    // `/file` still land in the destination
+    let file_extension = entry
+        .filename
+        .file_name()
+        .map(Path::new)
+        .and_then(Path::extension)
+        .ok_or(anyhow!("Failed to get file extension from rar header"))?;
+
+    destination(destination_directory, file_name, file_extension)
+}
+
+fn destination(
+    destination_directory: &Path,
+    file_name: &str,
+    file_extension: &OsStr,
+) -> Result<PathBuf> {
+    let file_name = Path::new(file_name).with_extension(file_extension);
+    check_file_name(&file_name)?;
+
+    Ok(destination_directory.join(file_name))
+}
+
+/// Checks that the destination file name stays inside the destination directory, as release
+/// names come from untrusted downloads. Absolute names and names with `..` are refused
+pub fn check_file_name(file_name: &Path) -> Result<()> {
+    let contained = file_name
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    match contained && file_name.file_name().is_some() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Refusing to write {file_name:?} outside the destination directory"
+        )),
+    }
+}
+
+/// Turns the destination into an extended-length path on Windows, so deeply
+/// nested destinations aren't limited to 260 characters
+#[cfg(windows)]
+fn long_path(path: &Path) -> Result<PathBuf> {
+    use std::path::{Component, Prefix};
+
+    let path = std::path::absolute(path).context("Failed to resolve destination directory")?;
+    let mut components = path.components();
+
+    let long_path = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut long_path = std::ffi::OsString::from(r"\\?\");
+                long_path.push(path.as_os_str());
+                PathBuf::from(long_path)
+            }
+            Prefix::UNC(server, share) => {
+                let mut long_path = PathBuf::from(r"\\?\UNC");
+                long_path.push(server);
+                long_path.push(share);
+                long_path.extend(components.skip(1));
+                long_path
+            }
+            _ => path,
+        },
+        _ => path,
+    };
+
+    Ok(long_path)
+}
+
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> Result<PathBuf> {
+    Ok(path.to_path_buf())
+}
+
+fn action_for(entry: &FileHeader, destination: &Path, compare: Compare) -> Result<Action> {
+    let source = compare::Source {
+        size: entry.unpacked_size as u64,
+        modified: compare::dos_time(entry.file_time),
+        checksum: Checksum::Crc(entry.file_crc),
+    };
+
+    compare::action(source, destination, compare)
+}
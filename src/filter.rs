@@ -0,0 +1,47 @@
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use std::path::Path;
+
+/// Selects the releases to process by name, with a glob pattern like `*S02E*` or a regex
+/// between slashes like `/S02E0[1-4]/`
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl Filter {
+    /// Parses the value of `--filter`
+    pub fn parse(value: &str) -> Result<Filter, String> {
+        match value
+            .strip_prefix('/')
+            .and_then(|value| value.strip_suffix('/'))
+        {
+            Some(regex) => Regex::new(regex)
+                .map(Filter::Regex)
+                .map_err(|e| e.to_string()),
+            None => Pattern::new(value)
+                .map(Filter::Glob)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Whether the name of the release directory or file matches, globs ignoring case
+    pub fn matches(&self, release: &Path) -> bool {
+        let name = release
+            .file_name()
+            .unwrap_or(release.as_os_str())
+            .to_string_lossy();
+
+        match self {
+            Filter::Glob(pattern) => pattern.matches_with(
+                &name,
+                MatchOptions {
+                    case_sensitive: false,
+                    ..MatchOptions::new()
+                },
+            ),
+            Filter::Regex(regex) => regex.is_match(&name),
+        }
+    }
+}
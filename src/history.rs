@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, Row};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(clap::Args, Debug)]
+pub struct HistoryArgs {
+    /// SQLite database jobs were recorded in, defaults to one in the data directory
+    #[clap(long)]
+    history_database: Option<PathBuf>,
+
+    /// Only show jobs with this outcome
+    #[clap(long, value_enum)]
+    outcome: Option<Outcome>,
+
+    /// Only show jobs whose source or name contains this text
+    #[clap(long)]
+    search: Option<String>,
+
+    /// Maximum number of jobs to show, newest first
+    #[clap(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+    /// An import rolled back by `dessert undo`
+    Undone,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Succeeded => "succeeded",
+            Outcome::Failed => "failed",
+            Outcome::Undone => "undone",
+        }
+    }
+
+    fn from_str(outcome: &str) -> Outcome {
+        match outcome {
+            "succeeded" => Outcome::Succeeded,
+            "undone" => Outcome::Undone,
+            _ => Outcome::Failed,
+        }
+    }
+}
+
+pub struct Entry {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub source_directory: PathBuf,
+    pub destination_directory: PathBuf,
+    pub name: Option<String>,
+    pub files: Vec<PathBuf>,
+    pub archive_size: u64,
+    pub unpacked_size: u64,
+    pub duration: Duration,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+    /// The qualities of an import that replaced a worse one, like `720p -> 1080p`
+    pub upgrade: Option<String>,
+    /// The job's own log file, when job logs are enabled
+    pub log_file: Option<PathBuf>,
+    /// Identifies the content of the source of an import, see
+    /// [`checksum::content_hash`](crate::checksum::content_hash)
+    pub content_hash: Option<String>,
+}
+
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<History> {
+        let connection = Connection::open(path).context("Failed to open history database")?;
+        connection
+            .busy_timeout(Duration::from_secs(30))
+            .context("Failed to configure history database")?;
+        connection
+            .execute_batch(
+                r"CREATE TABLE IF NOT EXISTS jobs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    started_at TEXT NOT NULL,
+                    source_directory TEXT NOT NULL,
+                    destination_directory TEXT NOT NULL,
+                    name TEXT,
+                    files TEXT NOT NULL,
+                    archive_size INTEGER NOT NULL,
+                    unpacked_size INTEGER NOT NULL,
+                    duration_ms INTEGER NOT NULL,
+                    outcome TEXT NOT NULL,
+                    error TEXT,
+                    upgrade TEXT,
+                    log_file TEXT,
+                    content_hash TEXT
+                );",
+            )
+            .context("Failed to create history table")?;
+        // Databases created before these were recorded lack their columns
+        for column in ["upgrade", "log_file", "content_hash"] {
+            if connection
+                .prepare(&format!("SELECT {column} FROM jobs"))
+                .is_err()
+            {
+                connection
+                    .execute(&format!("ALTER TABLE jobs ADD COLUMN {column} TEXT"), [])
+                    .with_context(|| format!("Failed to add {column} column to history table"))?;
+            }
+        }
+        connection
+            .execute(
+                "CREATE INDEX IF NOT EXISTS jobs_content_hash ON jobs (content_hash)",
+                [],
+            )
+            .context("Failed to create history index")?;
+
+        Ok(History { connection })
+    }
+
+    pub fn record(&self, entry: &Entry) -> Result<i64> {
+        self.connection
+            .execute(
+                r"INSERT INTO jobs (
+                    started_at, source_directory, destination_directory, name, files,
+                    archive_size, unpacked_size, duration_ms, outcome, error, upgrade,
+                    log_file, content_hash
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    entry.started_at.to_rfc3339(),
+                    entry.source_directory.to_string_lossy(),
+                    entry.destination_directory.to_string_lossy(),
+                    entry.name,
+                    entry
+                        .files
+                        .iter()
+                        .map(|file| file.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    entry.archive_size as i64,
+                    entry.unpacked_size as i64,
+                    entry.duration.as_millis() as i64,
+                    entry.outcome.as_str(),
+                    entry.error,
+                    entry.upgrade,
+                    entry
+                        .log_file
+                        .as_ref()
+                        .map(|log_file| log_file.to_string_lossy()),
+                    entry.content_hash,
+                ],
+            )
+            .context("Failed to record job in history")?;
+
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT * FROM jobs WHERE id = ?1")
+            .context("Failed to query history")?;
+
+        let mut entries = statement
+            .query_map(params![id], entry_from_row)
+            .context("Failed to query history")?;
+
+        entries.next().transpose().context("Failed to read history")
+    }
+
+    /// Marks the job as undone, so it no longer counts as an import of its files or content
+    pub fn undo(&self, id: i64) -> Result<()> {
+        self.connection
+            .execute(
+                "UPDATE jobs SET outcome = 'undone' WHERE id = ?1",
+                params![id],
+            )
+            .context("Failed to mark job as undone in history")?;
+
+        Ok(())
+    }
+
+    /// The latest succeeded job that wrote the file
+    pub fn imported(&self, file: &Path) -> Result<Option<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                r"SELECT * FROM jobs
+                WHERE outcome = 'succeeded'
+                AND instr(char(10) || files || char(10), char(10) || ?1 || char(10)) > 0
+                ORDER BY id DESC
+                LIMIT 1",
+            )
+            .context("Failed to query history")?;
+
+        let mut entries = statement
+            .query_map(params![file.to_string_lossy()], entry_from_row)
+            .context("Failed to query history")?;
+
+        entries.next().transpose().context("Failed to read history")
+    }
+
+    /// The latest succeeded job whose source had the content
+    pub fn imported_content(&self, content_hash: &str) -> Result<Option<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                r"SELECT * FROM jobs
+                WHERE outcome = 'succeeded' AND content_hash = ?1
+                ORDER BY id DESC
+                LIMIT 1",
+            )
+            .context("Failed to query history")?;
+
+        let mut entries = statement
+            .query_map(params![content_hash], entry_from_row)
+            .context("Failed to query history")?;
+
+        entries.next().transpose().context("Failed to read history")
+    }
+
+    /// Lists the latest failed job of every source that hasn't succeeded since
+    pub fn unresolved_failures(&self) -> Result<Vec<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                r"SELECT * FROM jobs AS failed
+                WHERE outcome = 'failed'
+                AND id = (
+                    SELECT MAX(id) FROM jobs AS latest
+                    WHERE latest.source_directory = failed.source_directory
+                    AND latest.destination_directory = failed.destination_directory
+                )
+                ORDER BY id",
+            )
+            .context("Failed to query history")?;
+
+        let entries = statement
+            .query_map([], entry_from_row)
+            .context("Failed to query history")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read history")?;
+
+        Ok(entries)
+    }
+
+    /// Lists the jobs started since the time, oldest first
+    pub fn since(&self, since: DateTime<Utc>) -> Result<Vec<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT * FROM jobs WHERE started_at >= ?1 ORDER BY id")
+            .context("Failed to query history")?;
+
+        let entries = statement
+            .query_map(params![since.to_rfc3339()], entry_from_row)
+            .context("Failed to query history")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read history")?;
+
+        Ok(entries)
+    }
+
+    pub fn list(
+        &self,
+        outcome: Option<Outcome>,
+        search: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Entry>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                r"SELECT * FROM jobs
+                WHERE (?1 IS NULL OR outcome = ?1)
+                AND (?2 IS NULL OR instr(source_directory, ?2) > 0 OR instr(name, ?2) > 0)
+                ORDER BY id DESC
+                LIMIT ?3",
+            )
+            .context("Failed to query history")?;
+
+        let entries = statement
+            .query_map(
+                params![outcome.map(Outcome::as_str), search, limit as i64],
+                entry_from_row,
+            )
+            .context("Failed to query history")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read history")?;
+
+        Ok(entries)
+    }
+}
+
+/// The given history database, or else the default one in the data directory
+pub fn database(path: Option<&Path>) -> Result<PathBuf> {
+    match path {
+        Some(path) => Ok(path.to_path_buf()),
+        None => crate::dirs::history_database(),
+    }
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<Entry> {
+    let started_at = row.get::<_, String>("started_at")?;
+    let files = row.get::<_, String>("files")?;
+
+    Ok(Entry {
+        id: row.get("id")?,
+        started_at: DateTime::parse_from_rfc3339(&started_at)
+            .map(|started_at| started_at.with_timezone(&Utc))
+            .unwrap_or_default(),
+        source_directory: PathBuf::from(row.get::<_, String>("source_directory")?),
+        destination_directory: PathBuf::from(row.get::<_, String>("destination_directory")?),
+        name: row.get("name")?,
+        files: files.lines().map(PathBuf::from).collect(),
+        archive_size: row.get::<_, i64>("archive_size")? as u64,
+        unpacked_size: row.get::<_, i64>("unpacked_size")? as u64,
+        duration: Duration::from_millis(row.get::<_, i64>("duration_ms")? as u64),
+        outcome: Outcome::from_str(&row.get::<_, String>("outcome")?),
+        error: row.get("error")?,
+        upgrade: row.get("upgrade")?,
+        log_file: row.get::<_, Option<String>>("log_file")?.map(PathBuf::from),
+        content_hash: row.get("content_hash")?,
+    })
+}
+
+pub fn show(args: &HistoryArgs) -> Result<()> {
+    let entries = History::open(&database(args.history_database.as_deref())?)?.list(
+        args.outcome,
+        args.search.as_deref(),
+        args.limit,
+    )?;
+
+    for entry in entries {
+        println!(
+            "{:>5}  {}  {:<9}  {:>8.1}s  {}  <- {}",
+            entry.id,
+            entry.started_at.format("%Y-%m-%d %H:%M:%S"),
+            entry.outcome.as_str(),
+            entry.duration.as_secs_f64(),
+            entry.name.as_deref().unwrap_or("-"),
+            entry.source_directory.display(),
+        );
+        if let Some(error) = entry.error {
+            println!("       {error}");
+        }
+        if let Some(upgrade) = entry.upgrade {
+            println!("       Upgraded {upgrade}");
+        }
+    }
+
+    Ok(())
+}
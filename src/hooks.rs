@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use std::process::{Command, Output};
+
+/// Runs the pre-processing hooks in order, aborting on the first failure
+pub fn run_pre_hooks(commands: &[String], env: &[(&str, String)]) -> Result<()> {
+    for command in commands {
+        run_hook(command, env)
+            .with_context(|| format!("Pre-processing hook aborted the job: {command}"))?;
+        info!("Pre-processing hook succeeded: {command}");
+    }
+
+    Ok(())
+}
+
+/// Runs every post-processing hook, reporting failures without returning them
+pub fn run_post_hooks(commands: &[String], env: &[(&str, String)]) {
+    for command in commands {
+        match run_hook(command, env) {
+            Ok(_) => info!("Post-processing hook succeeded: {command}"),
+            Err(e) => error!("Post-processing hook failed: {e:#}"),
+        }
+    }
+}
+
+fn run_hook(command: &str, env: &[(&str, String)]) -> Result<Output> {
+    let output = shell(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .output()
+        .with_context(|| format!("Failed to start hook: {command}"))?;
+
+    log_output(&output);
+
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(anyhow!("{command} exited with {}", output.status))
+    }
+}
+
+/// Runs the command through the platform shell
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").raw_arg(command);
+    shell
+}
+
+/// Runs the command through the platform shell
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+fn log_output(output: &Output) {
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!("hook stdout: {line}");
+    }
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        info!("hook stderr: {line}");
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::{email, mqtt, prompt};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Naming presets offered by the wizard, as name, episode template and movie template
+const NAMING_PRESETS: [(&str, &str, &str); 3] = [
+    (
+        "plex",
+        "{name} - S{season:02}E{episode:02}",
+        "{name} ({year})",
+    ),
+    (
+        "kodi",
+        "{name} S{season:02}E{episode:02}",
+        "{name} ({year})",
+    ),
+    (
+        "dotted",
+        "{name}.S{season:02}E{episode:02}",
+        "{name}.{year}",
+    ),
+];
+
+#[derive(clap::Args, Debug)]
+pub struct InitArgs {
+    /// Overwrite the config file if it already exists
+    #[clap(long)]
+    force: bool,
+
+    /// Naming preset to use instead of asking for one
+    #[clap(long, value_parser = NAMING_PRESETS.map(|(name, _, _)| name))]
+    naming: Option<String>,
+}
+
+/// Asks for the basic settings and writes them to the config file, `--config` or the default one
+pub fn init(args: &InitArgs, path: Option<&Path>) -> Result<()> {
+    let path = path
+        .map(Path::to_path_buf)
+        .or_else(dessert::dirs::config_file)
+        .ok_or(anyhow!("Failed to find a config directory, pass --config"))?;
+    if path.exists() && !args.force {
+        return Err(anyhow!(
+            "Config file {path:?} already exists, pass --force to overwrite it"
+        ));
+    }
+
+    let mut config = toml::Table::new();
+
+    let mut sources = vec![ask_directory("Source directory", None)?];
+    loop {
+        let source = ask_directory("Another source directory, empty to finish", Some(""))?;
+        if source.as_os_str().is_empty() {
+            break;
+        }
+        sources.push(source);
+    }
+    config.insert("source_directory".to_string(), paths(&sources));
+    config.insert(
+        "destination_directory".to_string(),
+        paths(&[ask_directory("Destination directory", None)?]),
+    );
+
+    let naming = match &args.naming {
+        Some(preset) => naming(preset).ok_or(anyhow!("Unknown naming preset {preset:?}"))?,
+        None => ask_naming()?,
+    };
+    config.insert("naming".to_string(), naming.into());
+
+    if prompt::ask_yes_no("Send an email when jobs finish?", false)? {
+        config.insert("email".to_string(), ask_email()?.into());
+    }
+    if prompt::ask_yes_no("Publish job events to an MQTT broker?", false)? {
+        config.insert("mqtt".to_string(), ask_mqtt()?.into());
+    }
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).context("Failed to create config directory")?;
+    }
+    fs::write(
+        &path,
+        format!(
+            "# Written by `dessert init`, see dessert.example.toml for every option\n\n{}",
+            toml::to_string(&config).context("Failed to serialize config")?
+        ),
+    )
+    .context("Failed to write config file")?;
+
+    println!("Wrote {}", path.display());
+    if Some(&path) == dessert::dirs::config_file().as_ref() {
+        println!("Run `dessert` to process the source directories");
+    } else {
+        println!(
+            "Run `dessert --config {}` to process the source directories",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn ask_directory(question: &str, default: Option<&str>) -> Result<PathBuf> {
+    loop {
+        let directory = PathBuf::from(prompt::ask(question, default)?);
+        if directory.as_os_str().is_empty()
+            || directory.is_dir()
+            || prompt::ask_yes_no(
+                &format!("{} is not a directory, use it anyway?", directory.display()),
+                false,
+            )?
+        {
+            return Ok(directory);
+        }
+    }
+}
+
+fn paths(paths: &[PathBuf]) -> toml::Value {
+    paths
+        .iter()
+        .map(|path| toml::Value::String(path.display().to_string()))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn ask_naming() -> Result<toml::Table> {
+    eprintln!("Naming presets:");
+    for (name, episode, movie) in NAMING_PRESETS {
+        eprintln!("  {name:<8} {episode}  {movie}");
+    }
+
+    loop {
+        let preset = prompt::ask("Naming preset", Some(NAMING_PRESETS[0].0))?;
+        match naming(&preset) {
+            Some(naming) => return Ok(naming),
+            None => eprintln!("Unknown preset {preset:?}"),
+        }
+    }
+}
+
+/// The `[naming]` section of the preset
+fn naming(preset: &str) -> Option<toml::Table> {
+    let (_, episode, movie) = NAMING_PRESETS.iter().find(|(name, _, _)| *name == preset)?;
+    let mut naming = toml::Table::new();
+    naming.insert("episode".to_string(), episode.to_string().into());
+    naming.insert("movie".to_string(), movie.to_string().into());
+    Some(naming)
+}
+
+fn ask_email() -> Result<toml::Table> {
+    loop {
+        let mut section = toml::Table::new();
+        section.insert("to".to_string(), prompt::ask("Send to", None)?.into());
+        section.insert(
+            "domain".to_string(),
+            prompt::ask("Mailgun domain", None)?.into(),
+        );
+        section.insert(
+            "api_base_path".to_string(),
+            prompt::ask("Mailgun API base path", Some("https://api.mailgun.net/v3"))?.into(),
+        );
+        section.insert(
+            "api_key".to_string(),
+            prompt::ask("Mailgun API key", None)?.into(),
+        );
+
+        let client = toml::Value::Table(section.clone())
+            .try_into::<email::Client>()
+            .context("Failed to parse email settings")?;
+        eprintln!("Sending a test email...");
+        if confirm_test(client.send_test())? {
+            return Ok(section);
+        }
+    }
+}
+
+fn ask_mqtt() -> Result<toml::Table> {
+    loop {
+        let mut section = toml::Table::new();
+        section.insert(
+            "host".to_string(),
+            prompt::ask("MQTT host", Some("localhost"))?.into(),
+        );
+        let port = loop {
+            match prompt::ask("MQTT port", Some("1883"))?.parse::<u16>() {
+                Ok(port) => break port,
+                Err(_) => eprintln!("Please give a port number"),
+            }
+        };
+        section.insert("port".to_string(), i64::from(port).into());
+        section.insert(
+            "topic".to_string(),
+            prompt::ask("MQTT topic", Some("dessert"))?.into(),
+        );
+        let username = prompt::ask("MQTT username, empty for none", Some(""))?;
+        if !username.is_empty() {
+            section.insert("username".to_string(), username.into());
+            section.insert(
+                "password".to_string(),
+                prompt::ask("MQTT password", None)?.into(),
+            );
+        }
+
+        let config = toml::Value::Table(section.clone())
+            .try_into::<mqtt::Config>()
+            .context("Failed to parse MQTT settings")?;
+        eprintln!("Publishing a test message...");
+        if confirm_test(mqtt::Client::send_test(config))? {
+            return Ok(section);
+        }
+    }
+}
+
+/// Whether to keep the settings after sending a test notification with them
+fn confirm_test(result: Result<()>) -> Result<bool> {
+    match result {
+        Ok(()) => {
+            eprintln!("Test notification sent");
+            Ok(true)
+        }
+        Err(e) => {
+            eprintln!("{e:#}");
+            prompt::ask_yes_no("Keep these settings anyway?", false)
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::context;
+use chrono::Utc;
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::SharedLogger;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Writes each record as one line of JSON, with the job and phase it was logged in
+pub struct JsonLogger<W: Write + Send> {
+    level: LevelFilter,
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn new(level: LevelFilter, writer: W) -> Box<JsonLogger<W>> {
+        Box::new(JsonLogger {
+            level,
+            writer: Mutex::new(writer),
+        })
+    }
+}
+
+impl<W: Write + Send> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let current = context::current();
+        let line = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "job": current.job,
+            "phase": current.phase,
+            "message": record.args().to_string(),
+        });
+
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,137 @@
+//! Unpacks downloaded releases into a media library, named the way it expects.
+//!
+//! The `dessert` binary is a command line on top of this library. Programs embedding dessert
+//! build a [`Pipeline`] from [`Options`] and run [`Job`]s on it, each giving an [`Outcome`].
+//! Their progress is sent as [`events::Event`]s to the observers added with
+//! [`Pipeline::observe`].
+//! Settings shared by jobs, like naming templates, rules and notification clients, come from
+//! the [`config`] set with [`config::set`].
+//!
+//! Without the default `pipeline` feature only the release name [`parser`] is built, e.g. with
+//! `dessert = { version = "0.1", default-features = false, features = ["parser"] }`.
+
+#[cfg(feature = "pipeline")]
+pub mod aliases;
+#[cfg(feature = "pipeline")]
+pub mod backend;
+#[cfg(feature = "pipeline")]
+pub mod bazarr;
+#[cfg(feature = "pipeline")]
+pub mod case;
+#[cfg(feature = "pipeline")]
+pub mod checksum;
+#[cfg(feature = "pipeline")]
+pub mod cleanup;
+#[cfg(feature = "pipeline")]
+pub mod compare;
+#[cfg(feature = "pipeline")]
+pub mod config;
+#[cfg(feature = "pipeline")]
+pub mod context;
+#[cfg(feature = "pipeline")]
+pub mod copy;
+#[cfg(feature = "pipeline")]
+pub mod dirs;
+#[cfg(feature = "pipeline")]
+pub mod email;
+#[cfg(feature = "pipeline")]
+pub mod error;
+#[cfg(feature = "pipeline")]
+pub mod events;
+#[cfg(feature = "pipeline")]
+pub mod exit;
+#[cfg(feature = "pipeline")]
+pub mod extract;
+#[cfg(feature = "pipeline")]
+pub mod filter;
+#[cfg(feature = "pipeline")]
+pub mod history;
+#[cfg(feature = "pipeline")]
+pub mod hooks;
+#[cfg(feature = "pipeline")]
+pub mod jsonlog;
+#[cfg(feature = "pipeline")]
+pub mod library;
+#[cfg(feature = "pipeline")]
+pub mod lock;
+#[cfg(feature = "pipeline")]
+pub mod logfile;
+#[cfg(feature = "pipeline")]
+pub mod mapping;
+#[cfg(feature = "pipeline")]
+pub mod metafile;
+#[cfg(feature = "pipeline")]
+pub mod metrics;
+#[cfg(feature = "pipeline")]
+pub mod mqtt;
+#[cfg(feature = "pipeline")]
+pub mod naming;
+#[cfg(feature = "pipeline")]
+pub mod nfo;
+#[cfg(feature = "pipeline")]
+pub mod output;
+#[cfg(feature = "parser")]
+pub mod parser;
+#[cfg(feature = "pipeline")]
+pub mod pipe;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+#[cfg(feature = "pipeline")]
+pub mod privileges;
+#[cfg(feature = "pipeline")]
+pub mod profile;
+#[cfg(feature = "pipeline")]
+pub mod prompt;
+#[cfg(feature = "pipeline")]
+pub mod quality;
+#[cfg(feature = "pipeline")]
+pub mod quarantine;
+#[cfg(feature = "pipeline")]
+pub mod queue;
+#[cfg(feature = "pipeline")]
+pub mod redact;
+#[cfg(feature = "pipeline")]
+pub mod release;
+#[cfg(feature = "pipeline")]
+pub mod remux;
+#[cfg(feature = "pipeline")]
+pub mod repair;
+#[cfg(feature = "pipeline")]
+pub mod retention;
+#[cfg(feature = "pipeline")]
+pub mod rules;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "pipeline")]
+pub mod secrets;
+#[cfg(feature = "sftp")]
+pub mod sftp;
+#[cfg(feature = "pipeline")]
+pub mod shutdown;
+#[cfg(feature = "pipeline")]
+pub mod space;
+#[cfg(feature = "pipeline")]
+pub mod spool;
+#[cfg(feature = "pipeline")]
+pub mod status;
+#[cfg(feature = "pipeline")]
+pub mod subtitles;
+#[cfg(feature = "pipeline")]
+pub mod summary;
+#[cfg(feature = "pipeline")]
+pub mod symlink;
+#[cfg(feature = "pipeline")]
+pub mod syslog;
+#[cfg(feature = "pipeline")]
+pub mod timeout;
+#[cfg(feature = "pipeline")]
+pub mod tmdb;
+#[cfg(feature = "pipeline")]
+pub mod trace;
+
+#[cfg(feature = "pipeline")]
+pub use error::DessertError;
+#[cfg(feature = "pipeline")]
+pub use pipeline::{Import, Job, Options, Outcome, Pipeline};
@@ -0,0 +1,159 @@
+use crate::extract::is_video;
+use crate::naming::Naming;
+use crate::release::{self, Release};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What happens when the release is already in the library under another name, like in
+/// another quality
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Duplicate {
+    /// Import it next to the existing files
+    Keep,
+    /// Import it and delete the existing files
+    Replace,
+    /// Skip the import
+    Skip,
+}
+
+/// The video files below the directory, leaving out extras which are named freely
+pub fn videos(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut videos = Vec::new();
+    find_videos(directory, &mut videos)?;
+    videos.sort();
+
+    Ok(videos)
+}
+
+fn find_videos(directory: &Path, videos: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(directory)
+        .with_context(|| format!("Failed to read library directory {:?}", directory))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() && entry.file_name() != "extras" {
+            find_videos(&path, videos)?;
+        } else if file_type.is_file() && is_video(&path) {
+            videos.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The videos at the file name below the library, with any extension
+pub fn named(library: &Path, file_name: &str) -> Vec<PathBuf> {
+    let path = library.join(file_name);
+    let Some(Ok(entries)) = path.parent().map(fs::read_dir) else {
+        return Vec::new();
+    };
+
+    let mut videos = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|video| is_video(video) && video.with_extension("") == path)
+        .collect::<Vec<_>>();
+    videos.sort();
+    videos
+}
+
+/// The path of the video below the library without its extension, with `/` between the
+/// directories like in naming templates
+pub fn relative_name(library: &Path, video: &Path) -> String {
+    video
+        .strip_prefix(library)
+        .unwrap_or(video)
+        .with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The release of a video of the library by its [`relative_name`], parsing it as rendered by
+/// the naming templates or else as a release name
+pub fn identify(naming: &Naming, name: &str, mode: release::Mode) -> Option<Release> {
+    let file_stem = name.rsplit('/').next().unwrap_or(name);
+    // Files of templates with directories may still have the file name of the template
+    let file_naming = Naming {
+        episode: last_component(&naming.episode).to_string(),
+        movie: last_component(&naming.movie).to_string(),
+        ..naming.clone()
+    };
+
+    naming
+        .parse(name)
+        .or_else(|| file_naming.parse(file_stem))
+        .or_else(|| Release::parse(file_stem, mode).ok())
+}
+
+fn last_component(template: &str) -> &str {
+    template.rsplit('/').next().unwrap_or(template)
+}
+
+/// The video and the files next to it named like it, like `name.en.srt`
+pub fn companions(video: &Path) -> Result<Vec<PathBuf>> {
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    let directory = video.parent().unwrap_or(Path::new("."));
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).context("Failed to read directory of video")? {
+        let path = entry.context("Failed to read directory of video")?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name
+            .strip_prefix(stem.as_ref())
+            .is_some_and(|suffix| suffix.starts_with('.'))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// The videos of the library of the same release, besides the one at `file_name`
+pub fn duplicates(
+    library: &Path,
+    naming: &Naming,
+    release: &Release,
+    file_name: &str,
+    mode: release::Mode,
+) -> Result<Vec<PathBuf>> {
+    Ok(videos(library)?
+        .into_iter()
+        .filter(|video| {
+            let name = relative_name(library, video);
+            name != file_name
+                && identify(naming, &name, mode).is_some_and(|other| other.is_same(release))
+        })
+        .collect())
+}
+
+/// Deletes the duplicates with the files named like them, after the release was imported
+pub fn delete(duplicates: &[PathBuf]) -> Result<()> {
+    for duplicate in duplicates {
+        for file in companions(duplicate)? {
+            fs::remove_file(&file).with_context(|| format!("Failed to delete {:?}", file))?;
+            info!("Deleted duplicate: {:?}", file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Logs the duplicates of the release, returning whether to go on with the import
+pub fn report(duplicates: &[PathBuf], duplicate: Duplicate) -> bool {
+    for existing in duplicates {
+        match duplicate {
+            Duplicate::Keep => warn!("Already in the library as {:?}, importing anyway", existing),
+            Duplicate::Replace => info!("Replacing duplicate {:?}", existing),
+            Duplicate::Skip => info!("Skipping, already in the library as {:?}", existing),
+        }
+    }
+
+    duplicates.is_empty() || duplicate != Duplicate::Skip
+}
@@ -0,0 +1,128 @@
+use crate::exit::{Code, ExitCode};
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+
+/// The lock files held by this process, shared by its jobs, as they only guard against other
+/// processes. Jobs of this process writing the same destination wait on each other with
+/// [`crate::queue::lock_destination`] instead
+static HELD: LazyLock<Mutex<HashMap<PathBuf, Weak<Held>>>> = LazyLock::new(Default::default);
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Don't take a lock file
+    None,
+    /// One lock shared by every dessert process on the machine
+    Global,
+    /// One lock per destination directory
+    Destination,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Contention {
+    /// Wait for the other process to finish
+    Wait,
+    /// Give up on the job
+    Exit,
+}
+
+/// Holds the lock file until dropped, and every other job of the process holding it with it
+pub struct Lock {
+    _file: Option<Arc<Held>>,
+}
+
+/// A locked lock file, deleted once the last job holding it is done so it doesn't stay behind
+/// in the library. Windows can't delete open files, so there it stays
+struct Held {
+    _file: File,
+    #[cfg_attr(windows, allow(dead_code))]
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for Held {
+    fn drop(&mut self) {
+        // Deleted while still locked, processes that opened it before retry, see `is_current`
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::debug!("Failed to delete lock file {:?}: {e}", self.path);
+        }
+    }
+}
+
+pub fn acquire(scope: Scope, contention: Contention, destination_directory: &Path) -> Result<Lock> {
+    let path = match scope {
+        Scope::None => return Ok(Lock { _file: None }),
+        Scope::Global => std::env::temp_dir().join("dessert.lock"),
+        Scope::Destination => destination_directory.join(".dessert.lock"),
+    };
+
+    // Checking and taking the lock happen under the registry, so jobs of this process never
+    // take it apart. Only waiting for another process happens outside of it
+    let mut held = HELD.lock().unwrap();
+    if let Some(file) = held.get(&path).and_then(Weak::upgrade) {
+        return Ok(Lock { _file: Some(file) });
+    }
+
+    let file = loop {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file {:?}", path))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => match contention {
+                Contention::Wait => {
+                    drop(held);
+                    info!("Waiting for another dessert process holding {:?}", path);
+                    file.lock().context("Failed to lock lock file")?;
+                    held = HELD.lock().unwrap();
+                }
+                Contention::Exit => return Err(busy(path)).exit_code(Code::Conflict),
+            },
+            Err(TryLockError::Error(e)) => return Err(e).context("Failed to lock lock file"),
+        }
+
+        if is_current(&file, &path) {
+            break file;
+        }
+    };
+
+    let file = Arc::new(Held {
+        _file: file,
+        path: path.clone(),
+    });
+    held.insert(path, Arc::downgrade(&file));
+    Ok(Lock { _file: Some(file) })
+}
+
+/// Whether the locked file is still the one at the path, and not one the process holding it
+/// deleted after it was opened here
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(file), Ok(path)) => file.dev() == path.dev() && file.ino() == path.ino(),
+        _ => false,
+    }
+}
+
+/// Whether the locked file is still the one at the path, always so on Windows, where lock
+/// files aren't deleted
+#[cfg(windows)]
+fn is_current(_: &File, _: &Path) -> bool {
+    true
+}
+
+fn busy(path: PathBuf) -> anyhow::Error {
+    anyhow!(
+        "Another dessert process is holding {:?}, giving up on this job",
+        path
+    )
+}
@@ -0,0 +1,171 @@
+use crate::config::Logging;
+use crate::context;
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::SharedLogger;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A log file that outlives the process, `dessert.log` in the log directory.
+///
+/// It is rotated to `dessert.log.1`, `dessert.log.2` and so on once it grows past the maximum
+/// size or gets older than the maximum age, keeping a limited number of rotated files.
+pub struct RotatingFile {
+    path: PathBuf,
+    /// Closed while rotating, as Windows can't rename open files
+    file: Option<File>,
+    size: u64,
+    /// Whether the last write ended a line, rotating only happens between lines
+    line_ended: bool,
+    created: SystemTime,
+    max_size: u64,
+    max_age: Option<Duration>,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(directory: &Path, logging: &Logging) -> Result<RotatingFile> {
+        fs::create_dir_all(directory).context("Failed to create log directory")?;
+
+        let path = directory.join("dessert.log");
+        let (file, size, created) = open(&path).context("Failed to open log file")?;
+
+        Ok(RotatingFile {
+            path,
+            file: Some(file),
+            size,
+            line_ended: true,
+            created,
+            max_size: logging.max_size,
+            max_age: logging
+                .max_age_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            keep: logging.keep,
+        })
+    }
+
+    fn rotated(&self, number: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{number}"));
+        PathBuf::from(path)
+    }
+
+    /// Closes the log file and shifts it and the rotated files up by one
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        ignore_missing(fs::remove_file(self.rotated(self.keep)))?;
+        for number in (1..self.keep).rev() {
+            ignore_missing(fs::rename(self.rotated(number), self.rotated(number + 1)))?;
+        }
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        Ok(())
+    }
+
+    fn is_due(&self, incoming: usize) -> bool {
+        let too_big = self.size + incoming as u64 > self.max_size;
+        let too_old = self.max_age.is_some_and(|max_age| {
+            self.created
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed > max_age)
+        });
+
+        self.line_ended && self.size > 0 && (too_big || too_old)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_some() && self.is_due(buf.len()) {
+            self.rotate()?;
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let (file, size, created) = open(&self.path)?;
+                (self.size, self.created) = (size, created);
+                self.file.insert(file)
+            }
+        };
+
+        let written = file.write(buf)?;
+        self.size += written as u64;
+        self.line_ended = buf[..written].ends_with(b"\n");
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open(path: &Path) -> io::Result<(File, u64, SystemTime)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let created = match metadata.len() {
+        0 => SystemTime::now(),
+        _ => metadata.created().or_else(|_| metadata.modified())?,
+    };
+
+    Ok((file, metadata.len(), created))
+}
+
+fn ignore_missing(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Writes the records of each job to the job's own log file, see [`context::open_log`]
+pub struct JobLogger {
+    level: LevelFilter,
+}
+
+impl JobLogger {
+    pub fn new(level: LevelFilter) -> Box<JobLogger> {
+        Box::new(JobLogger { level })
+    }
+}
+
+impl Log for JobLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            context::write_log(&format!(
+                "{} [{}] {}",
+                chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+                record.level(),
+                record.args()
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for JobLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -1,227 +1,452 @@
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use log::{error, info};
-use regex::{Regex, RegexBuilder};
-use simplelog::WriteLogger;
-use std::ffi::OsStr;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use dessert::summary::Summary;
+use dessert::{
+    config, dirs, exit, history, jsonlog, logfile, output, redact, retention, status, syslog,
+    timeout, Job, Options, Pipeline,
+};
+use simplelog::{ColorChoice, CombinedLogger, SharedLogger, TermLogger, TerminalMode, WriteLogger};
 use std::fs::File;
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
-use titlecase::titlecase;
-use unrar::Archive;
-
-mod email;
 
+mod audit;
+mod daemon;
+mod digest;
+mod doctor;
+mod init;
+mod man;
+mod notification;
+mod parse;
+mod poll;
+mod retry;
+mod schedule;
+mod server;
+mod systemd;
+#[cfg(feature = "tui")]
+mod tui;
+mod undo;
+mod watch;
+mod web;
+
+/// Shown by `--version`, with what the build was made from
+const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit: ",
+    env!("DESSERT_GIT_COMMIT"),
+    "\nbuilt: ",
+    env!("DESSERT_BUILD_DATE"),
+    "\nfeatures: ",
+    env!("DESSERT_FEATURES"),
+    "\nrar: ",
+    env!("DESSERT_UNRAR_VERSION"),
+);
+
+/// Unpacks downloaded releases into a media library, named the way it expects
 #[derive(Parser, Debug)]
-struct Args {
-    #[clap(short, long)]
-    source_directory: PathBuf,
+#[clap(
+    version,
+    long_version = LONG_VERSION,
+    arg_required_else_help = true,
+    after_help = exit::help()
+)]
+struct Cli {
+    /// TOML configuration file, command line flags override its values
+    #[clap(long, global = true, env = "DESSERT_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Most verbose level logged: off, error, warn, info, debug or trace. Overrides RUST_LOG and
+    /// the config file
+    #[clap(long, global = true, value_name = "LEVEL")]
+    log_level: Option<log::LevelFilter>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand the flags of `dessert run` are accepted, as hooks of download
+    /// clients have long called dessert that way
+    #[clap(flatten)]
+    run: RunArgs,
+}
 
-    #[clap(short, long)]
-    destination_directory: PathBuf,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process the source directories once
+    Run(RunArgs),
 
-    #[clap(short, long)]
-    email_config_file: Option<PathBuf>,
-}
+    /// Serve an HTTP API for triggering and querying jobs
+    Serve(server::ServeArgs),
 
-fn main() -> Result<()> {
-    let mut log_file = set_up_logging()?;
+    /// Watch the source directory and process releases as they complete
+    Watch(watch::WatchArgs),
 
-    let args = Args::parse();
+    /// Poll the source directory and process releases once their sizes are stable
+    Poll(poll::PollArgs),
 
-    let file = match run(&args) {
-        Ok(file) => Some(file),
-        Err(e) => {
-            error!("{e}");
-            None
-        }
-    };
+    /// Show what a release name is parsed as and the file name it gets
+    Parse(parse::ParseArgs),
 
-    let mut log = String::new();
-    log_file
-        .read_to_string(&mut log)
-        .context("Failed to read log file")?;
+    /// List previously processed jobs
+    History(history::HistoryArgs),
 
-    match args.email_config_file {
-        Some(config_file) => {
-            email::Client::init_from_file(&config_file)
-                .context("Failed to initialize email client")?
-                .send_email(file.as_deref(), log.as_str())?;
-        }
-        None => {
-            eprintln!("{log}")
-        }
-    }
+    /// Re-run previously failed jobs from the history
+    Retry(retry::RetryArgs),
+
+    /// Remove the files an import wrote and forget it was imported, for a clean re-run
+    Undo(undo::UndoArgs),
+
+    /// Check that the files of a library are named by the naming templates
+    Audit(audit::AuditArgs),
+
+    /// Delete the expired files of the [[retention]] directories of the config file
+    Cleanup(retention::CleanupArgs),
+
+    /// Send a test notification with the email and MQTT settings
+    Notify(notification::NotifyArgs),
+
+    /// Check the directories, unrar library, notification targets, metadata APIs and free space
+    /// that `dessert run` with the same flags depends on
+    Doctor(RunArgs),
+
+    /// Interactively write a config file for the basic settings
+    Init(init::InitArgs),
+
+    /// Work with the config file
+    Config {
+        #[clap(subcommand)]
+        command: config::ConfigCommand,
+    },
 
-    Ok(())
+    /// Manage systemd integration
+    Systemd {
+        #[clap(subcommand)]
+        command: systemd::SystemdCommand,
+    },
+
+    /// Print a completion script for the shell, e.g. `dessert completions bash`
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page, for packaging
+    #[clap(hide = true)]
+    Man,
 }
 
-fn set_up_logging() -> Result<File> {
-    let log_file = NamedTempFile::new().context("Failed to create log file")?;
-    let read_handle = log_file
-        .reopen()
-        .context("Failed to create read handle for log file")?;
-    WriteLogger::init(
-        simplelog::LevelFilter::Info,
-        simplelog::Config::default(),
-        log_file,
-    )
-    .context("Failed to initialize logger")?;
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[clap(flatten)]
+    sources: Sources,
 
-    Ok(read_handle)
+    #[clap(flatten)]
+    options: Options,
 }
 
-fn run(args: &Args) -> Result<String> {
-    verify_paths(args)?;
-    info!("Verified paths");
+#[derive(clap::Args, Debug)]
+struct Sources {
+    /// Source directory, rar file, ISO image, split file or video file to process, instead of
+    /// the source directories of the flag or config file
+    #[clap(value_name = "SOURCE", conflicts_with = "source_directory")]
+    source: Option<PathBuf>,
 
-    let rar_file = find_rar_file(&args.source_directory)?;
-    info!("Found rar file: {:?}", rar_file);
+    /// Destination directory, instead of the destination directories of the flag or config file
+    #[clap(value_name = "DESTINATION", conflicts_with = "destination_directory")]
+    destination: Option<PathBuf>,
 
-    let destination_file_name = get_destination_file_name(&rar_file)?;
-    info!(
-        "Determined destination file name: {:?}",
-        destination_file_name
-    );
+    /// Source directory, rar file, ISO image, split file or video file to process, may be
+    /// repeated
+    #[clap(short, long)]
+    source_directory: Vec<PathBuf>,
 
-    extract_rar_file(
-        &rar_file,
-        &args.destination_directory,
-        &destination_file_name,
-    )?;
-    info!("Extracted rar file");
+    /// Destination directory, either one shared by all sources or one per source in the same order
+    #[clap(short, long)]
+    destination_directory: Vec<PathBuf>,
 
-    Ok(destination_file_name)
+    /// Read the sources from stdin, one path per line, e.g. from `find /downloads -mtime -1`
+    #[clap(long, conflicts_with_all = ["source", "source_directory"])]
+    stdin: bool,
 }
 
-fn verify_paths(args: &Args) -> Result<()> {
-    if !args.source_directory.is_dir() {
-        return Err(anyhow!("Source directory is not a directory"));
+impl Sources {
+    fn jobs(&self) -> Result<Vec<Job>> {
+        let read = match self.stdin {
+            true => Sources::read_stdin()?,
+            false => Vec::new(),
+        };
+        let sources = match &self.source {
+            Some(source) => std::slice::from_ref(source),
+            None if self.stdin => read.as_slice(),
+            None => self.source_directory.as_slice(),
+        };
+        if self.stdin && sources.is_empty() {
+            return Err(anyhow!("No sources on stdin"));
+        }
+        let destinations = match &self.destination {
+            Some(destination) => std::slice::from_ref(destination),
+            None => self.destination_directory.as_slice(),
+        };
+        if sources.is_empty() {
+            return Sources::configured();
+        }
+        if destinations.is_empty() {
+            return Err(anyhow!("Missing destination directory"));
+        }
+
+        let destinations = match destinations {
+            [destination] => vec![destination; sources.len()],
+            destinations if destinations.len() == sources.len() => destinations.iter().collect(),
+            _ => {
+                return Err(anyhow!(
+                    "Expected one destination directory, or one per source directory"
+                ))
+            }
+        };
+
+        Ok(sources
+            .iter()
+            .zip(destinations)
+            .map(|(source_directory, destination_directory)| Job {
+                source_directory: source_directory.clone(),
+                destination_directory: destination_directory.clone(),
+            })
+            .collect())
     }
 
-    if !args.destination_directory.is_dir() {
-        return Err(anyhow!("Destination directory is not a directory"));
+    /// The paths of the lines of stdin, skipping empty lines and repeated paths
+    fn read_stdin() -> Result<Vec<PathBuf>> {
+        let mut sources = Vec::new();
+        for line in io::stdin().lines() {
+            let line = line.context("Failed to read sources from stdin")?;
+            let source = PathBuf::from(line.trim_end_matches('\r'));
+            if !source.as_os_str().is_empty() && !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+
+        Ok(sources)
     }
 
-    Ok(())
-}
+    /// The jobs of the `[[sources]]` tables of the config file
+    fn configured() -> Result<Vec<Job>> {
+        let config = config::current();
+        if config.sources.is_empty() {
+            return Err(anyhow!("Missing source directory"));
+        }
 
-fn find_rar_file(source_directory: &Path) -> Result<PathBuf> {
-    source_directory
-        .read_dir()
-        .context("Failed to read source directory")?
-        .flatten()
-        .filter_map(|entry| {
-            entry
-                .path()
-                .extension()
-                .and_then(OsStr::to_str)
-                .and_then(|ext| {
-                    if ext == "rar" {
-                        Some(entry.path())
-                    } else {
-                        None
-                    }
-                })
-        })
-        .next()
-        .ok_or(anyhow!("Failed to find rar file"))
+        Ok(config
+            .sources
+            .iter()
+            .map(|source| Job {
+                source_directory: source.source_directory.clone(),
+                destination_directory: source.destination_directory.clone(),
+            })
+            .collect())
+    }
 }
 
-fn get_destination_file_name(rar_file: &Path) -> Result<String> {
-    let file_name = rar_file
-        .file_stem()
-        .and_then(OsStr::to_str)
-        .ok_or(anyhow!("Failed to get rar file stem"))?;
-
-    if let Some(episode_captures) =
-        Regex::new(r"(?P<name>.*)[sS](?P<season>\d{1,2}).?[eE](?P<episode>\d{1,2})")
-            .context("Failed to compile episode regex")?
-            .captures(file_name)
-    {
-        let name = episode_captures
-            .name("name")
-            .map(|name| titlecase(name.as_str().replace('.', " ").trim()))
-            .ok_or(anyhow!("Failed to get episode name from file name"))?;
-
-        let season = episode_captures
-            .name("season")
-            .map(|season| season.as_str())
-            .ok_or(anyhow!("Failed to get episode season from file name"))?;
-
-        let episode = episode_captures
-            .name("episode")
-            .map(|episode| episode.as_str())
-            .ok_or(anyhow!("Failed to get episode number from file name"))?;
-
-        Ok(format!("{} - S{:02}E{:02}", name, season, episode))
-    } else if let Some(movie_captures) = RegexBuilder::new(r"(?P<name>.*)\.(?P<year>\d{4})")
-        .swap_greed(true)
-        .build()
-        .context("Failed to compile movie regex")?
-        .captures(file_name)
-    {
-        let name = movie_captures
-            .name("name")
-            .map(|name| titlecase(name.as_str().replace('.', " ").trim()))
-            .ok_or(anyhow!("Failed to get movie name from file name"))?;
-
-        let year = movie_captures
-            .name("year")
-            .map(|year| year.as_str())
-            .ok_or(anyhow!("Failed to get movie year from file name"))?;
-
-        Ok(format!("{} ({})", name, year))
-    } else {
-        Err(anyhow!(
-            "Failed to get destination file name from rar file stem"
-        ))
+fn main() -> std::process::ExitCode {
+    match dessert() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            std::process::ExitCode::from(exit::code(&e))
+        }
     }
 }
 
-fn extract_rar_file(rar_file: &Path, destination_directory: &Path, file_name: &str) -> Result<()> {
-    let mut archive = Archive::new(rar_file)
-        .open_for_processing()
-        .context("Failed to open rar file for processing")?;
-
-    while let Some(header) = archive.read_header().context("Failed to read rar")? {
-        archive = if header.entry().is_file() {
-            let file_extension = header
-                .entry()
-                .filename
-                .extension()
-                .and_then(OsStr::to_str)
-                .map(str::to_string)
-                .ok_or(anyhow!("Failed to get file extension from rar header"))?;
-
-            let destination = destination_directory
-                .join(file_name)
-                .with_extension(file_extension);
-
-            if destination.exists() {
-                if header.entry().unpacked_size
-                    != destination
-                        .metadata()
-                        .map(|metadata| metadata.len() as usize)
-                        .context("Failed to read file size of existing destination file")?
-                {
-                    std::fs::remove_file(&destination)
-                        .context("Failed to remove existing destination file")?;
-                    info!("Removed existing destination file: {:?}", destination)
-                } else {
-                    info!("Skipping existing destination file: {:?}", destination);
-                    break;
-                }
+fn dessert() -> Result<()> {
+    let args = std::env::args_os().collect::<Vec<_>>();
+
+    // The config subcommands inspect broken config files instead of failing to load them,
+    // init writes a new one and completions don't depend on it
+    let skipping_config = Cli::command()
+        .ignore_errors(true)
+        .try_get_matches_from(&args)
+        .is_ok_and(|matches| {
+            matches!(
+                matches.subcommand_name(),
+                Some("config" | "init" | "completions" | "man")
+            )
+        });
+    if skipping_config {
+        let cli = Cli::parse_from(&args);
+        match &cli.command {
+            Some(Command::Config { command }) => {
+                return config::run(command, cli.config.as_deref(), Cli::command())
+            }
+            Some(Command::Init(init_args)) => return init::init(init_args, cli.config.as_deref()),
+            Some(Command::Completions { shell }) => {
+                clap_complete::generate(*shell, &mut Cli::command(), "dessert", &mut io::stdout());
+                return Ok(());
             }
+            Some(Command::Man) => return man::render(Cli::command(), &mut io::stdout()),
+            _ => {}
+        }
+    }
+
+    let config = config::Config::load(config::path_from_args(&args).as_deref(), &Cli::command())?;
+
+    let log_file = set_up_logging(config::log_level_from_args(&args), &config.logging)?;
+
+    let matches = config.apply_to(Cli::command())?.get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    #[cfg(feature = "plugins")]
+    dessert::plugins::load(&config.plugins)?;
+    config::set(config);
+
+    match cli.command {
+        Some(Command::Serve(serve_args)) => server::serve(&serve_args, log_file),
+        Some(Command::Watch(watch_args)) => watch::watch(&watch_args, log_file),
+        Some(Command::Poll(poll_args)) => poll::poll(&poll_args, log_file),
+        Some(Command::Run(run_args)) => run_batch(&run_args, log_file),
+        Some(Command::Parse(parse_args)) => parse::show(&parse_args),
+        Some(Command::History(history_args)) => history::show(&history_args),
+        Some(Command::Retry(retry_args)) => retry::retry(&retry_args, log_file),
+        Some(Command::Undo(undo_args)) => undo::undo(&undo_args),
+        Some(Command::Audit(audit_args)) => audit::audit(&audit_args),
+        Some(Command::Cleanup(cleanup_args)) => retention::cleanup(&cleanup_args),
+        Some(Command::Notify(notify_args)) => notification::send_test(&notify_args),
+        Some(Command::Doctor(doctor_args)) => doctor::doctor(&doctor_args),
+        Some(Command::Init(init_args)) => init::init(&init_args, cli.config.as_deref()),
+        Some(Command::Config { command }) => {
+            config::run(&command, cli.config.as_deref(), Cli::command())
+        }
+        Some(Command::Systemd { command }) => systemd::run(&command),
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "dessert", &mut io::stdout());
+            Ok(())
+        }
+        Some(Command::Man) => man::render(Cli::command(), &mut io::stdout()),
+        None => run_batch(&cli.run, log_file),
+    }
+}
 
-            header
-                .extract_to(destination)
-                .context("Failed to extract rar file")?
-        } else {
-            header.skip().context("Failed to skip rar file header")?
+/// Processes the source directories once, then notifies of the outcome
+fn run_batch(args: &RunArgs, mut log_file: File) -> Result<()> {
+    let options = &args.options;
+    options.drop_privileges()?;
+    let jobs = args
+        .sources
+        .jobs()?
+        .into_iter()
+        .map(|job| options.map_source(job))
+        .filter(|job| {
+            job.as_ref()
+                .map_or(true, |job| options.selects(&job.source_directory))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let started = Instant::now();
+    let pipeline = Pipeline::new(options.clone());
+    let _expiry = options.timeout.map(|run_timeout| {
+        timeout::Watchdog::start(run_timeout, move || timeout::expire_run(run_timeout))
+    });
+    let stuck = match options.timeout {
+        Some(run_timeout) => Some(stuck_watchdog(
+            run_timeout,
+            &pipeline,
+            jobs.len(),
+            log_file.try_clone().context("Failed to open log file")?,
+        )),
+        None => None,
+    };
+    let (outcomes, details) = pipeline.run_all(jobs);
+    drop(stuck);
+    let summary = Summary::new(&outcomes, started.elapsed());
+    summary.report();
+    output::print(&outcomes, &details);
+    pipeline.notify(&summary, &mut log_file)?;
+    exit::check(&outcomes)
+}
+
+/// Notifies of the run failing and exits, once its jobs haven't stopped within the grace period
+/// of timing out
+fn stuck_watchdog(
+    run_timeout: Duration,
+    pipeline: &Pipeline,
+    jobs: usize,
+    mut log_file: File,
+) -> timeout::Watchdog {
+    let pipeline = pipeline.clone();
+    let started = Instant::now();
+    timeout::Watchdog::start(run_timeout + timeout::GRACE, move || {
+        let summary = Summary {
+            found: jobs,
+            failed: jobs,
+            elapsed: started.elapsed(),
+            ..Default::default()
         };
+        summary.report();
+        if let Err(e) = pipeline.notify(&summary, &mut log_file) {
+            log::error!("{e}");
+        }
+        timeout::exit("The run");
+    })
+}
+
+/// Logs to a temporary file, whose read handle is returned for the notifications, to the
+/// persistent log file if a log directory is configured, to the system log if one is configured
+/// and to the terminal if there is one.
+/// A level given on the command line applies to all of them
+fn set_up_logging(level: Option<log::LevelFilter>, logging: &config::Logging) -> Result<File> {
+    let console_level = level.unwrap_or(logging.console_level);
+    let level = level.unwrap_or(logging.level);
+
+    let log_file = match dirs::cache_dir() {
+        Some(cache_dir) => NamedTempFile::new_in(cache_dir),
+        None => NamedTempFile::new(),
+    }
+    .context("Failed to create log file")?;
+    let read_handle = log_file
+        .reopen()
+        .context("Failed to create read handle for log file")?;
+
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![WriteLogger::new(
+        level,
+        simplelog::Config::default(),
+        log_file,
+    )];
+    if let Some(directory) = &logging.directory {
+        let log_file = logfile::RotatingFile::open(directory, logging)?;
+        loggers.push(match logging.format {
+            // The persistent log spans days, so it is timestamped with dates
+            config::Format::Text => WriteLogger::new(
+                level,
+                simplelog::ConfigBuilder::new()
+                    .set_time_format_rfc3339()
+                    .build(),
+                log_file,
+            ),
+            config::Format::Json => jsonlog::JsonLogger::new(level, log_file),
+        });
+        if logging.job_logs {
+            loggers.push(logfile::JobLogger::new(level));
+        }
+    }
+    if let Some(system) = logging.system {
+        loggers.push(syslog::SystemLogger::new(
+            level,
+            system,
+            logging.unit.clone(),
+        )?);
     }
+    if std::io::stderr().is_terminal() && console_level != log::LevelFilter::Off {
+        loggers.push(Box::new(status::ConsoleLogger(TermLogger::new(
+            console_level,
+            simplelog::Config::default(),
+            TerminalMode::Stderr,
+            ColorChoice::Auto,
+        ))));
+        output::set_console_logging();
+    }
+    loggers.push(output::WarningLogger::new(level));
+    loggers.push(status::TailLogger::new(console_level));
+    let max_level = loggers.iter().map(|logger| logger.level()).max();
+    log::set_boxed_logger(Box::new(redact::Logger(CombinedLogger::new(loggers))))
+        .context("Failed to initialize logger")?;
+    log::set_max_level(max_level.unwrap_or(log::LevelFilter::Off));
 
-    Ok(())
+    Ok(read_handle)
 }
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use clap_mangen::roff::{bold, italic, roman, Roff};
+use clap_mangen::Man;
+use dessert::{config, exit};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+const EXAMPLE_CONFIG: &str = include_str!("../dessert.example.toml");
+
+/// Writes the man page of dessert, with its subcommands, config keys and exit codes
+pub fn render(command: clap::Command, out: &mut dyn Write) -> Result<()> {
+    let mut command = command.disable_help_subcommand(true);
+    command.build();
+    let man = Man::new(command.clone());
+
+    let mut roff = Roff::new();
+    roff.control("SH", ["CONFIGURATION"])
+        .text([
+            roman("Settings are read from the file given with "),
+            bold("--config"),
+            roman(" or "),
+            bold("DESSERT_CONFIG"),
+            roman(", else from "),
+            italic("dessert/config.toml"),
+            roman(" in the XDG config directory. "),
+            bold("DESSERT_<KEY>"),
+            roman(" environment variables override it, "),
+            roman("and flags given on the command line override both."),
+        ])
+        .control("PP", [])
+        .text([roman(
+            "Top level keys are the long flags of the subcommands with underscores:",
+        )]);
+    for key in option_keys(&command) {
+        roff.control("TP", []).text([bold(key)]);
+    }
+    roff.control("PP", [])
+        .text([roman("Settings without a flag go in these tables:")]);
+    for section in config::SECTIONS {
+        roff.control("TP", []).text([bold(format!("[{section}]"))]);
+    }
+    roff.control("PP", [])
+        .text([roman("An example of every setting:")])
+        .control("EX", []);
+    for line in EXAMPLE_CONFIG.lines() {
+        roff.text([roman(line)]).control("br", []);
+    }
+    roff.control("EE", []);
+
+    roff.control("SH", ["EXIT STATUS"]);
+    for (code, meaning) in exit::CODES {
+        roff.control("TP", [])
+            .text([bold(code.to_string())])
+            .text([roman(meaning)]);
+    }
+
+    man.render_title(out)
+        .and_then(|()| man.render_name_section(out))
+        .and_then(|()| man.render_synopsis_section(out))
+        .and_then(|()| man.render_description_section(out))
+        .and_then(|()| man.render_options_section(out))
+        .and_then(|()| man.render_subcommands_section(out))
+        .and_then(|()| roff.to_writer(out))
+        .and_then(|()| man.render_version_section(out))
+        .context("Failed to write man page")
+}
+
+/// The keys of every flag that can be set in the config file
+fn option_keys(command: &clap::Command) -> BTreeSet<String> {
+    let mut keys = command
+        .get_arguments()
+        .filter(|arg| arg.get_long().is_some() && !arg.is_hide_set())
+        .map(|arg| arg.get_id().to_string())
+        .filter(|id| !["help", "version"].contains(&id.as_str()))
+        .filter(|id| !config::EARLY_FLAGS.contains(&id.as_str()))
+        .collect::<BTreeSet<_>>();
+    for subcommand in command.get_subcommands() {
+        keys.extend(option_keys(subcommand));
+    }
+    keys
+}
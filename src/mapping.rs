@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rewrites paths reported by a download client running with a different view
+/// of the filesystem, e.g. inside a container
+#[derive(Deserialize)]
+pub struct Mappings {
+    #[serde(default, rename = "mapping")]
+    mappings: Vec<Mapping>,
+}
+
+#[derive(Deserialize)]
+struct Mapping {
+    /// The path as the download client reports it
+    remote: PathBuf,
+
+    /// The same path on the machine dessert runs on
+    local: PathBuf,
+}
+
+impl Mappings {
+    pub fn init_from_file(path: &Path) -> Result<Mappings> {
+        let config = fs::read_to_string(path).context("Failed to read path mapping file")?;
+
+        toml::from_str::<Mappings>(&config).context("Failed to parse path mapping file")
+    }
+
+    /// Maps the path using the mapping with the longest matching remote prefix
+    pub fn map(&self, path: &Path) -> PathBuf {
+        let mapped = self
+            .mappings
+            .iter()
+            .filter_map(|mapping| {
+                let rest = path.strip_prefix(&mapping.remote).ok()?;
+                Some((
+                    mapping.remote.components().count(),
+                    mapping.local.join(rest),
+                ))
+            })
+            .max_by_key(|(length, _)| *length);
+
+        match mapped {
+            Some((_, mapped)) => {
+                info!("Mapped {:?} to {:?}", path, mapped);
+                mapped
+            }
+            None => path.to_path_buf(),
+        }
+    }
+}
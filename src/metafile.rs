@@ -0,0 +1,153 @@
+use log::debug;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Extensions of the files of releases, left out of the names of their files
+static FILE_EXTENSION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(\.vol\d+\+\d+\.par2|(\.part\d+)?\.rar|\.r\d{2,3}|\.\d{3}|\.(par2|nfo|sfv|nzb|mkv|mp4|avi|m4v|ts|iso))$",
+    )
+    .expect("file extension regex is valid")
+});
+
+/// Releases nested deeper than this in a torrent are refused, as no real torrent is
+const MAX_DEPTH: usize = 32;
+
+/// The release names told by the `.nzb` and `.torrent` files in the directory, for releases
+/// whose archives are obfuscated. For each file, the name it holds comes before its own name
+pub fn release_names(directory: &Path) -> Vec<String> {
+    let Ok(entries) = directory.read_dir() else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+
+    let mut names = Vec::new();
+    for file in files {
+        let extension = file
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let held = match extension.as_deref() {
+            Some("nzb") => fs::read(&file)
+                .ok()
+                .and_then(|nzb| nzb_name(&String::from_utf8_lossy(&nzb))),
+            Some("torrent") => fs::read(&file)
+                .ok()
+                .and_then(|torrent| torrent_name(&torrent)),
+            _ => continue,
+        };
+        debug!("Found release name {:?} in {:?}", held, file);
+
+        let own = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+        for name in held.into_iter().chain(own) {
+            let name = FILE_EXTENSION.replace(name.trim(), "").to_string();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    names
+}
+
+/// The name in the head of the NZB, or else the file name in the subject of its first file,
+/// like `[01/42] - "Show.Name.S01E02.720p.part01.rar" yEnc (1/50)`
+fn nzb_name(nzb: &str) -> Option<String> {
+    if let Some(name) = between(nzb, r#"<meta type="name">"#, "</meta>") {
+        return Some(unescape(name));
+    }
+
+    let subject = unescape(between(nzb, r#"subject=""#, "\"")?);
+    let name = between(&subject, "\"", "\"")?;
+    Some(name.to_string())
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let to = from + text[from..].find(end)?;
+
+    Some(&text[from..to])
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// A bencoded value of a torrent
+enum Bencode<'a> {
+    Bytes(&'a [u8]),
+    Integer,
+    List,
+    Dictionary(Vec<(&'a [u8], Bencode<'a>)>),
+}
+
+/// The name of the info dictionary of the torrent, the name of its directory or single file
+fn torrent_name(torrent: &[u8]) -> Option<String> {
+    let (Bencode::Dictionary(torrent), _) = bencode(torrent, 0)? else {
+        return None;
+    };
+    let (_, Bencode::Dictionary(info)) = torrent.into_iter().find(|(key, _)| *key == b"info")?
+    else {
+        return None;
+    };
+
+    info.into_iter().find_map(|(key, value)| match value {
+        Bencode::Bytes(name) if key == b"name" => Some(String::from_utf8_lossy(name).to_string()),
+        _ => None,
+    })
+}
+
+/// The value at the start of the data, with the data left after it
+fn bencode(data: &[u8], depth: usize) -> Option<(Bencode<'_>, &[u8])> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    match data.first()? {
+        b'i' => {
+            let end = data.iter().position(|byte| *byte == b'e')?;
+            Some((Bencode::Integer, &data[end + 1..]))
+        }
+        b'l' => {
+            let mut rest = &data[1..];
+            while *rest.first()? != b'e' {
+                rest = bencode(rest, depth + 1)?.1;
+            }
+            Some((Bencode::List, &rest[1..]))
+        }
+        b'd' => {
+            let mut entries = Vec::new();
+            let mut rest = &data[1..];
+            while *rest.first()? != b'e' {
+                let (Bencode::Bytes(key), after_key) = bencode(rest, depth + 1)? else {
+                    return None;
+                };
+                let (value, after_value) = bencode(after_key, depth + 1)?;
+                entries.push((key, value));
+                rest = after_value;
+            }
+            Some((Bencode::Dictionary(entries), &rest[1..]))
+        }
+        b'0'..=b'9' => {
+            let colon = data.iter().position(|byte| *byte == b':')?;
+            let length = std::str::from_utf8(&data[..colon])
+                .ok()?
+                .parse::<usize>()
+                .ok()?;
+            let bytes = data.get(colon + 1..colon + 1 + length)?;
+            Some((Bencode::Bytes(bytes), &data[colon + 1 + length..]))
+        }
+        _ => None,
+    }
+}
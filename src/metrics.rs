@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+
+/// Upper bounds in seconds of the extraction duration histogram buckets
+const DURATION_BUCKETS: [f64; 10] = [
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+static SUCCEEDED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+static EXTRACTED_BYTES: AtomicU64 = AtomicU64::new(0);
+static NOTIFICATION_FAILURES: AtomicU64 = AtomicU64::new(0);
+static QUEUED: AtomicI64 = AtomicI64::new(0);
+static RUNNING: AtomicI64 = AtomicI64::new(0);
+static EXTRACTION_DURATION: Mutex<Histogram> = Mutex::new(Histogram {
+    buckets: [0; DURATION_BUCKETS.len()],
+    count: 0,
+    sum: 0.0,
+});
+
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+pub fn job_finished(succeeded: bool) {
+    match succeeded {
+        true => SUCCEEDED.fetch_add(1, Ordering::Relaxed),
+        false => FAILED.fetch_add(1, Ordering::Relaxed),
+    };
+}
+
+pub fn extracted(bytes: u64, duration: Duration) {
+    EXTRACTED_BYTES.fetch_add(bytes, Ordering::Relaxed);
+
+    let seconds = duration.as_secs_f64();
+    let mut histogram = EXTRACTION_DURATION.lock().unwrap();
+    for (bucket, bound) in histogram.buckets.iter_mut().zip(DURATION_BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+pub fn notification_failed() {
+    NOTIFICATION_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A daemon has accepted a job that waits for a free slot
+pub fn job_queued() {
+    QUEUED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A queued job has started, until the guard is dropped
+pub fn job_started() -> RunningGuard {
+    QUEUED.fetch_sub(1, Ordering::Relaxed);
+    RUNNING.fetch_add(1, Ordering::Relaxed);
+    RunningGuard
+}
+
+/// A queued job was dropped without running
+pub fn job_cancelled() {
+    QUEUED.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut text = String::new();
+
+    let _ = writeln!(
+        text,
+        "# HELP dessert_jobs_total Jobs processed, by outcome\n\
+         # TYPE dessert_jobs_total counter\n\
+         dessert_jobs_total{{outcome=\"succeeded\"}} {}\n\
+         dessert_jobs_total{{outcome=\"failed\"}} {}",
+        SUCCEEDED.load(Ordering::Relaxed),
+        FAILED.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        text,
+        "# HELP dessert_extracted_bytes_total Bytes extracted from archives\n\
+         # TYPE dessert_extracted_bytes_total counter\n\
+         dessert_extracted_bytes_total {}",
+        EXTRACTED_BYTES.load(Ordering::Relaxed)
+    );
+
+    let histogram = EXTRACTION_DURATION.lock().unwrap();
+    let _ = writeln!(
+        text,
+        "# HELP dessert_extraction_duration_seconds Time spent extracting an archive\n\
+         # TYPE dessert_extraction_duration_seconds histogram"
+    );
+    for (count, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+        let _ = writeln!(
+            text,
+            "dessert_extraction_duration_seconds_bucket{{le=\"{bound}\"}} {count}"
+        );
+    }
+    let _ = writeln!(
+        text,
+        "dessert_extraction_duration_seconds_bucket{{le=\"+Inf\"}} {count}\n\
+         dessert_extraction_duration_seconds_sum {}\n\
+         dessert_extraction_duration_seconds_count {count}",
+        histogram.sum,
+        count = histogram.count
+    );
+    drop(histogram);
+
+    let _ = writeln!(
+        text,
+        "# HELP dessert_notification_failures_total Notifications that failed to send\n\
+         # TYPE dessert_notification_failures_total counter\n\
+         dessert_notification_failures_total {}",
+        NOTIFICATION_FAILURES.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        text,
+        "# HELP dessert_queue_depth Jobs waiting for a free slot\n\
+         # TYPE dessert_queue_depth gauge\n\
+         dessert_queue_depth {}\n\
+         # HELP dessert_jobs_running Jobs currently running\n\
+         # TYPE dessert_jobs_running gauge\n\
+         dessert_jobs_running {}",
+        QUEUED.load(Ordering::Relaxed),
+        RUNNING.load(Ordering::Relaxed)
+    );
+
+    text
+}
+
+pub fn response() -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(render())
+        .with_header(Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap())
+}
+
+/// The health of the daemon as JSON, with status 503 when it is unhealthy
+pub fn health_response() -> Response<std::io::Cursor<Vec<u8>>> {
+    let health = crate::status::health();
+    Response::from_data(health.to_json().to_string().into_bytes())
+        .with_status_code(if health.healthy() { 200 } else { 503 })
+        .with_header(
+            "Content-Type: application/json"
+                .parse::<Header>()
+                .expect("Valid header"),
+        )
+}
+
+/// Serves `/metrics` and `/healthz` on its own thread, for the daemon modes without an HTTP API
+pub fn serve(listen: &str) -> Result<()> {
+    let server = Server::http(listen).map_err(|e| anyhow!("Failed to listen on {listen}: {e}"))?;
+    info!("Serving metrics on {listen}");
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let result = match request.url() {
+                "/metrics" => request.respond(response()),
+                "/healthz" => request.respond(health_response()),
+                _ => request.respond(Response::from_string("Not found").with_status_code(404)),
+            };
+            if let Err(e) = result {
+                error!("Failed to send response: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::{redact, secrets};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, error};
+use rumqttc::{MqttOptions, Outgoing, QoS};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    /// The host name of the MQTT broker
+    host: String,
+
+    /// The port of the MQTT broker
+    #[serde(default = "default_port")]
+    port: u16,
+
+    /// Events are published to `<topic>/<event>`
+    #[serde(default = "default_topic")]
+    topic: String,
+
+    /// The client id used when connecting
+    #[serde(default = "default_client_id")]
+    client_id: String,
+
+    username: Option<String>,
+
+    password: Option<String>,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_topic() -> String {
+    "dessert".to_string()
+}
+
+fn default_client_id() -> String {
+    "dessert".to_string()
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    Started,
+    Extracted,
+    Imported,
+    Failed,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Started => "started",
+            Event::Extracted => "extracted",
+            Event::Imported => "imported",
+            Event::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct Payload<'a> {
+    pub source_directory: Option<&'a Path>,
+    pub title: Option<&'a str>,
+    pub file_name: Option<&'a str>,
+    pub error: Option<String>,
+    pub log_file: Option<&'a Path>,
+    /// Whether the volumes of the archive were reconstructed from its recovery volumes
+    pub repaired: bool,
+}
+
+pub struct Client {
+    client: rumqttc::Client,
+    topic: String,
+    connection: Option<JoinHandle<()>>,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let config = fs::read_to_string(path).context("Failed to read MQTT config file")?;
+        let mut config = config
+            .parse::<toml::Table>()
+            .context("Failed to parse MQTT config file")?;
+        secrets::resolve(&mut config, "password")?;
+        toml::Value::Table(config)
+            .try_into::<Config>()
+            .context("Failed to parse MQTT config file")
+    }
+}
+
+impl Client {
+    pub fn init_from_file(path: &Path) -> Result<Client> {
+        Client::init(Config::from_file(path)?)
+    }
+
+    pub fn init(config: Config) -> Result<Client> {
+        let mut options = MqttOptions::new(config.client_id, config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = config.username {
+            options.set_credentials(username, config.password.unwrap_or_default());
+        }
+
+        let (client, mut connection) = rumqttc::Client::new(options, 16);
+        let connection = thread::spawn(move || {
+            for notification in connection.iter() {
+                match notification {
+                    Ok(rumqttc::Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("MQTT connection failed: {e}");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Client {
+            client,
+            topic: config.topic,
+            connection: Some(connection),
+        })
+    }
+
+    /// Connects to the broker and waits for it to accept the connection, without publishing
+    pub fn check(config: Config) -> Result<()> {
+        let mut options = MqttOptions::new(config.client_id, config.host, config.port);
+        if let Some(username) = config.username {
+            options.set_credentials(username, config.password.unwrap_or_default());
+        }
+
+        let (client, mut connection) = rumqttc::Client::new(options, 16);
+        loop {
+            match connection.recv_timeout(Duration::from_secs(10)) {
+                Ok(Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)))) => break,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => return Err(e).context("Failed to connect to MQTT broker"),
+                Err(_) => return Err(anyhow!("Timed out waiting for the MQTT broker")),
+            }
+        }
+        let _ = client.disconnect();
+
+        Ok(())
+    }
+
+    /// Publishes a message to `<topic>/test` and waits for the broker to acknowledge it
+    pub fn send_test(config: Config) -> Result<()> {
+        let mut options = MqttOptions::new(config.client_id, config.host, config.port);
+        if let Some(username) = config.username {
+            options.set_credentials(username, config.password.unwrap_or_default());
+        }
+
+        let (client, mut connection) = rumqttc::Client::new(options, 16);
+        client
+            .publish(
+                format!("{}/test", config.topic.trim_end_matches('/')),
+                QoS::AtLeastOnce,
+                false,
+                serde_json::json!({ "event": "test" }).to_string(),
+            )
+            .context("Failed to publish MQTT test message")?;
+
+        loop {
+            match connection.recv_timeout(Duration::from_secs(10)) {
+                Ok(Ok(rumqttc::Event::Incoming(rumqttc::Packet::PubAck(_)))) => break,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => return Err(e).context("Failed to connect to MQTT broker"),
+                Err(_) => return Err(anyhow!("Timed out waiting for the MQTT broker")),
+            }
+        }
+        let _ = client.disconnect();
+
+        Ok(())
+    }
+
+    pub fn publish(&self, event: Event, payload: &Payload) {
+        let topic = format!("{}/{}", self.topic.trim_end_matches('/'), event.name());
+        let body =
+            redact::redact(&serde_json::json!({ "event": event, "job": payload }).to_string())
+                .into_owned();
+        debug!("Publishing MQTT event to {topic}: {body}");
+
+        if let Err(e) = self.client.publish(topic, QoS::AtLeastOnce, false, body) {
+            error!("Failed to publish MQTT event: {e}");
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if self.client.disconnect().is_ok() {
+            if let Some(connection) = self.connection.take() {
+                let _ = connection.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::parser;
+use crate::release::Release;
+use anyhow::{anyhow, Result};
+use log::warn;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Templates for destination file names, e.g. `{name} - S{season:02}E{episode:02}`.
+///
+/// Variables are written as `{variable}`, numbers can be zero-padded to a width as `{variable:02}`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Naming {
+    /// Template for episodes, with the variables `name`, `season` and `episode`
+    pub episode: String,
+
+    /// Template for movies, with the variables `name` and `year`
+    pub movie: String,
+
+    /// Template for the directory of an album, with the variables `artist`, `name` and `year`
+    pub album: String,
+
+    /// Template for the tracks of an album, with the variables `track` and `title`
+    pub track: String,
+
+    /// Unicode normalization of the file names, so names from sources that decompose
+    /// characters, like macOS, match the existing files
+    pub normalization: Normalization,
+
+    /// How the names of shows and movies are cased
+    pub case_style: CaseStyle,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStyle {
+    /// Title case, by the rules of the locale
+    #[default]
+    Title,
+
+    /// As written in the release name, with its dots and underscores as spaces
+    Original,
+
+    /// All lowercase
+    Lower,
+}
+
+impl CaseStyle {
+    /// The release with its name cased by the style, looking up the original case in the names
+    /// it was parsed from. Albums keep their names as written
+    pub fn apply(self, release: Release, names: &[&str]) -> Release {
+        let name = match self {
+            _ if matches!(release, Release::Album { .. }) => return release,
+            CaseStyle::Title => return release,
+            CaseStyle::Original => match parser::original_case(release.name(), names) {
+                Some(name) => name,
+                None => {
+                    warn!(
+                        "Failed to find {:?} in the release name, keeping its case",
+                        release.name()
+                    );
+                    return release;
+                }
+            },
+            CaseStyle::Lower => release.name().to_lowercase(),
+        };
+
+        release.with_name(name)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Composed characters, as written by Linux and Windows
+    #[default]
+    Nfc,
+
+    /// Decomposed characters, as written by macOS
+    Nfd,
+
+    /// The name as rendered
+    None,
+}
+
+impl Normalization {
+    pub fn apply(self, file_name: &str) -> String {
+        match self {
+            Normalization::Nfc => file_name.nfc().collect(),
+            Normalization::Nfd => file_name.nfd().collect(),
+            Normalization::None => file_name.to_string(),
+        }
+    }
+}
+
+impl Default for Naming {
+    fn default() -> Naming {
+        Naming {
+            episode: "{name} - S{season:02}E{episode:02}".to_string(),
+            movie: "{name} ({year})".to_string(),
+            album: "{artist}/{name} ({year})".to_string(),
+            track: "{track:02} - {title}".to_string(),
+            normalization: Normalization::default(),
+            case_style: CaseStyle::default(),
+        }
+    }
+}
+
+impl Naming {
+    pub fn file_name(&self, release: &Release) -> Result<String> {
+        match release {
+            Release::Episode { .. } => render(&self.episode, release),
+            Release::Movie { .. } => render(&self.movie, release),
+            Release::Album { .. } => render(&self.album, release),
+        }
+    }
+
+    /// The file name of a track of an album, without its extension
+    pub fn track_name(&self, track: u32, title: &str) -> Result<String> {
+        render_with(&self.track, |variable| match variable {
+            "track" => Some(Value::Number(track)),
+            "title" => Some(Value::Text(title)),
+            _ => None,
+        })
+    }
+
+    /// The release of a file name rendered by one of the templates, the inverse of
+    /// [`Naming::file_name`], or `None` if it doesn't match either. Albums aren't parsed, their
+    /// directories hold tracks rather than videos
+    pub fn parse(&self, file_name: &str) -> Option<Release> {
+        if let Some(variables) = variables(&self.episode, file_name) {
+            return Some(Release::Episode {
+                name: variables.get("name")?.to_string(),
+                season: variables.get("season")?.parse().ok()?,
+                episode: variables.get("episode")?.parse().ok()?,
+            });
+        }
+
+        let variables = variables(&self.movie, file_name)?;
+        Some(Release::Movie {
+            name: variables.get("name")?.to_string(),
+            year: variables.get("year")?.parse().ok()?,
+        })
+    }
+}
+
+/// The values of the variables of a name rendered by the template, or `None` if the template
+/// doesn't render it, e.g. because a variable used twice has two values
+fn variables<'a>(template: &str, file_name: &'a str) -> Option<HashMap<String, &'a str>> {
+    let mut pattern = "^".to_string();
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        pattern.push_str(&regex::escape(&rest[..start]));
+        let end = start + rest[start..].find('}')?;
+        let variable = rest[start + 1..end].split(':').next()?;
+        match variable {
+            "name" => pattern.push_str("(.+?)"),
+            _ => pattern.push_str(r"(\d+)"),
+        }
+        names.push(variable.to_string());
+        rest = &rest[end + 1..];
+    }
+    pattern.push_str(&regex::escape(rest));
+    pattern.push('$');
+
+    let captures = Regex::new(&pattern).ok()?.captures(file_name)?;
+    let mut variables = HashMap::new();
+    for (name, value) in names.into_iter().zip(captures.iter().skip(1)) {
+        let value = value?.as_str();
+        if *variables.entry(name).or_insert(value) != value {
+            return None;
+        }
+    }
+
+    Some(variables)
+}
+
+/// Renders the template with the variables of the release
+pub fn render(template: &str, release: &Release) -> Result<String> {
+    match release {
+        Release::Episode {
+            name,
+            season,
+            episode,
+        } => render_with(template, |variable| match variable {
+            "name" => Some(Value::Text(name)),
+            "season" => Some(Value::Number(*season)),
+            "episode" => Some(Value::Number(*episode)),
+            _ => None,
+        }),
+        Release::Movie { name, year } => render_with(template, |variable| match variable {
+            "name" => Some(Value::Text(name)),
+            "year" => Some(Value::Number(*year)),
+            _ => None,
+        }),
+        Release::Album { artist, name, year } => render_with(template, |variable| match variable {
+            "artist" => Some(Value::Text(artist)),
+            "name" => Some(Value::Text(name)),
+            "year" => Some(Value::Number(*year)),
+            _ => None,
+        }),
+    }
+}
+
+enum Value<'a> {
+    Text(&'a str),
+    Number(u32),
+}
+
+fn render_with<'a>(template: &str, lookup: impl Fn(&str) -> Option<Value<'a>>) -> Result<String> {
+    let mut file_name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        file_name.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or(anyhow!("Unclosed variable in naming template {template:?}"))?;
+        let (variable, width) = match rest[start + 1..end].split_once(':') {
+            Some((variable, width)) => (
+                variable,
+                width.parse::<usize>().map_err(|_| {
+                    anyhow!("Invalid width {width:?} in naming template {template:?}")
+                })?,
+            ),
+            None => (&rest[start + 1..end], 0),
+        };
+
+        match lookup(variable) {
+            Some(Value::Text(text)) => file_name.push_str(text),
+            Some(Value::Number(number)) => file_name.push_str(&format!("{number:0width$}")),
+            None => {
+                return Err(anyhow!(
+                    "Unknown variable {variable:?} in naming template {template:?}"
+                ))
+            }
+        }
+
+        rest = &rest[end + 1..];
+    }
+    file_name.push_str(rest);
+
+    Ok(file_name)
+}
@@ -0,0 +1,136 @@
+use crate::release::Release;
+use anyhow::{Context, Result};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes a minimal Kodi-compatible `.nfo` file next to the destination file
+pub fn write(release: &Release, destination_directory: &Path, file_name: &str) -> Result<PathBuf> {
+    let destination = path(release, destination_directory, file_name);
+
+    fs::write(&destination, render(release)).context("Failed to write nfo file")?;
+
+    Ok(destination)
+}
+
+/// Where [`write`] writes the `.nfo` file, inside the directory of albums as `album.nfo`
+pub fn path(release: &Release, destination_directory: &Path, file_name: &str) -> PathBuf {
+    match release {
+        Release::Album { .. } => destination_directory.join(file_name).join("album.nfo"),
+        _ => destination_directory.join(format!("{file_name}.nfo")),
+    }
+}
+
+fn render(release: &Release) -> String {
+    let body = match release {
+        Release::Episode {
+            name,
+            season,
+            episode,
+        } => format!(
+            r"<episodedetails>
+    <title>{} - S{season:02}E{episode:02}</title>
+    <showtitle>{}</showtitle>
+    <season>{season}</season>
+    <episode>{episode}</episode>
+</episodedetails>",
+            escape(name),
+            escape(name),
+        ),
+        Release::Movie { name, year } => format!(
+            r"<movie>
+    <title>{}</title>
+    <year>{year}</year>
+</movie>",
+            escape(name),
+        ),
+        Release::Album { artist, name, year } => format!(
+            r"<album>
+    <title>{}</title>
+    <artist>{}</artist>
+    <year>{year}</year>
+</album>",
+            escape(name),
+            escape(artist),
+        ),
+    };
+
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n{body}\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Most lines of release notes kept for notifications
+const RELEASE_NOTES_LINES: usize = 60;
+
+/// The release's own `.nfo` in its directory, cleaned up for notifications. The ASCII art
+/// around the source and encode details is dropped, as are lines past the first 60
+pub fn release_notes(directory: &Path) -> Option<String> {
+    let mut files = fs::read_dir(directory)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("nfo"))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let file = files.first()?;
+    let contents = match fs::read(file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            debug!("Failed to read release notes {:?}: {e}", file);
+            return None;
+        }
+    };
+
+    let notes = clean(&contents);
+    (!notes.is_empty()).then_some(notes)
+}
+
+/// Keeps the printable ASCII of the lines with any letters or digits, as `.nfo` files are
+/// usually code page 437 with box drawing characters
+fn clean(contents: &[u8]) -> String {
+    let mut lines = Vec::new();
+    for line in contents.split(|&byte| byte == b'\n') {
+        let line = line
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' => byte as char,
+                _ => ' ',
+            })
+            .collect::<String>();
+        let line = line.trim_end();
+
+        if line.chars().any(|c| c.is_ascii_alphanumeric()) {
+            lines.push(line.to_string());
+        } else if lines.last().is_some_and(|last: &String| !last.is_empty()) {
+            lines.push(String::new());
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines.truncate(RELEASE_NOTES_LINES);
+
+    // Without the indentation shared by every line, left by the art
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
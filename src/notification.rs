@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::exit::{Code, ExitCode};
+use dessert::{config, email, mqtt};
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct NotifyArgs {
+    /// Email report settings, instead of the [email] section of the config file
+    #[clap(short, long)]
+    email_config_file: Option<PathBuf>,
+
+    /// MQTT broker settings, instead of the [mqtt] section of the config file
+    #[clap(long)]
+    mqtt_config_file: Option<PathBuf>,
+}
+
+/// Sends a test notification through every configured channel
+pub fn send_test(args: &NotifyArgs) -> Result<()> {
+    let config = config::current();
+    let email = match &args.email_config_file {
+        Some(path) => Some(email::Client::init_from_file(path)?),
+        None => config.email.clone(),
+    };
+    let mqtt = match &args.mqtt_config_file {
+        Some(path) => Some(mqtt::Config::from_file(path)?),
+        None => config.mqtt.clone(),
+    };
+
+    if email.is_none() && mqtt.is_none() {
+        return Err(anyhow!(
+            "No email or MQTT settings to send a notification with"
+        ));
+    }
+    if let Some(email) = email {
+        email
+            .send_test()
+            .context("Failed to send test email")
+            .exit_code(Code::Notification)?;
+        println!("Sent test email");
+    }
+    if let Some(mqtt) = mqtt {
+        mqtt::Client::send_test(mqtt)
+            .context("Failed to publish test message")
+            .exit_code(Code::Notification)?;
+        println!("Published test message");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use crate::error::DessertError;
+use crate::release::Release;
+use crate::{context, Import, Job, Outcome};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use simplelog::SharedLogger;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How the outcome of a run is printed to stdout
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Format {
+    /// A table of totals, and what would be done in a dry run
+    #[default]
+    Text,
+
+    /// One JSON object per job, with its status, source, parsed release, destination, duration
+    /// and warnings
+    Json,
+
+    /// Only the destination path of each imported release, one per line, for scripts
+    Destination,
+}
+
+static FORMAT: Mutex<Format> = Mutex::new(Format::Text);
+
+/// Whether records are logged to the terminal as they happen
+static CONSOLE_LOGGING: AtomicBool = AtomicBool::new(false);
+
+pub fn set(format: Format) {
+    *FORMAT.lock().unwrap() = format;
+}
+
+fn format() -> Format {
+    *FORMAT.lock().unwrap()
+}
+
+pub fn set_console_logging() {
+    CONSOLE_LOGGING.store(true, Ordering::Relaxed);
+}
+
+pub fn console_logging() -> bool {
+    CONSOLE_LOGGING.load(Ordering::Relaxed)
+}
+
+/// Prints text for people to stdout, unless stdout is kept for results
+pub fn text(text: &str) {
+    if format() == Format::Text {
+        print!("{text}");
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+/// What the report of a job needs besides its outcome, taken on the thread that ran it
+pub struct Details {
+    source: PathBuf,
+    duration: Duration,
+    warnings: Vec<String>,
+}
+
+impl Details {
+    /// The details of the job the thread just ran, with the warnings and errors logged in it
+    pub fn new(job: &Job, duration: Duration) -> Details {
+        Details {
+            source: job.source_directory.clone(),
+            duration,
+            warnings: context::take_warnings(),
+        }
+    }
+}
+
+/// The outcome of a job, as printed by `--output json`
+#[derive(Serialize)]
+struct Report<'a> {
+    status: Status,
+    source: &'a Path,
+    release: Option<&'a Release>,
+    destination: Option<&'a PathBuf>,
+    files: &'a [PathBuf],
+    duration_secs: f64,
+    warnings: Vec<&'a String>,
+    error: Option<String>,
+}
+
+impl Report<'_> {
+    fn new<'a>(import: &'a Outcome, details: &'a Details) -> Report<'a> {
+        let error = import.as_ref().err().map(|e| e.to_string());
+        let status = match import {
+            Ok(_) => Status::Succeeded,
+            Err(DessertError::Skipped) => Status::Skipped,
+            Err(_) => Status::Failed,
+        };
+        let import = import.as_ref().ok();
+
+        Report {
+            status,
+            source: &details.source,
+            release: import.map(|import| &import.release),
+            destination: import.and_then(Import::destination),
+            files: import.map(|import| import.files.as_slice()).unwrap_or(&[]),
+            duration_secs: details.duration.as_secs_f64(),
+            // The error the job failed with is logged too, but reported on its own
+            warnings: details
+                .warnings
+                .iter()
+                .filter(|warning| Some(*warning) != error.as_ref())
+                .collect(),
+            error,
+        }
+    }
+}
+
+/// Prints a report of each job to stdout as JSON lines, or the destination of each imported
+/// release, if that is the output format
+pub fn print(outcomes: &[Outcome], details: &[Details]) {
+    match format() {
+        Format::Text => {}
+        Format::Json => {
+            for (import, details) in outcomes.iter().zip(details) {
+                let report = Report::new(import, details);
+                println!("{}", serde_json::to_string(&report).unwrap_or_default());
+            }
+        }
+        Format::Destination => {
+            for import in outcomes.iter().flatten() {
+                if let Some(destination) = import.destination() {
+                    println!("{}", destination.display());
+                }
+            }
+        }
+    }
+}
+
+/// Collects the warnings and errors logged in each job, see [`context::take_warnings`]
+pub struct WarningLogger {
+    level: LevelFilter,
+}
+
+impl WarningLogger {
+    /// Collects up to the level, at most warnings
+    pub fn new(level: LevelFilter) -> Box<WarningLogger> {
+        Box::new(WarningLogger {
+            level: level.min(LevelFilter::Warn),
+        })
+    }
+}
+
+impl Log for WarningLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            context::warned(&record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for WarningLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::config::Config;
+use dessert::release::{self, Release};
+use dessert::rules::Rule;
+use dessert::{aliases, config, parser};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct ParseArgs {
+    /// Release name or path of an archive, e.g. `Show.Name.S01E02.720p.rar`. The directories of
+    /// a path are hints for archives named uselessly, like `Show.Name.S03/e05.rar`
+    #[clap(required_unless_present = "corpus")]
+    name: Option<String>,
+
+    /// Parse every release name of the file, one per line, printing the file name each gets
+    /// instead. Empty lines and lines starting with `#` are skipped
+    #[clap(long, value_name = "FILE", conflicts_with = "name")]
+    corpus: Option<PathBuf>,
+
+    /// The output of an earlier `--corpus` run to compare with, failing if any name now parses
+    /// differently
+    #[clap(long, value_name = "FILE", requires = "corpus")]
+    expected: Option<PathBuf>,
+
+    /// How the release name is parsed
+    #[clap(long, value_enum, default_value = "auto")]
+    mode: release::Mode,
+
+    /// The language of the release name, casing its title by its rules
+    #[clap(long, value_enum, default_value = "en")]
+    locale: release::Locale,
+}
+
+/// What a release name was parsed as, with the rule and naming of the config file applied
+struct Parsed<'a> {
+    release: Release,
+    rule: Option<&'a Rule>,
+    file_name: String,
+}
+
+/// Prints what the release name is parsed as and the destination file name it gets, or with
+/// `--corpus` the file names of every release name of a file
+pub fn show(args: &ParseArgs) -> Result<()> {
+    let config = config::current();
+    if let Some(corpus) = &args.corpus {
+        return run_corpus(args, &config, corpus);
+    }
+    let input = args.name.as_deref().unwrap_or_default();
+    let name = release_name(input);
+
+    let Parsed {
+        release,
+        rule,
+        file_name,
+    } = parse(&config, input, args)?;
+
+    match &release {
+        Release::Episode {
+            name,
+            season,
+            episode,
+        } => {
+            println!("Type       episode");
+            println!("Name       {name}");
+            println!("Season     {season}");
+            println!("Episode    {episode}");
+        }
+        Release::Movie { name, year } => {
+            println!("Type       movie");
+            println!("Name       {name}");
+            println!("Year       {year}");
+        }
+        Release::Album { artist, name, year } => {
+            println!("Type       album");
+            println!("Artist     {artist}");
+            println!("Name       {name}");
+            println!("Year       {year}");
+        }
+    }
+    if let Some(parsed) = parser::parse(&name, args.mode) {
+        let tags = [
+            ("Quality", parsed.quality),
+            ("Source", parsed.source),
+            ("Codec", parsed.codec),
+            ("Group", parsed.group),
+        ];
+        for (tag, value) in tags {
+            if let Some(value) = value {
+                println!("{tag:<10} {value}");
+            }
+        }
+    }
+    if let Some(rule) = rule {
+        println!("Rule       {}", rule.pattern);
+    }
+    println!("File name  {file_name}");
+
+    Ok(())
+}
+
+/// The release name of a name or archive path, without the `.rar` extension
+fn release_name(input: &str) -> String {
+    let path = Path::new(input);
+    match path.extension().is_some_and(|extension| extension == "rar") {
+        true => path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into(),
+        false => input.to_string(),
+    }
+}
+
+fn parse<'a>(config: &'a Config, input: &str, args: &ParseArgs) -> Result<Parsed<'a>> {
+    let directories = Path::new(input)
+        .ancestors()
+        .skip(1)
+        .filter_map(|directory| directory.file_name()?.to_str())
+        .collect::<Vec<_>>();
+
+    let release = Release::parse_with_hints(&release_name(input), &directories, args.mode)?
+        .recased(args.locale);
+    let names = Path::new(input)
+        .ancestors()
+        .filter_map(|path| path.file_name()?.to_str())
+        .collect::<Vec<_>>();
+    let release = config.naming.case_style.apply(release, &names);
+    let mut release = aliases::apply(release, &config.aliases, &config.replacements)?;
+    let rule = Rule::find(&config.rules, &release)?;
+    if let Some(rule) = rule {
+        release = rule.apply(release)?;
+    }
+    let file_name = match rule.and_then(|rule| rule.file_name(&release)) {
+        Some(file_name) => file_name?,
+        None => config.naming.file_name(&release)?,
+    };
+
+    Ok(Parsed {
+        release,
+        rule,
+        file_name,
+    })
+}
+
+/// Prints a line of the release name and its file name, or why it failed to parse, for every
+/// name of the corpus. Redirected to a file, the output is what `--expected` compares with
+fn run_corpus(args: &ParseArgs, config: &Config, corpus: &Path) -> Result<()> {
+    let expected = match &args.expected {
+        Some(path) => Some(read_lines(path)?.into_iter().collect::<BTreeMap<_, _>>()),
+        None => None,
+    };
+
+    let (mut failed, mut changed, mut total) = (0, 0, 0);
+    for (input, _) in read_lines(corpus)? {
+        total += 1;
+        let outcome = match parse(config, &input, args) {
+            Ok(parsed) => parsed.file_name,
+            Err(e) => {
+                failed += 1;
+                format!("FAILED: {e}")
+            }
+        };
+
+        match expected.as_ref().map(|expected| expected.get(&input)) {
+            None => println!("{input}\t{outcome}"),
+            Some(Some(expected)) if *expected == outcome => {}
+            Some(Some(expected)) => {
+                changed += 1;
+                println!("- {input}\t{expected}");
+                println!("+ {input}\t{outcome}");
+            }
+            Some(None) => {
+                changed += 1;
+                println!("+ {input}\t{outcome}");
+            }
+        }
+    }
+
+    // Names expected to fail aren't regressions
+    match expected {
+        Some(_) => {
+            eprintln!("{total} names, {failed} failed to parse, {changed} changed");
+            match changed {
+                0 => Ok(()),
+                changed => Err(anyhow!("{changed} names parsed differently than expected")),
+            }
+        }
+        None => {
+            eprintln!("{total} names, {failed} failed to parse");
+            match failed {
+                0 => Ok(()),
+                failed => Err(anyhow!("{failed} names failed to parse")),
+            }
+        }
+    }
+}
+
+/// The release names the lines of a corpus or expected file start with, and what follows the
+/// tab after them
+fn read_lines(path: &Path) -> Result<Vec<(String, String)>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+
+    Ok(text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once('\t') {
+            Some((name, outcome)) => (name.to_string(), outcome.to_string()),
+            None => (line.to_string(), String::new()),
+        })
+        .collect())
+}
@@ -0,0 +1,412 @@
+//! Parses release names like `Show.Name.S01E02.720p.WEB-DL.x264-GROUP`, without any of the
+//! extraction and notification dependencies of the rest of dessert
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+use titlecase::titlecase;
+
+static EPISODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?P<name>.*)[sS](?P<season>\d{1,2}).?[eE](?P<episode>\d{1,2})").unwrap()
+});
+
+/// A season without an episode, like the directory `Show.Name.S03.1080p.WEB-DL` of a pack
+static SEASON: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"^(?P<name>.+?)[\s._-]+(?:s|season[\s._]?)(?P<season>\d{1,2})(?:[\s._-]|$)")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+/// An episode number on its own, like `e05` or `Episode 5`
+static EPISODE_NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"(?:^|[^a-z])e(?:p|pisode)?[\s._]?(?P<episode>\d{1,3})(?:\D|$)")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+static MOVIE: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"(?P<name>.*)\.(?P<year>\d{4})")
+        .swap_greed(true)
+        .build()
+        .unwrap()
+});
+
+static ANIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:\[(?P<group>[^\]]*)\][\s_.]*)?(?P<name>.+?)[\s_.]+-[\s_.]+(?P<episode>\d{1,4})\b",
+    )
+    .unwrap()
+});
+
+static ALBUM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<artist>.+?)\s+-\s+(?P<album>.+?)\s*\((?P<year>\d{4})\)(?:\s*\[(?P<format>[^\]]+)\])?",
+    )
+    .unwrap()
+});
+
+static QUALITY: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"\b(2160p|1080p|720p|576p|480p|4k)\b")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+static SOURCE: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"\b(web-?dl|webrip|web|bluray|blu-ray|bdrip|brrip|remux|hdtv|dvdrip)\b")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+static CODEC: LazyLock<Regex> = LazyLock::new(|| {
+    RegexBuilder::new(r"\b(x264|x265|h\.?264|h\.?265|hevc|avc|xvid)\b")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+});
+
+static GROUP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)-(?P<group>[^-.\s\]]+)(?:\.part\d+)?$").unwrap());
+
+/// How release names are parsed
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "pipeline", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Episodes named with SxxEyy, falling back to movies named with a year
+    #[default]
+    Auto,
+    /// Only episodes named with SxxEyy
+    Episode,
+    /// Only movies named with a year
+    Movie,
+    /// Absolute episode numbers like `[Group] Show - 123`, as the first season
+    Anime,
+    /// Albums named like `Artist - Album (2019) [FLAC]`, which would parse as movies otherwise
+    Music,
+}
+
+/// The language of release names, deciding how their titles are cased
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "pipeline", derive(clap::ValueEnum))]
+pub enum Locale {
+    /// Title case with lowercase particles, like `The Lord of the Rings`
+    #[default]
+    #[serde(rename = "en")]
+    #[cfg_attr(feature = "pipeline", value(name = "en"))]
+    English,
+    /// Only the first word capitalized, like `Hjem til jul`
+    #[serde(rename = "nb", alias = "no", alias = "nn")]
+    #[cfg_attr(feature = "pipeline", value(name = "nb", alias = "no", alias = "nn"))]
+    Norwegian,
+    /// Only the first word capitalized
+    #[serde(rename = "sv")]
+    #[cfg_attr(feature = "pipeline", value(name = "sv"))]
+    Swedish,
+    /// Only the first word capitalized
+    #[serde(rename = "da")]
+    #[cfg_attr(feature = "pipeline", value(name = "da"))]
+    Danish,
+    /// Only the first word capitalized, like `Le fabuleux destin d'Amélie Poulain`
+    #[serde(rename = "fr")]
+    #[cfg_attr(feature = "pipeline", value(name = "fr"))]
+    French,
+    /// Every word capitalized but articles, prepositions and conjunctions, like
+    /// `Das Leben der Anderen`
+    #[serde(rename = "de")]
+    #[cfg_attr(feature = "pipeline", value(name = "de"))]
+    German,
+    /// Every word capitalized, without the rules of any language
+    #[serde(rename = "off")]
+    #[cfg_attr(feature = "pipeline", value(name = "off"))]
+    Off,
+}
+
+/// German words lowercase inside titles
+const GERMAN_PARTICLES: [&str; 39] = [
+    "der", "die", "das", "den", "dem", "des", "ein", "eine", "einer", "eines", "einem", "einen",
+    "und", "oder", "aber", "im", "in", "am", "an", "auf", "aus", "bei", "mit", "nach", "von",
+    "vom", "zu", "zum", "zur", "für", "über", "unter", "vor", "durch", "gegen", "ohne", "um",
+    "bis", "als",
+];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Episode,
+    Movie,
+    Album,
+}
+
+/// What a release name says about the release. Episodes always have a season and an episode,
+/// movies always have a year, albums always have an artist and a year
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParsedRelease {
+    pub kind: Kind,
+    /// The name of the show or movie, title cased, or of the album as named
+    pub title: String,
+    pub artist: Option<String>,
+    pub year: Option<u32>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    /// The resolution, e.g. `1080p`
+    pub quality: Option<String>,
+    /// Where the release was ripped from, e.g. `WEB-DL` or `BluRay`
+    pub source: Option<String>,
+    /// The video codec, e.g. `x264`, or the audio format of albums, e.g. `FLAC`
+    pub codec: Option<String>,
+    /// The release group, e.g. `GROUP` of `Show.Name.S01E02.720p-GROUP`
+    pub group: Option<String>,
+}
+
+/// Parses the release name, or gives `None` if it isn't named the way the mode expects
+pub fn parse(name: &str, mode: Mode) -> Option<ParsedRelease> {
+    match mode {
+        Mode::Auto => parse_episode(name).or_else(|| parse_movie(name)),
+        Mode::Episode => parse_episode(name),
+        Mode::Movie => parse_movie(name),
+        Mode::Anime => parse_anime(name),
+        Mode::Music => parse_album(name),
+    }
+}
+
+/// Parses the release name with the names of the directories it is in as hints, nearest first,
+/// for archives named uselessly like `abc-s03e05` or `e05`. Directories named like a season,
+/// like `Show.Name.S03.1080p.WEB-DL`, give the title and season of an episode the name has the
+/// number of. The most specific parse with a title wins, the nearest on a tie, and without any
+/// the name is parsed on its own
+pub fn parse_with_hints(name: &str, directories: &[&str], mode: Mode) -> Option<ParsedRelease> {
+    let episodes = matches!(mode, Mode::Auto | Mode::Episode);
+    let hints = directories.iter().filter_map(|directory| {
+        match episodes && SEASON.is_match(directory) && !EPISODE.is_match(directory) {
+            true => parse_in_season(name, directory),
+            false => parse(directory, mode),
+        }
+    });
+
+    parse(name, mode)
+        .into_iter()
+        .chain(hints)
+        .filter(|release| !release.title.is_empty())
+        .fold(None, |best: Option<ParsedRelease>, release| match best {
+            Some(best) if best.specificity() >= release.specificity() => Some(best),
+            _ => Some(release),
+        })
+        .or_else(|| parse(name, mode))
+}
+
+/// The episode of the name in the season the directory is named like
+fn parse_in_season(name: &str, directory: &str) -> Option<ParsedRelease> {
+    let captures = SEASON.captures(directory)?;
+    let (season, episode) = match parse_episode(name) {
+        Some(release) => (release.season, release.episode?),
+        None => (
+            None,
+            EPISODE_NUMBER
+                .captures(name)?
+                .name("episode")?
+                .as_str()
+                .parse()
+                .ok()?,
+        ),
+    };
+    let release = ParsedRelease::new(
+        Kind::Episode,
+        title(captures.name("name")?.as_str(), &['.', '_']),
+        &directory[captures.get(0)?.end()..],
+    );
+    let tags = ParsedRelease::new(Kind::Episode, String::new(), name);
+
+    Some(ParsedRelease {
+        season: season.or(captures.name("season")?.as_str().parse().ok()),
+        episode: Some(episode),
+        quality: tags.quality.or(release.quality.clone()),
+        source: tags.source.or(release.source.clone()),
+        codec: tags.codec.or(release.codec.clone()),
+        ..release
+    })
+}
+
+fn parse_episode(name: &str) -> Option<ParsedRelease> {
+    let captures = EPISODE.captures(name)?;
+    let rest = &name[captures.get(0)?.end()..];
+
+    Some(ParsedRelease {
+        season: Some(captures.name("season")?.as_str().parse().ok()?),
+        episode: Some(captures.name("episode")?.as_str().parse().ok()?),
+        ..ParsedRelease::new(
+            Kind::Episode,
+            title(captures.name("name")?.as_str(), &['.']),
+            rest,
+        )
+    })
+}
+
+fn parse_movie(name: &str) -> Option<ParsedRelease> {
+    let captures = MOVIE.captures(name)?;
+    let rest = &name[captures.get(0)?.end()..];
+
+    Some(ParsedRelease {
+        year: Some(captures.name("year")?.as_str().parse().ok()?),
+        ..ParsedRelease::new(
+            Kind::Movie,
+            title(captures.name("name")?.as_str(), &['.']),
+            rest,
+        )
+    })
+}
+
+fn parse_anime(name: &str) -> Option<ParsedRelease> {
+    let captures = ANIME.captures(name)?;
+    let rest = &name[captures.get(0)?.end()..];
+    let release = ParsedRelease::new(
+        Kind::Episode,
+        title(captures.name("name")?.as_str(), &['.', '_']),
+        rest,
+    );
+
+    Some(ParsedRelease {
+        season: Some(1),
+        episode: Some(captures.name("episode")?.as_str().parse().ok()?),
+        // Fansub groups lead the name in brackets instead of trailing it
+        group: captures
+            .name("group")
+            .map(|group| group.as_str().to_string())
+            .or(release.group.clone()),
+        ..release
+    })
+}
+
+fn parse_album(name: &str) -> Option<ParsedRelease> {
+    let captures = ALBUM.captures(name)?;
+    let rest = &name[captures.get(0)?.end()..];
+    let release = ParsedRelease::new(
+        Kind::Album,
+        // Album titles are kept as written, they are often styled
+        captures.name("album")?.as_str().trim().to_string(),
+        rest,
+    );
+
+    Some(ParsedRelease {
+        artist: Some(captures.name("artist")?.as_str().trim().to_string()),
+        year: Some(captures.name("year")?.as_str().parse().ok()?),
+        codec: captures
+            .name("format")
+            .map(|format| format.as_str().to_string())
+            .or(release.codec.clone()),
+        ..release
+    })
+}
+
+impl ParsedRelease {
+    /// How much the name said about the release
+    fn specificity(&self) -> usize {
+        [
+            self.artist.is_some(),
+            self.year.is_some(),
+            self.season.is_some(),
+            self.episode.is_some(),
+            self.quality.is_some(),
+            self.source.is_some(),
+            self.codec.is_some(),
+            self.group.is_some(),
+        ]
+        .into_iter()
+        .filter(|known| *known)
+        .count()
+    }
+
+    /// A release with the tags found in the rest of the name after the title and numbering
+    fn new(kind: Kind, title: String, rest: &str) -> ParsedRelease {
+        let tag = |regex: &Regex| regex.find(rest).map(|tag| tag.as_str().to_string());
+
+        ParsedRelease {
+            kind,
+            title,
+            artist: None,
+            year: None,
+            season: None,
+            episode: None,
+            quality: tag(&QUALITY),
+            source: tag(&SOURCE),
+            codec: tag(&CODEC),
+            group: GROUP
+                .captures(rest)
+                .and_then(|captures| captures.name("group"))
+                // The tail of WEB-DL isn't a group
+                .filter(|group| !group.as_str().eq_ignore_ascii_case("dl"))
+                .map(|group| group.as_str().to_string()),
+        }
+    }
+}
+
+/// The resolution anywhere in the name, e.g. `1080p`, for names that don't parse
+pub fn quality(name: &str) -> Option<String> {
+    QUALITY
+        .find(name)
+        .map(|quality| quality.as_str().to_string())
+}
+
+/// Cases a title parsed with the English rules by the rules of the locale instead. Words with
+/// capitals past their first letter, like `NRK` or `McQueen`, keep their case
+pub fn recase(title: &str, locale: Locale) -> String {
+    if locale == Locale::English {
+        return title.to_string();
+    }
+
+    title
+        .split(' ')
+        .enumerate()
+        .map(|(position, word)| {
+            let lowercase = word.to_lowercase();
+            match locale {
+                _ if word.chars().skip(1).any(char::is_uppercase) => word.to_string(),
+                _ if position == 0 => capitalize(word),
+                Locale::Off => capitalize(word),
+                Locale::German if GERMAN_PARTICLES.contains(&lowercase.as_str()) => lowercase,
+                Locale::German => capitalize(word),
+                _ => lowercase,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The title as written in one of the names it was parsed from, with their separators as
+/// spaces, or `None` if none of them has it
+pub fn original_case(title: &str, names: &[&str]) -> Option<String> {
+    let title = title.chars().collect::<Vec<_>>();
+    if title.is_empty() {
+        return None;
+    }
+
+    names.iter().find_map(|name| {
+        let name = name
+            .chars()
+            .map(|c| if matches!(c, '.' | '_') { ' ' } else { c })
+            .collect::<Vec<_>>();
+        name.windows(title.len())
+            .find(|window| {
+                window
+                    .iter()
+                    .zip(&title)
+                    .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+            })
+            .map(|window| window.iter().collect())
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut characters = word.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+fn title(name: &str, separators: &[char]) -> String {
+    titlecase(name.replace(separators, " ").trim())
+}
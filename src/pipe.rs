@@ -0,0 +1,119 @@
+use crate::hooks;
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{ChildStdin, Stdio};
+use std::thread;
+
+/// Runs the command with what `write` writes on its stdin, instead of writing a destination
+/// file. `DESSERT_DESTINATION` holds where the file would have been written, for commands
+/// writing their output there, like `ffmpeg -i pipe:0 ... "$DESSERT_DESTINATION"`
+pub fn run(
+    command: &str,
+    destination: &Path,
+    write: impl FnOnce(&mut ChildStdin) -> io::Result<()>,
+) -> Result<()> {
+    let mut child = hooks::shell(command)
+        .env("DESSERT_DESTINATION", destination)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start pipe command: {command}"))?;
+    let (stdin, stdout, stderr) = (child.stdin.take(), child.stdout.take(), child.stderr.take());
+
+    // Read while the file is written, as a command that fills its output pipes stops reading
+    let (written, status) = thread::scope(|scope| {
+        scope.spawn(|| log_lines("pipe stdout", stdout));
+        scope.spawn(|| log_lines("pipe stderr", stderr));
+
+        let mut stdin = stdin.expect("stdin of the pipe command is piped");
+        let written = write(&mut stdin);
+        drop(stdin);
+        (written, child.wait())
+    });
+
+    let status = status.with_context(|| format!("Failed to wait for pipe command: {command}"))?;
+    // A command that fails stops reading, so the write fails too, but its status tells why
+    if !status.success() {
+        return Err(anyhow!("{command} exited with {status}"));
+    }
+    written.with_context(|| format!("Failed to pipe {:?} to {command}", destination))?;
+    info!("Piped {:?} to {command}", destination);
+
+    Ok(())
+}
+
+/// Like [`run`], for writers that only write to a path, like the unrar library. The path is
+/// a FIFO the command reads from
+#[cfg(unix)]
+pub fn run_through_path<T>(
+    command: &str,
+    destination: &Path,
+    write: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    use nix::fcntl::OFlag;
+    use nix::sys::stat::Mode;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::time::Duration;
+
+    let directory = tempfile::tempdir().context("Failed to create directory for FIFO")?;
+    let fifo = directory.path().join("pipe");
+    nix::unistd::mkfifo(&fifo, Mode::S_IRUSR | Mode::S_IWUSR).context("Failed to create FIFO")?;
+
+    thread::scope(|scope| {
+        let piped = scope.spawn(|| {
+            run(command, destination, |stdin| {
+                io::copy(&mut File::open(&fifo)?, stdin).map(|_| ())
+            })
+        });
+        let written = write(&fifo);
+
+        // Opening the FIFO blocks until both ends are open, so a writer that failed before
+        // opening it would leave the reader waiting
+        while !piped.is_finished() {
+            let _ = std::fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(OFlag::O_NONBLOCK.bits())
+                .open(&fifo);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        piped
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        written
+    })
+}
+
+/// Like [`run`], for writers that only write to a path, like the unrar library. Without FIFOs
+/// the file is written next to the destination first, and deleted once piped
+#[cfg(not(unix))]
+pub fn run_through_path<T>(
+    command: &str,
+    destination: &Path,
+    write: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    let staged = std::path::PathBuf::from(format!("{}.piping", destination.display()));
+
+    let piped = write(&staged).and_then(|written| {
+        run(command, destination, |stdin| {
+            io::copy(&mut File::open(&staged)?, stdin).map(|_| ())
+        })?;
+        Ok(written)
+    });
+    let _ = std::fs::remove_file(&staged);
+
+    piped
+}
+
+fn log_lines(prefix: &str, reader: Option<impl Read>) {
+    let Some(reader) = reader else {
+        return;
+    };
+    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+        info!("{prefix}: {line}");
+    }
+}
@@ -0,0 +1,1852 @@
+use crate::error::DessertError;
+use crate::events::{self, Event, Observer, Observers};
+use crate::exit::{self, ExitCode};
+use crate::release::{self, Release};
+use crate::summary::{self, Summary};
+use crate::{
+    aliases, backend, bazarr, case, checksum, cleanup, compare, config, context, copy, dirs, email,
+    extract, filter, history, hooks, library, lock, mapping, metafile, metrics, mqtt, nfo, output,
+    parser, privileges, profile, prompt, quality, quarantine, queue, remux, repair, rules,
+    shutdown, space, spool, subtitles, symlink, timeout,
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Args, FromArgMatches};
+use log::{error, info, warn};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A release to import, and the library directory it goes to
+#[derive(clap::Args, Debug, Clone)]
+pub struct Job {
+    /// Source directory holding the releases to import
+    #[clap(short, long)]
+    pub source_directory: PathBuf,
+
+    /// Destination directory the releases are imported to
+    #[clap(short, long)]
+    pub destination_directory: PathBuf,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+/// How jobs are run, the flags shared by every subcommand that runs them
+pub struct Options {
+    /// Number of jobs to run concurrently, jobs writing the same destination still run one at a
+    /// time. Sources holding several archives, like season packs, run a job per archive
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Email report settings, instead of the [email] section of the config file
+    #[clap(short, long)]
+    pub email_config_file: Option<PathBuf>,
+
+    /// MQTT broker settings, instead of the [mqtt] section of the config file
+    #[clap(long)]
+    pub mqtt_config_file: Option<PathBuf>,
+
+    /// Trigger a Bazarr subtitle search when no subtitles were imported, instead of the
+    /// [bazarr] section of the config file
+    #[clap(long)]
+    pub bazarr_config_file: Option<PathBuf>,
+
+    /// Show what would be extracted where, without changing any files or sending notifications
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Lock file preventing concurrent dessert processes from racing each other
+    #[clap(long, value_enum, default_value_t = lock::Scope::Destination)]
+    pub lock: lock::Scope,
+
+    /// What to do when another process holds the lock
+    #[clap(long, value_enum, default_value_t = lock::Contention::Wait)]
+    pub on_lock: lock::Contention,
+
+    /// Confirm, edit or skip the destination name of each job
+    #[clap(short, long)]
+    pub interactive: bool,
+
+    /// SQLite database every job is recorded in, defaults to one in the data directory
+    #[clap(long)]
+    pub history_database: Option<PathBuf>,
+
+    /// Skip sources with the content of one imported before by the history, even under
+    /// another name like a re-download or a cross-seed. The content is told by the hash of
+    /// the start of the archive's first volume or the file, and its size
+    #[clap(long)]
+    pub skip_known_content: bool,
+
+    /// Write a Kodi-compatible .nfo file next to the destination file
+    #[clap(long)]
+    pub write_nfo: bool,
+
+    /// Write the SHA-256 of each imported file to a .sha256 file next to it, to verify the
+    /// library with `sha256sum -c` later
+    #[clap(long)]
+    pub write_checksum: bool,
+
+    /// Command the extracted files are piped to on stdin instead of being written, like
+    /// `ffmpeg -i pipe:0 ... "$DESSERT_DESTINATION"`, with DESSERT_DESTINATION holding where
+    /// each would have been written. Files the command writes there are imported
+    #[clap(long)]
+    pub pipe_to: Option<String>,
+
+    /// Remux MKVs into the container with ffmpeg after extraction, copying the streams
+    /// without re-encoding, unless the profile says otherwise
+    #[clap(long, value_enum)]
+    pub remux: Option<remux::Container>,
+
+    /// Extract the text subtitle tracks of MKVs to .srt files named by their language with
+    /// ffmpeg, before they are remuxed
+    #[clap(long)]
+    pub extract_subtitles: bool,
+
+    /// The ffmpeg executable of --remux and --extract-subtitles, with ffprobe next to it
+    #[clap(long, default_value = "ffmpeg")]
+    pub ffmpeg: PathBuf,
+
+    /// The rar executable reconstructing the volumes of archives that fail to extract from the
+    /// .rev recovery volumes next to them
+    #[clap(long, default_value = "rar")]
+    pub rar: PathBuf,
+
+    /// Command to run before extraction, a non-zero exit aborts the job, may be repeated
+    #[clap(long)]
+    pub pre_hook: Vec<String>,
+
+    /// Command to run after the job, may be repeated
+    #[clap(long)]
+    pub post_hook: Vec<String>,
+
+    /// TOML file of remote to local path mappings applied to incoming source directories
+    #[clap(long)]
+    pub path_mapping_file: Option<PathBuf>,
+
+    /// User to switch to after startup when started as root
+    #[clap(long)]
+    pub run_as_user: Option<String>,
+
+    /// Profile from the config file to process the jobs with
+    #[clap(long)]
+    pub profile: Option<String>,
+
+    /// Download client category, routed by its [category] table of the config file or else
+    /// selecting the profile that lists it
+    #[clap(long)]
+    pub category: Option<String>,
+
+    /// Download client tags or labels, comma separated like qBittorrent's %G. Without
+    /// --category, the first that a [category] table or a profile lists routes the job like a
+    /// category, and without any the release name alone does
+    #[clap(long = "tag", value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// How the outcome is printed to stdout
+    #[clap(long, value_enum, default_value_t = output::Format::Text)]
+    pub output: output::Format,
+
+    /// Print only the destination path of each imported release, the same as
+    /// `--output destination`
+    #[clap(long, conflicts_with = "output")]
+    pub print_destination: bool,
+
+    /// Create the destination directory when it doesn't exist, instead of failing
+    #[clap(long)]
+    pub create_destination: bool,
+
+    /// Follow symlinks in the source and destination that point outside of them, which is
+    /// refused by default
+    #[clap(long)]
+    pub follow_symlinks: bool,
+
+    /// Permissions of the directories created in the destination, in octal like 775, on Unix
+    #[clap(long, value_parser = |mode: &str| u32::from_str_radix(mode, 8))]
+    pub directory_mode: Option<u32>,
+
+    /// Size in bytes of the buffer video files are copied through where they can't be hard
+    /// linked and the kernel can't copy them
+    #[clap(long, default_value_t = copy::DEFAULT_BUFFER_SIZE)]
+    pub copy_buffer_size: usize,
+
+    /// Free space the destination keeps, in bytes or with a unit like 50G. Imports that would
+    /// start below it are refused, or space is freed with --on-low-space free
+    #[clap(long, value_parser = space::parse_size)]
+    pub min_free_space: Option<u64>,
+
+    /// What happens when the destination has less free space than --min-free-space
+    #[clap(
+        long,
+        value_enum,
+        default_value = "refuse",
+        requires_if("free", "expendable_directory")
+    )]
+    pub on_low_space: space::LowSpace,
+
+    /// Directory whose oldest files and directories are deleted to free space with
+    /// --on-low-space free, e.g. a folder of watched recordings. Can't be or be inside the source
+    /// or destination directory, nor hold them
+    #[clap(long)]
+    pub expendable_directory: Option<PathBuf>,
+
+    /// Look for the release elsewhere in the destination directory, by the episode or movie
+    /// the files there are named as, and what to do when it is there under another name
+    #[clap(long, value_enum)]
+    pub on_duplicate: Option<library::Duplicate>,
+
+    /// Replace the videos an import would replace, at its destination or as duplicates with
+    /// --on-duplicate replace, only by a better quality by the [quality] ranking of the config
+    /// file, skipping the job otherwise
+    #[clap(long)]
+    pub quality_upgrades: bool,
+
+    /// How existing destination files are compared with the source, to skip the ones that
+    /// match and replace the others
+    #[clap(long, value_enum, default_value = "size")]
+    pub compare: compare::Compare,
+
+    /// What happens to the video files of an archive besides its largest, like featurettes
+    #[clap(long, value_enum, default_value = "skip")]
+    pub extras: extract::Extras,
+
+    /// Keep the paths the other files of an archive have in it, like artwork, discs and the
+    /// extras of --extras import, next to the main video file instead of naming them by its
+    /// file name
+    #[clap(long)]
+    pub preserve_structure: bool,
+
+    /// Size video files need to be imported, in bytes or with a unit like 100M, so samples,
+    /// trailers and other small videos of releases are never taken for the main video file
+    #[clap(long, value_parser = space::parse_size)]
+    pub min_video_size: Option<u64>,
+
+    /// Whether names in the destination differing only in case are the same file, like on SMB
+    /// shares and exFAT drives. Where they are, files are written in the case of the existing
+    /// ones instead of next to them
+    #[clap(long, value_enum, default_value = "auto")]
+    pub destination_case: case::Case,
+
+    /// Delete the source once the destination files are verified to match it
+    #[clap(long)]
+    pub delete_source: bool,
+
+    /// With --delete-source, keep sources modified within this long, e.g. "2d" to let them seed,
+    /// for a later run to delete
+    #[clap(long, value_parser = humantime::parse_duration)]
+    pub keep_source_for: Option<Duration>,
+
+    /// Move sources that fail to be found, parsed or extracted into this directory, next to a
+    /// report of what went wrong, instead of leaving them in the download directory
+    #[clap(long)]
+    pub quarantine_directory: Option<PathBuf>,
+
+    /// Only process releases whose names match, a glob like "*S02E*" or a regex like "/S02E0[1-4]/"
+    #[clap(long, value_parser = filter::Filter::parse)]
+    pub filter: Option<filter::Filter>,
+
+    /// How long to wait for the email to be sent before moving on, e.g. "30s". An email that
+    /// isn't sent by then stays in the spool and is sent with the next one
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    pub notification_timeout: Duration,
+
+    /// Longest a job may take, e.g. "2h", before it is aborted and fails. A job that doesn't
+    /// stop within 30 seconds of timing out, like one stuck on a wedged mount, makes dessert
+    /// exit
+    #[clap(long, value_parser = humantime::parse_duration)]
+    pub job_timeout: Option<Duration>,
+
+    /// Longest `dessert run` may take, e.g. "6h", before its jobs are aborted and fail. If
+    /// they don't stop within 30 seconds, the failure is notified and dessert exits
+    #[clap(long, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+}
+
+/// The defaults of the flags
+impl Default for Options {
+    fn default() -> Options {
+        let command = Options::augment_args(clap::Command::new("dessert"));
+        Options::from_arg_matches(&command.get_matches_from(["dessert"]))
+            .expect("Failed to build default options")
+    }
+}
+
+impl Options {
+    /// Whether the release passes `--filter`, logging the ones left out
+    pub fn selects(&self, release: &Path) -> bool {
+        let selected = self
+            .filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(release));
+        if !selected {
+            info!("Skipping release not matching the filter: {:?}", release);
+            return false;
+        }
+
+        #[cfg(feature = "plugins")]
+        if !crate::plugins::selects(release) {
+            info!("Skipping release left out by a plugin: {:?}", release);
+            return false;
+        }
+
+        true
+    }
+
+    /// The email client from `--email-config-file`, or else from the config file
+    pub fn email_client(&self) -> Option<Result<email::Client>> {
+        match &self.email_config_file {
+            Some(path) => Some(email::Client::init_from_file(path)),
+            None => config::current().email.clone().map(Ok),
+        }
+    }
+
+    /// The MQTT client from `--mqtt-config-file`, or else from the config file
+    fn mqtt_client(&self) -> Option<Result<mqtt::Client>> {
+        match &self.mqtt_config_file {
+            Some(path) => Some(mqtt::Client::init_from_file(path)),
+            None => config::current().mqtt.clone().map(mqtt::Client::init),
+        }
+    }
+
+    /// The Bazarr client from `--bazarr-config-file`, or else from the config file
+    pub fn bazarr_client(&self) -> Option<Result<bazarr::Client>> {
+        match &self.bazarr_config_file {
+            Some(path) => Some(
+                bazarr::Client::init_from_file(path).context("Failed to initialize Bazarr client"),
+            ),
+            None => config::current().bazarr.clone().map(Ok),
+        }
+    }
+
+    pub fn drop_privileges(&self) -> Result<()> {
+        match &self.run_as_user {
+            Some(user) => privileges::drop_to(user),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the path mappings to a source directory reported by a download client
+    pub fn map_source(&self, mut job: Job) -> Result<Job> {
+        if let Some(path) = &self.path_mapping_file {
+            job.source_directory =
+                mapping::Mappings::init_from_file(path)?.map(&job.source_directory);
+        }
+
+        Ok(job)
+    }
+}
+
+/// What a job imported into the library
+pub struct Import {
+    pub release: Release,
+    /// The destination file name without extension, as named by the naming templates
+    pub file_name: String,
+    /// The library directory the files went to, as routed by rules, categories and profiles
+    pub destination_directory: PathBuf,
+    /// Where the files were stored, by the backend
+    pub files: Vec<PathBuf>,
+    /// Where the `.nfo` and checksum files written for them were stored
+    pub sidecars: Vec<PathBuf>,
+    /// The size of the files in bytes
+    pub bytes: u64,
+    /// The cleaned up `.nfo` of the release, if it came with one
+    pub release_notes: Option<String>,
+    /// The URL of the poster of the release on TMDB, when the config file has a [tmdb] section
+    pub artwork: Option<String>,
+    /// The qualities of the videos it replaced and of the release, with --quality-upgrades
+    pub upgrade: Option<(String, String)>,
+    /// Whether the volumes of the archive were reconstructed from its recovery volumes
+    pub repaired: bool,
+    pub stats: summary::Stats,
+    /// Identifies the content of the source, recorded for --skip-known-content
+    pub content_hash: Option<String>,
+}
+
+/// The result of a job, failed with [`DessertError::Skipped`] when it was skipped
+pub type Outcome = Result<Import, DessertError>;
+
+impl Import {
+    /// The video file of the import, or else its first file
+    pub fn destination(&self) -> Option<&PathBuf> {
+        self.files
+            .iter()
+            .find(|file| extract::is_video(file))
+            .or(self.files.first())
+    }
+}
+
+/// Discovers, parses, extracts and notifies of releases with the options, for the subcommands
+/// and for embedding dessert in other programs
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    options: Options,
+    observers: Observers,
+}
+
+impl Pipeline {
+    pub fn new(options: Options) -> Pipeline {
+        Pipeline {
+            options,
+            observers: Observers::default(),
+        }
+    }
+
+    /// Sends the events of the jobs run from now on to the observer as well
+    pub fn observe(mut self, observer: impl Observer + 'static) -> Pipeline {
+        self.observers.add(Arc::new(observer));
+        self
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Runs a job, recording it in the history and publishing its progress. Sources holding
+    /// several archives are a job per archive, see [`Pipeline::jobs`]
+    pub fn run(&self, job: &Job) -> Outcome {
+        run_job(job, &self.options, &self.observers)
+    }
+
+    /// The jobs of the source, a job per archive of sources holding several like season packs,
+    /// or else the job itself
+    pub fn jobs(job: Job) -> Vec<Job> {
+        split(job)
+    }
+
+    /// Runs the jobs, as many at a time as the options allow, with the details of each for the
+    /// output. Sources holding several archives run as a job per archive
+    pub fn run_all(&self, jobs: Vec<Job>) -> (Vec<Outcome>, Vec<output::Details>) {
+        let options = &self.options;
+        let jobs = jobs.into_iter().flat_map(Pipeline::jobs).collect();
+        output::set(match options.print_destination {
+            true => output::Format::Destination,
+            false => options.output,
+        });
+        queue::run_all(options.jobs, jobs, |job| {
+            let started = Instant::now();
+            let import = run_job(&job, options, &self.observers);
+            (import, output::Details::new(&job, started.elapsed()))
+        })
+        .into_iter()
+        .unzip()
+    }
+
+    /// Emails the summary with the log, or prints the log if nothing else has shown it. Failing
+    /// to send the email is logged, not returned, as the jobs are done either way
+    pub fn notify(&self, summary: &Summary, log_file: &mut File) -> Result<(), DessertError> {
+        notify(&self.options, summary, log_file).map_err(DessertError::from)
+    }
+}
+
+/// For async programs, running the blocking extraction on tokio's blocking threads while
+/// network calls are awaited
+#[cfg(feature = "async")]
+impl Pipeline {
+    pub async fn run_async(&self, job: Job) -> Outcome {
+        let pipeline = self.clone();
+        tokio::task::spawn_blocking(move || pipeline.run(&job))
+            .await
+            .context("Failed to run job")?
+    }
+
+    /// Like [`Pipeline::notify`], with the log and job logs of [`read_log`]
+    pub async fn notify_async(
+        &self,
+        summary: &Summary,
+        log: &str,
+        job_logs: &[PathBuf],
+    ) -> Result<(), DessertError> {
+        #[cfg(feature = "plugins")]
+        crate::plugins::notify(summary);
+        match self.options.email_client() {
+            Some(email) if !self.options.dry_run => {
+                let message = email::Message::of_run(summary, log, job_logs);
+                let Some(send) = sender(email, message) else {
+                    return Ok(());
+                };
+                let timeout = self.options.notification_timeout;
+                match tokio::time::timeout(timeout, tokio::task::spawn_blocking(send)).await {
+                    Ok(Ok(result)) => report_sent(result),
+                    Ok(Err(e)) => error!("Failed to send email: {e}"),
+                    Err(_) => timed_out(timeout),
+                }
+                Ok(())
+            }
+            _ => {
+                print_log(log);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn run_job(job: &Job, options: &Options, observers: &Observers) -> Outcome {
+    let _job = context::enter_job(None);
+    let _observing = events::observe(observers.clone());
+    let id = context::current().job.unwrap_or_default();
+    events::emit(Event::JobStarted {
+        job: id,
+        source: job.source_directory.clone(),
+    });
+
+    let import = process(job, options).map_err(DessertError::from);
+    events::emit(Event::JobFinished {
+        job: id,
+        succeeded: import.is_ok(),
+    });
+    import
+}
+
+/// Runs the job with its MQTT events, history entry, subtitle search and post hooks
+fn process(job: &Job, options: &Options) -> Result<Import> {
+    start_job_log(job);
+
+    if options.dry_run {
+        let import = run(job, options, None);
+        if let Err(e) = &import {
+            error!("{e}");
+        }
+        return import;
+    }
+
+    let mqtt = options.mqtt_client().and_then(|client| match client {
+        Ok(client) => Some(client),
+        Err(e) => {
+            error!("Failed to initialize MQTT client: {e}");
+            metrics::notification_failed();
+            None
+        }
+    });
+
+    let payload = mqtt::Payload {
+        source_directory: Some(&job.source_directory),
+        ..Default::default()
+    };
+    if let Some(mqtt) = &mqtt {
+        mqtt.publish(mqtt::Event::Started, &payload);
+    }
+
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let import = match options.job_timeout {
+        Some(job_timeout) => {
+            let _deadline = timeout::JobDeadline::start(job_timeout);
+            let _watchdog = timeout::Watchdog::start(job_timeout + timeout::GRACE, {
+                let (job, options) = (job.clone(), options.clone());
+                move || stuck(&job, &options)
+            });
+            timed(run(job, options, mqtt.as_ref()))
+        }
+        None => timed(run(job, options, mqtt.as_ref())),
+    };
+    metrics::job_finished(import.is_ok());
+
+    if let Err(e) = &import {
+        error!("{e}");
+        context::failed(&e.to_string());
+    }
+
+    context::phase("history");
+    if let Err(e) =
+        history::database(options.history_database.as_deref()).and_then(|history_database| {
+            record_history(&history_database, job, started_at, started, &import)
+        })
+    {
+        error!("{e}");
+    }
+
+    if let Some(mqtt) = &mqtt {
+        let log_file = context::log_path();
+        let payload = mqtt::Payload {
+            log_file: log_file.as_deref(),
+            ..payload
+        };
+        match &import {
+            Ok(import) => mqtt.publish(
+                mqtt::Event::Imported,
+                &mqtt::Payload {
+                    title: Some(import.release.name()),
+                    file_name: Some(&import.file_name),
+                    repaired: import.repaired,
+                    ..payload
+                },
+            ),
+            Err(e) => mqtt.publish(
+                mqtt::Event::Failed,
+                &mqtt::Payload {
+                    error: Some(e.to_string()),
+                    ..payload
+                },
+            ),
+        }
+    }
+
+    if let (Err(e), Some(directory)) = (&import, &options.quarantine_directory) {
+        if quarantine::is_for(e) && job.source_directory.exists() {
+            context::phase("quarantine");
+            match quarantine::quarantine(
+                &job.source_directory,
+                find_source_file(&job.source_directory).ok().as_deref(),
+                directory,
+                e,
+                context::log_path().as_deref(),
+            ) {
+                Ok(quarantined) => info!(
+                    "Quarantined {:?} as {:?}",
+                    job.source_directory, quarantined
+                ),
+                Err(e) => error!("{e:#}"),
+            }
+        }
+    }
+
+    if let (Ok(import), Some(bazarr)) = (&import, options.bazarr_client()) {
+        context::phase("subtitles");
+        if let Err(e) = bazarr.and_then(|bazarr| search_subtitles(import, &bazarr)) {
+            error!("{e}");
+        }
+    }
+
+    if !options.post_hook.is_empty() {
+        context::phase("post_hook");
+        hooks::run_post_hooks(&options.post_hook, &post_hook_env(import.as_ref().ok()));
+    }
+
+    import
+}
+
+fn record_history(
+    history_database: &Path,
+    job: &Job,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    import: &Result<Import>,
+) -> Result<()> {
+    let files = import
+        .as_ref()
+        .map(|import| import.files.clone())
+        .unwrap_or_default();
+    // Undoing the import removes the sidecars along with the files
+    let sidecars = import
+        .as_ref()
+        .map(|import| import.sidecars.clone())
+        .unwrap_or_default();
+
+    history::History::open(history_database)?.record(&history::Entry {
+        id: 0,
+        started_at,
+        source_directory: job.source_directory.clone(),
+        destination_directory: import
+            .as_ref()
+            .map_or(&job.destination_directory, |import| {
+                &import.destination_directory
+            })
+            .clone(),
+        name: import.as_ref().ok().map(|import| import.file_name.clone()),
+        archive_size: archive_size(&job.source_directory),
+        unpacked_size: files
+            .iter()
+            .filter_map(|file| file.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum(),
+        files: files.into_iter().chain(sidecars).collect(),
+        duration: started.elapsed(),
+        outcome: match import {
+            Ok(_) => history::Outcome::Succeeded,
+            Err(_) => history::Outcome::Failed,
+        },
+        error: import.as_ref().err().map(|e| e.to_string()),
+        upgrade: import
+            .as_ref()
+            .ok()
+            .and_then(|import| import.upgrade.as_ref())
+            .map(|(from, to)| format!("{from} -> {to}")),
+        log_file: context::log_path(),
+        content_hash: import
+            .as_ref()
+            .ok()
+            .and_then(|import| import.content_hash.clone()),
+    })?;
+
+    Ok(())
+}
+
+/// The size of the source's archive: its rar volumes, its ISO image or video file, the parts of
+/// its split file or the files of its disc structure
+fn archive_size(source: &Path) -> u64 {
+    let Ok(source_file) = find_source_file(source) else {
+        return 0;
+    };
+    let files = match extract::Kind::of(&source_file) {
+        extract::Kind::Disc => return extract::disc_size(&source_file),
+        _ => cleanup::archive_files(&source_file).unwrap_or_default(),
+    };
+
+    files
+        .iter()
+        .filter_map(|file| file.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn search_subtitles(import: &Import, bazarr: &bazarr::Client) -> Result<()> {
+    if bazarr::has_subtitles(&import.files) {
+        return Ok(());
+    }
+
+    let kind = match import.release {
+        Release::Episode { .. } => bazarr::Kind::Series,
+        Release::Movie { .. } => bazarr::Kind::Movies,
+        Release::Album { .. } => return Ok(()),
+    };
+
+    bazarr.search_missing_subtitles(kind)?;
+    info!("Triggered Bazarr subtitle search");
+
+    Ok(())
+}
+
+/// Starts the job's own log file, if job logs are enabled
+fn start_job_log(job: &Job) {
+    let config = config::current();
+    if let (Some(directory), true) = (&config.logging.directory, config.logging.job_logs) {
+        let name = job
+            .source_directory
+            .file_name()
+            .unwrap_or(job.source_directory.as_os_str())
+            .to_string_lossy();
+        context::start_log(directory, &name);
+    }
+}
+
+fn notify(options: &Options, summary: &Summary, log_file: &mut File) -> Result<()> {
+    context::phase("notify");
+    #[cfg(feature = "plugins")]
+    crate::plugins::notify(summary);
+    let (log, job_logs) = read_log(log_file)?;
+
+    match options.email_client() {
+        Some(email) if !options.dry_run => {
+            let message = email::Message::of_run(summary, &log, &job_logs);
+            if let Some(send) = sender(email, message) {
+                let (sent, result) = mpsc::channel();
+                thread::spawn(move || sent.send(send()));
+                match result.recv_timeout(options.notification_timeout) {
+                    Ok(result) => report_sent(result),
+                    Err(_) => timed_out(options.notification_timeout),
+                }
+            }
+            Ok(())
+        }
+        _ => {
+            print_log(&log);
+            Ok(())
+        }
+    }
+}
+
+/// Spools the email and returns what sends the spool, or else the email if it couldn't be
+/// spooled. `None` if the client failed to initialize
+fn sender(
+    email: Result<email::Client>,
+    message: email::Message,
+) -> Option<impl FnOnce() -> Result<()> + Send + 'static> {
+    let spooled = spool::save(&message);
+    let email = match email.context("Failed to initialize email client") {
+        Ok(email) => email,
+        Err(e) => {
+            error!("{e:#}");
+            metrics::notification_failed();
+            if spooled.is_ok() {
+                info!("Kept the email in the spool");
+            }
+            return None;
+        }
+    };
+
+    Some(move || match spooled {
+        Ok(_) => spool::send(|message| email.send_message(message)),
+        Err(e) => {
+            warn!("Sending the email without spooling it: {e:#}");
+            email.send_message(&message).inspect_err(|_| {
+                metrics::notification_failed();
+            })
+        }
+    })
+}
+
+fn report_sent(result: Result<()>) {
+    if let Err(e) = result {
+        error!("{e:#}, keeping it in the spool to send with the next email");
+    }
+}
+
+fn timed_out(timeout: Duration) {
+    warn!(
+        "The email wasn't sent within {}, it is sent in the background and stays in the spool \
+         until it is",
+        humantime::format_duration(timeout)
+    );
+}
+
+/// The log written since the last read, with the log files of the jobs opened since, for
+/// notifications
+pub fn read_log(log_file: &mut File) -> Result<(String, Vec<PathBuf>)> {
+    let job_logs = context::take_logs();
+
+    let mut log = String::new();
+    log_file
+        .read_to_string(&mut log)
+        .context("Failed to read log file")?;
+
+    Ok((log, job_logs))
+}
+
+fn print_log(log: &str) {
+    // The terminal has already seen the log
+    if !output::console_logging() {
+        eprintln!("{log}")
+    }
+}
+
+fn post_hook_env(import: Option<&Import>) -> Vec<(&'static str, String)> {
+    match import {
+        Some(import) => vec![
+            ("DESSERT_STATUS", "success".to_string()),
+            ("DESSERT_TITLE", import.release.name().to_string()),
+            ("DESSERT_FILE_NAME", import.file_name.clone()),
+            (
+                "DESSERT_DEST_PATH",
+                import
+                    .files
+                    .first()
+                    .map(|file| file.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+        ],
+        None => vec![("DESSERT_STATUS", "failure".to_string())],
+    }
+}
+
+/// Fails the job with the timeout code if it failed for having timed out
+fn timed(import: Result<Import>) -> Result<Import> {
+    match (import, timeout::expired()) {
+        (Err(e), Some(expired)) => Err(e.context(format!(
+            "Timed out after {}",
+            humantime::format_duration(expired)
+        )))
+        .exit_code(exit::Code::Timeout),
+        (import, _) => import,
+    }
+}
+
+/// Notifies MQTT of the failure of a job that didn't stop after timing out, and exits
+fn stuck(job: &Job, options: &Options) {
+    if let Some(Ok(mqtt)) = options.mqtt_client() {
+        mqtt.publish(
+            mqtt::Event::Failed,
+            &mqtt::Payload {
+                source_directory: Some(&job.source_directory),
+                error: Some("Timed out".to_string()),
+                ..Default::default()
+            },
+        );
+    }
+    timeout::exit(&format!("The job of {:?}", job.source_directory));
+}
+
+fn run(job: &Job, options: &Options, mqtt: Option<&mqtt::Client>) -> Result<Import> {
+    if let Some(expired) = timeout::expired() {
+        return Err(anyhow!(
+            "Not started, the run timed out after {}",
+            humantime::format_duration(expired)
+        ));
+    }
+    context::phase("discover");
+    verify_paths(job, options).exit_code(exit::Code::Verification)?;
+    info!("Verified paths");
+
+    let source_file = find_source_file(&job.source_directory).exit_code(exit::Code::NoArchive)?;
+    if !options.follow_symlinks && job.source_directory.is_dir() {
+        symlink::check_inside(&job.source_directory, &source_file)
+            .exit_code(exit::Code::Verification)?;
+    }
+    let kind = extract::Kind::of(&source_file);
+    match kind {
+        extract::Kind::Rar => info!("Found rar file: {:?}", source_file),
+        extract::Kind::Iso => info!("Found ISO image: {:?}", source_file),
+        extract::Kind::Split => info!("Found split file: {:?}", source_file),
+        extract::Kind::Video => info!("Found video file: {:?}", source_file),
+        extract::Kind::Disc => info!("Found disc structure: {:?}", source_file),
+    }
+    if let Some(min_video_size) = options.min_video_size {
+        check_video_size(&source_file, kind, min_video_size).exit_code(exit::Code::NoArchive)?;
+    }
+    if let Some(release) = source_file.file_stem() {
+        if let Err(e) = context::name_log(&release.to_string_lossy()) {
+            error!("{e:#}");
+        }
+    }
+
+    let content_hash = match kind {
+        // A disc structure is many files, without a start to hash
+        extract::Kind::Disc => None,
+        _ => match checksum::content_hash(&source_file) {
+            Ok(content_hash) => Some(content_hash),
+            Err(e) => {
+                warn!("{e:#}");
+                None
+            }
+        },
+    };
+    if let Some(content_hash) = content_hash.as_ref().filter(|_| options.skip_known_content) {
+        let history =
+            history::History::open(&history::database(options.history_database.as_deref())?)?;
+        if let Some(entry) = history.imported_content(content_hash)? {
+            info!(
+                "Skipping, the same content was imported from {:?} as {:?}",
+                entry.source_directory,
+                entry.name.unwrap_or_default()
+            );
+            return Err(summary::Skipped.into());
+        }
+    }
+
+    context::phase("parse");
+    let config = config::current();
+    let routed = profile::routed_category(
+        options.category.as_deref(),
+        &options.tags,
+        &config.categories,
+        &config.profiles,
+    );
+    let category = routed.and_then(|category| config.categories.get_key_value(category));
+    if let Some((name, _)) = category {
+        info!("Routing category {:?}", name);
+    }
+    let category = category.map(|(_, category)| category);
+    let profile = profile::Profile::select(
+        &config.profiles,
+        options
+            .profile
+            .as_deref()
+            .or(category.and_then(|category| category.profile.as_deref())),
+        routed,
+    )?;
+    if let Some((name, _)) = profile {
+        info!("Using profile {:?}", name);
+    }
+    let profile = profile.map(|(_, profile)| profile);
+
+    let mode = profile.map(|profile| profile.parse).unwrap_or_default();
+    let locale = profile.map(|profile| profile.locale).unwrap_or_default();
+    let naming = profile
+        .and_then(|profile| profile.naming.as_ref())
+        .unwrap_or(&config.naming);
+    let release = get_release(&source_file, &job.source_directory, mode)
+        .exit_code(exit::Code::Parse)?
+        .recased(locale);
+    let names = source_file
+        .ancestors()
+        .filter_map(|path| path.file_name()?.to_str())
+        .collect::<Vec<_>>();
+    let release = naming.case_style.apply(release, &names);
+    let mut release = aliases::apply(release, &config.aliases, &config.replacements)?;
+    if matches!(release, Release::Album { .. }) && kind != extract::Kind::Rar {
+        return Err(anyhow!("Albums can only be imported from rar files"))
+            .exit_code(exit::Code::Parse);
+    }
+    let rule = rules::Rule::find(&config.rules, &release)?;
+    if let Some(rule) = rule {
+        info!("Applying rule {:?}", rule.pattern);
+        release = rule.apply(release)?;
+    }
+
+    let destination_directory = rule
+        .and_then(|rule| rule.destination_directory.clone())
+        .or(category.and_then(|category| category.destination_directory.clone()))
+        .or(profile.and_then(|profile| profile.destination_directory.clone()));
+    let job = &match destination_directory {
+        Some(destination_directory) => {
+            if !destination_exists(&destination_directory, options)
+                .exit_code(exit::Code::Verification)?
+            {
+                return Err(anyhow!(
+                    "Destination directory of rule, category or profile is not a directory"
+                ));
+            }
+            Job {
+                source_directory: job.source_directory.clone(),
+                destination_directory,
+            }
+        }
+        None => job.clone(),
+    };
+
+    let scope = if options.dry_run {
+        lock::Scope::None
+    } else {
+        options.lock
+    };
+    context::phase("lock");
+    let _lock = lock::acquire(scope, options.on_lock, &job.destination_directory)?;
+
+    context::phase("name");
+    let mut destination_file_name = match rule.and_then(|rule| rule.file_name(&release)) {
+        Some(file_name) => file_name?,
+        None => naming.file_name(&release)?,
+    };
+    destination_file_name = naming.normalization.apply(&destination_file_name);
+    let layout = extract::Layout::of(
+        &release,
+        naming,
+        extract::VideoLayout {
+            extras: options.extras,
+            min_size: options.min_video_size.unwrap_or(0),
+            preserve_structure: options.preserve_structure,
+        },
+    );
+    info!(
+        "Determined destination file name: {:?}",
+        destination_file_name
+    );
+
+    if options.interactive {
+        match prompt::confirm(&job.source_directory, &destination_file_name)? {
+            prompt::Answer::Accept => {}
+            prompt::Answer::Rename(file_name) => {
+                info!("Renamed destination file name to {:?}", file_name);
+                destination_file_name = file_name;
+            }
+            prompt::Answer::Skip => return Err(summary::Skipped.into()),
+        }
+    }
+
+    extract::check_file_name(Path::new(&destination_file_name))
+        .exit_code(exit::Code::Extraction)?;
+
+    let case_insensitive = options
+        .destination_case
+        .is_insensitive(&job.destination_directory);
+    if case_insensitive {
+        if let Some(existing) =
+            case::existing_name(&job.destination_directory, &destination_file_name)
+        {
+            if existing != destination_file_name {
+                info!("Using the case of existing destination file {:?}", existing);
+                destination_file_name = existing;
+            }
+        }
+    }
+    let _destination = queue::lock_destination(
+        &job.destination_directory.join(&destination_file_name),
+        case_insensitive,
+    );
+
+    let duplicates = match options.on_duplicate {
+        Some(duplicate) => {
+            let duplicates = library::duplicates(
+                &job.destination_directory,
+                naming,
+                &release,
+                &destination_file_name,
+                mode,
+            )?;
+            if !library::report(&duplicates, duplicate) {
+                return Err(summary::Skipped.into());
+            }
+            match duplicate {
+                library::Duplicate::Replace => duplicates,
+                _ => Vec::new(),
+            }
+        }
+        None => Vec::new(),
+    };
+
+    let (upgrade, replaced) = match (options.quality_upgrades, layout) {
+        (true, extract::Layout::Video(_)) => {
+            let replaced = library::named(&job.destination_directory, &destination_file_name);
+            match upgrade(options, job, &source_file, &replaced, &duplicates)? {
+                Some(upgrade) => (Some(upgrade), replaced),
+                None => (None, Vec::new()),
+            }
+        }
+        _ => (None, Vec::new()),
+    };
+
+    let remux = profile
+        .and_then(|profile| profile.remux)
+        .or(options.remux)
+        .filter(|_| options.pipe_to.is_none());
+
+    if options.dry_run {
+        for duplicate in &duplicates {
+            dry_run(format!("Would delete duplicate: {:?}", duplicate));
+        }
+        if options.extract_subtitles {
+            dry_run("Would extract the text subtitles of an MKV main video".to_string());
+        }
+        if let Some(container) = remux {
+            dry_run(format!(
+                "Would remux an MKV main video to {} after extracting it",
+                container.extension()
+            ));
+        }
+        return plan(
+            job,
+            options,
+            &source_file,
+            release,
+            destination_file_name,
+            layout,
+            upgrade,
+        )
+        .map(|import| Import {
+            content_hash,
+            ..import
+        });
+    }
+
+    if !options.pre_hook.is_empty() {
+        context::phase("pre_hook");
+        hooks::run_pre_hooks(
+            &options.pre_hook,
+            &[
+                (
+                    "DESSERT_SOURCE_PATH",
+                    job.source_directory.to_string_lossy().to_string(),
+                ),
+                (
+                    "DESSERT_ARCHIVE_PATH",
+                    source_file.to_string_lossy().to_string(),
+                ),
+                ("DESSERT_TITLE", release.name().to_string()),
+                ("DESSERT_FILE_NAME", destination_file_name.clone()),
+            ],
+        )?;
+    }
+
+    if let Some(min_free_space) = options.min_free_space {
+        context::phase("space");
+        space::ensure(
+            &job.destination_directory,
+            &job.source_directory,
+            min_free_space,
+            options.on_low_space,
+            options.expendable_directory.as_deref(),
+        )
+        .exit_code(exit::Code::Verification)?;
+    }
+
+    // Naming templates may put releases in subdirectories, like one per show and season
+    if let Some(directory) = job
+        .destination_directory
+        .join(&destination_file_name)
+        .parent()
+    {
+        if !directory.is_dir() {
+            dirs::create(directory, options.directory_mode).exit_code(exit::Code::Extraction)?;
+        }
+    }
+    if !options.follow_symlinks {
+        symlink::check_inside(
+            &job.destination_directory,
+            &job.destination_directory.join(&destination_file_name),
+        )
+        .exit_code(exit::Code::Verification)?;
+    }
+
+    context::phase("extract");
+    // Measured before deleting the source empties it
+    let source_size = archive_size(&job.source_directory);
+    let extraction_started = Instant::now();
+    let mut repaired = false;
+    let mut files = match kind {
+        extract::Kind::Rar => extract_rar(
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            layout,
+            options,
+        )
+        .map(|(files, was_repaired)| {
+            repaired = was_repaired;
+            files
+        })
+        .inspect(|_| info!("Extracted rar file")),
+        extract::Kind::Iso => extract::extract_iso_file(
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            options.compare,
+            layout.min_video_size(),
+            options.pipe_to.as_deref(),
+        ),
+        extract::Kind::Split => extract::join_split_file(
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            options.compare,
+            options.copy_buffer_size,
+            options.pipe_to.as_deref(),
+        ),
+        extract::Kind::Video => extract::link_video_file(
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            options.compare,
+            options.copy_buffer_size,
+            options.pipe_to.as_deref(),
+        ),
+        extract::Kind::Disc => extract::copy_disc(
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            options.compare,
+            layout.min_video_size(),
+            options.copy_buffer_size,
+            options.pipe_to.as_deref(),
+        ),
+    }
+    .exit_code(exit::Code::Extraction)?;
+    let extraction = extraction_started.elapsed();
+    if let Some(video) = files.first().filter(|_| options.extract_subtitles).cloned() {
+        context::phase("subtitles");
+        let mut subtitles =
+            subtitles::extract(&video, &options.ffmpeg).exit_code(exit::Code::Extraction)?;
+        files.append(&mut subtitles);
+    }
+    let files = match remux {
+        Some(container) => {
+            context::phase("remux");
+            remux::remux(files, container, &options.ffmpeg).exit_code(exit::Code::Extraction)?
+        }
+        None => files,
+    };
+    let bytes = files
+        .iter()
+        .filter_map(|file| file.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    metrics::extracted(bytes, extraction_started.elapsed());
+
+    context::phase("duplicates");
+    library::delete(&duplicates)?;
+    // Videos of the same name with another extension than the upgrade's, the others were
+    // replaced by it
+    for video in replaced.iter().filter(|video| !files.contains(video)) {
+        std::fs::remove_file(video).with_context(|| format!("Failed to delete {:?}", video))?;
+        info!("Deleted replaced video: {:?}", video);
+        let sidecar = checksum::path(video);
+        if sidecar.exists() {
+            std::fs::remove_file(&sidecar)
+                .with_context(|| format!("Failed to delete {:?}", sidecar))?;
+        }
+    }
+
+    let release_notes = release_directory(&job.source_directory, &source_file)
+        .and_then(|directory| nfo::release_notes(&directory));
+    let artwork = config::current()
+        .tmdb
+        .as_ref()
+        .and_then(|tmdb| match tmdb.artwork(&release) {
+            Ok(artwork) => artwork,
+            Err(e) => {
+                warn!("Failed to look up artwork: {e:#}");
+                None
+            }
+        });
+
+    if let Some(mqtt) = mqtt {
+        mqtt.publish(
+            mqtt::Event::Extracted,
+            &mqtt::Payload {
+                source_directory: Some(&job.source_directory),
+                title: Some(release.name()),
+                file_name: Some(&destination_file_name),
+                ..Default::default()
+            },
+        );
+    }
+
+    let nfo_file = if options.write_nfo {
+        context::phase("nfo");
+        let nfo_file = nfo::write(&release, &job.destination_directory, &destination_file_name)?;
+        info!("Wrote nfo file: {:?}", nfo_file);
+        Some(nfo_file)
+    } else {
+        None
+    };
+
+    let mut checksum_files = Vec::new();
+    if options.write_checksum {
+        context::phase("checksum");
+        for file in &files {
+            let checksum_file = checksum::write(file)?;
+            info!("Wrote checksum file: {:?}", checksum_file);
+            checksum_files.push(checksum_file);
+        }
+    }
+
+    context::phase("store");
+    let backend = backend::current()?;
+    let staged = files
+        .iter()
+        .chain(&nfo_file)
+        .chain(&checksum_files)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut stored = backend.store(&job.destination_directory, &staged)?;
+    let sidecars = stored.split_off(files.len());
+
+    let mut verification = None;
+    if options.delete_source {
+        context::phase("cleanup");
+        match cleanup::delete_source(
+            &job.source_directory,
+            &source_file,
+            &job.destination_directory,
+            &destination_file_name,
+            options.compare,
+            layout,
+            options.keep_source_for,
+        ) {
+            Ok(verified_in) => verification = Some(verified_in),
+            Err(e) => error!("Failed to delete source: {e:#}"),
+        }
+    }
+    backend.release(&job.destination_directory, &staged)?;
+
+    let stats = summary::Stats {
+        archive_size: source_size,
+        unpacked_size: bytes,
+        extraction,
+        verification,
+    };
+    info!("Import statistics: {stats}");
+
+    Ok(Import {
+        release,
+        file_name: destination_file_name,
+        destination_directory: job.destination_directory.clone(),
+        files: stored,
+        sidecars,
+        bytes,
+        release_notes,
+        artwork,
+        upgrade,
+        repaired,
+        stats,
+        content_hash,
+    })
+}
+
+/// Extracts the rar file, and when that fails and recovery volumes are next to it, extracts it
+/// again once they have reconstructed its volumes. Tells whether the volumes were reconstructed
+fn extract_rar(
+    rar_file: &Path,
+    destination_directory: &Path,
+    file_name: &str,
+    layout: extract::Layout,
+    options: &Options,
+) -> Result<(Vec<PathBuf>, bool)> {
+    let extract = || {
+        extract::extract_rar_file(
+            rar_file,
+            destination_directory,
+            file_name,
+            options.compare,
+            layout,
+            options.pipe_to.as_deref(),
+        )
+    };
+    let e = match extract() {
+        Ok(files) => return Ok((files, false)),
+        Err(e) => e,
+    };
+
+    let recovery_volumes = repair::recovery_volumes(rar_file);
+    if recovery_volumes.is_empty() || shutdown::aborted() || timeout::expired().is_some() {
+        return Err(e);
+    }
+    warn!(
+        "Failed to extract, reconstructing the volumes from {} recovery volumes: {e:#}",
+        recovery_volumes.len()
+    );
+    if let Err(repair_error) = repair::reconstruct(rar_file, &options.rar) {
+        error!("{repair_error:#}");
+        return Err(e);
+    }
+
+    extract().map(|files| (files, true))
+}
+
+/// Decides whether the release replaces the videos by their quality, giving the qualities of
+/// an upgrade and failing with [`summary::Skipped`] when it would be none
+fn upgrade(
+    options: &Options,
+    job: &Job,
+    source_file: &Path,
+    replaced: &[PathBuf],
+    duplicates: &[PathBuf],
+) -> Result<Option<(String, String)>> {
+    let history = history::database(options.history_database.as_deref())
+        .and_then(|database| history::History::open(&database))
+        .inspect_err(|e| warn!("Failed to open history for qualities: {e:#}"))
+        .ok();
+    let existing = replaced
+        .iter()
+        .chain(duplicates)
+        .map(|video| quality::of_file(history.as_ref(), video))
+        .collect::<Vec<_>>();
+    let new = source_file
+        .file_stem()
+        .and_then(|name| parser::quality(&name.to_string_lossy()))
+        .or_else(|| parser::quality(&job.source_directory.file_name()?.to_string_lossy()));
+
+    match config::current().quality.decide(new.as_deref(), &existing) {
+        quality::Decision::Import => Ok(None),
+        quality::Decision::Upgrade { from, to } => {
+            info!("Upgrading from {from} to {to}");
+            Ok(Some((from, to)))
+        }
+        quality::Decision::Keep { existing, new } => Err(anyhow::Error::new(summary::Skipped)
+            .context(format!(
+                "Not replacing {existing} in the library with {}",
+                new.as_deref().unwrap_or("an unranked quality")
+            ))),
+    }
+}
+
+fn plan(
+    job: &Job,
+    options: &Options,
+    source_file: &Path,
+    release: Release,
+    file_name: String,
+    layout: extract::Layout,
+    upgrade: Option<(String, String)>,
+) -> Result<Import> {
+    for command in &options.pre_hook {
+        dry_run(format!("Would run pre-processing hook: {command}"));
+    }
+
+    if let Some(directory) = job.destination_directory.join(&file_name).parent() {
+        if !directory.is_dir() && directory != job.destination_directory {
+            dry_run(format!("Would create directory: {directory:?}"));
+        }
+    }
+
+    let kind = extract::Kind::of(source_file);
+    let plan = match kind {
+        extract::Kind::Rar => extract::plan_rar_file(
+            source_file,
+            &job.destination_directory,
+            &file_name,
+            options.compare,
+            layout,
+        )?,
+        extract::Kind::Iso => extract::plan_iso_file(
+            source_file,
+            &job.destination_directory,
+            &file_name,
+            options.compare,
+            layout.min_video_size(),
+        )?,
+        extract::Kind::Split => extract::plan_split_file(
+            source_file,
+            &job.destination_directory,
+            &file_name,
+            options.compare,
+        )?,
+        extract::Kind::Video => extract::plan_video_file(
+            source_file,
+            &job.destination_directory,
+            &file_name,
+            options.compare,
+        )?,
+        extract::Kind::Disc => extract::plan_disc(
+            source_file,
+            &job.destination_directory,
+            &file_name,
+            options.compare,
+            layout.min_video_size(),
+        )?,
+    };
+    for (destination, action) in &plan {
+        let action = match (kind, action) {
+            (extract::Kind::Video | extract::Kind::Disc, extract::Action::Extract) => {
+                "link or copy"
+            }
+            (extract::Kind::Split, extract::Action::Extract) => "join",
+            _ => action.describe(),
+        };
+        dry_run(format!(
+            "Would {} {:?} from {:?}",
+            action, destination, source_file
+        ));
+    }
+
+    if let Some(command) = &options.pipe_to {
+        dry_run(format!(
+            "Would pipe the files to {command} instead of writing them"
+        ));
+    }
+
+    if options.write_nfo {
+        dry_run(format!(
+            "Would write nfo file: {:?}",
+            nfo::path(&release, &job.destination_directory, &file_name)
+        ));
+    }
+
+    if options.write_checksum {
+        for (destination, _) in &plan {
+            dry_run(format!(
+                "Would write checksum file: {:?}",
+                checksum::path(destination)
+            ));
+        }
+    }
+
+    if options.delete_source {
+        dry_run(format!("Would delete source: {:?}", job.source_directory));
+    }
+
+    let config = config::current();
+    let remote = match (&config.sftp, &config.s3) {
+        (Some(sftp), _) => Some(format!("{}:{}", sftp.host, sftp.remote_root)),
+        (_, Some(s3)) => Some(format!("s3://{}/{}", s3.bucket, s3.prefix)),
+        (None, None) => None,
+    };
+    if let Some(remote) = remote {
+        dry_run(format!(
+            "Would upload the files to {remote} and delete them from {:?}",
+            job.destination_directory
+        ));
+    }
+
+    for command in &options.post_hook {
+        dry_run(format!("Would run post-processing hook: {command}"));
+    }
+
+    let files = plan
+        .into_iter()
+        .map(|(destination, _)| destination)
+        .collect::<Vec<_>>();
+    Ok(Import {
+        release,
+        file_name,
+        destination_directory: job.destination_directory.clone(),
+        bytes: files
+            .iter()
+            .filter_map(|file| file.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum(),
+        files,
+        sidecars: Vec::new(),
+        release_notes: release_directory(&job.source_directory, source_file)
+            .and_then(|directory| nfo::release_notes(&directory)),
+        artwork: None,
+        upgrade,
+        repaired: false,
+        stats: summary::Stats::default(),
+        content_hash: None,
+    })
+}
+
+fn dry_run(message: String) {
+    info!("{message}");
+    output::text(&format!("{message}\n"));
+}
+
+fn verify_paths(job: &Job, options: &Options) -> Result<()> {
+    if !job.source_directory.is_dir() && !job.source_directory.is_file() {
+        return Err(anyhow!("Source directory is not a directory or file"));
+    }
+
+    if !destination_exists(&job.destination_directory, options)? {
+        return Err(anyhow!("Destination directory is not a directory"));
+    }
+
+    Ok(())
+}
+
+/// Whether the destination directory is one, after creating it if `--create-destination` is set
+fn destination_exists(directory: &Path, options: &Options) -> Result<bool> {
+    if directory.is_dir() {
+        return Ok(true);
+    }
+    if !options.create_destination || directory.exists() {
+        return Ok(false);
+    }
+
+    if options.dry_run {
+        dry_run(format!("Would create destination directory: {directory:?}"));
+    } else {
+        dirs::create(directory, options.directory_mode)?;
+    }
+
+    Ok(true)
+}
+
+/// The archive, disc image, split file or video file to import, the source itself when it is a
+/// file. Directories are imported from their rar archive, or else see [`find_other_file`]
+fn find_source_file(source: &Path) -> Result<PathBuf> {
+    if !source.is_file() {
+        return find_rar_file(source).or_else(|e| find_other_file(source).ok_or(e));
+    }
+
+    let is_rar = source.extension().and_then(OsStr::to_str) == Some("rar");
+    if is_rar || extract::is_iso(source) || extract::is_video(source) {
+        Ok(source.to_path_buf())
+    } else if extract::is_split(source) {
+        // Any part given stands for the whole file
+        extract::split_parts(source)?
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("Failed to find the parts of split file"))
+    } else {
+        Err(anyhow!(
+            "Source file is not a rar file, an ISO image, a split file or a video file"
+        ))
+    }
+}
+
+/// Refuses video files and split files smaller than --min-video-size. Archives and disc images
+/// are checked for a large enough video file when they are read
+fn check_video_size(source_file: &Path, kind: extract::Kind, min_video_size: u64) -> Result<()> {
+    let parts = match kind {
+        extract::Kind::Video => vec![source_file.to_path_buf()],
+        extract::Kind::Split => extract::split_parts(source_file)?,
+        extract::Kind::Rar | extract::Kind::Iso | extract::Kind::Disc => return Ok(()),
+    };
+    let mut size = 0;
+    for part in parts {
+        size += std::fs::metadata(&part)
+            .with_context(|| format!("Failed to read metadata of {:?}", part))?
+            .len();
+    }
+
+    if size < min_video_size {
+        return Err(anyhow!(
+            "{:?} is smaller than the {} of --min-video-size",
+            source_file,
+            summary::format_bytes(min_video_size)
+        ));
+    }
+
+    Ok(())
+}
+
+/// The ISO image in the directory, or else the first part of a split video file, or else the
+/// directory itself if it has a disc structure, for sources without a rar archive
+pub fn find_other_file(source_directory: &Path) -> Option<PathBuf> {
+    let mut files = source_directory
+        .read_dir()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    files
+        .iter()
+        .find(|path| extract::is_iso(path))
+        .or_else(|| files.iter().find(|path| extract::is_split(path)))
+        .cloned()
+        .or_else(|| extract::is_disc(source_directory).then(|| source_directory.to_path_buf()))
+}
+
+/// Whether the directory holds other releases than the archive directly in it, like a downloads
+/// directory archives are dropped into. Directories named like releases or holding directories
+/// of their own are taken for other releases, so only the archive's files belong to its job
+pub fn is_shared(directory: &Path) -> bool {
+    let Ok(entries) = directory.read_dir() else {
+        return false;
+    };
+    let trees = extract::disc_trees(directory);
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !trees.contains(path))
+        .any(|path| {
+            let named = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| parser::parse(name, release::Mode::Auto).is_some());
+            let nested = path
+                .read_dir()
+                .is_ok_and(|entries| entries.flatten().any(|entry| entry.path().is_dir()));
+            named || nested
+        })
+}
+
+/// The directory of the release's own files, like its `.nfo` and `.nzb`: the source directory,
+/// or the directory of a source file holding no other archive or video. None when the source
+/// sits directly in a directory holding other releases, whose files could be any release's
+fn release_directory(source: &Path, source_file: &Path) -> Option<PathBuf> {
+    if source.is_dir() {
+        return (!is_shared(source)).then(|| source.to_path_buf());
+    }
+
+    let directory = source.parent().unwrap_or(Path::new("."));
+    let own = cleanup::targets(source, source_file).ok()?;
+    let others = directory.read_dir().ok()?.flatten().any(|entry| {
+        let path = entry.path();
+        !own.contains(&path)
+            && (extract::is_volume(&path)
+                || extract::is_iso(&path)
+                || extract::is_split(&path)
+                || extract::is_video(&path))
+    });
+
+    (!others && !is_shared(directory)).then(|| directory.to_path_buf())
+}
+
+/// The jobs of the archives in the source, one per rar archive or per subdirectory holding one,
+/// like the episodes of a season pack. Sources with a single archive are kept as they are
+fn split(job: Job) -> Vec<Job> {
+    let Ok(entries) = job.source_directory.read_dir() else {
+        return vec![job];
+    };
+
+    let (mut archives, mut directories) = (Vec::new(), Vec::new());
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            if find_rar_file(&path).is_ok() {
+                directories.push(path);
+            }
+        } else if is_first_volume(&path) {
+            archives.push(path);
+        }
+    }
+
+    let mut sources = match archives.len() {
+        0 if !directories.is_empty() => directories,
+        2.. => archives,
+        _ => return vec![job],
+    };
+    sources.sort();
+    info!(
+        "Found {} archives in {:?}",
+        sources.len(),
+        job.source_directory
+    );
+
+    sources
+        .into_iter()
+        .map(|source_directory| Job {
+            source_directory,
+            destination_directory: job.destination_directory.clone(),
+        })
+        .collect()
+}
+
+/// The archives directly in the directory, the first volume of each rar archive, ISO images and
+/// the first part of each split file, for directories archives are dropped into
+pub fn archives(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = directory.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut archives = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            is_first_volume(path)
+                || extract::is_iso(path)
+                || (extract::is_split(path)
+                    && extract::split_parts(path).is_ok_and(|parts| parts.first() == Some(path)))
+        })
+        .collect::<Vec<_>>();
+    archives.sort();
+
+    archives
+}
+
+/// Whether the file is a rar archive, or the first volume of one named `name.part1.rar`
+fn is_first_volume(path: &Path) -> bool {
+    if path.extension().and_then(OsStr::to_str) != Some("rar") {
+        return false;
+    }
+
+    let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    match stem.rsplit_once(".part") {
+        Some((_, part)) if !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) => {
+            part.parse() == Ok(1)
+        }
+        _ => true,
+    }
+}
+
+pub fn find_rar_file(source_directory: &Path) -> Result<PathBuf> {
+    source_directory
+        .read_dir()
+        .context("Failed to read source directory")?
+        .flatten()
+        .filter_map(|entry| {
+            entry
+                .path()
+                .extension()
+                .and_then(OsStr::to_str)
+                .and_then(|ext| {
+                    if ext == "rar" {
+                        Some(entry.path())
+                    } else {
+                        None
+                    }
+                })
+        })
+        .next()
+        .ok_or(anyhow!("Failed to find rar file"))
+}
+
+/// The release of the archive, by its name or else the names of the directories it is in up to
+/// the source directory
+fn get_release(rar_file: &Path, source_directory: &Path, mode: release::Mode) -> Result<Release> {
+    // Split files are named like `name.mkv.001`
+    let stem = match extract::is_split(rar_file) {
+        true => rar_file
+            .file_stem()
+            .map(Path::new)
+            .and_then(Path::file_stem),
+        // Disc directories are named like releases, dots and all
+        false if rar_file.is_dir() => rar_file.file_name(),
+        false => rar_file.file_stem(),
+    };
+    let file_name = stem
+        .and_then(OsStr::to_str)
+        .ok_or(anyhow!("Failed to get rar file stem"))?;
+
+    #[cfg(feature = "plugins")]
+    if let Some(release) = crate::plugins::parse(file_name) {
+        return Ok(release);
+    }
+
+    let directories = rar_file
+        .ancestors()
+        .skip(1)
+        .take_while(|directory| directory.starts_with(source_directory))
+        .filter_map(|directory| directory.file_name()?.to_str())
+        .collect::<Vec<_>>();
+
+    Release::parse_with_hints(file_name, &directories, mode).or_else(|e| {
+        // The .nzb and .torrent files of a shared directory could be any release's
+        let Some(directory) = release_directory(source_directory, rar_file) else {
+            return Err(e);
+        };
+        metafile::release_names(&directory)
+            .into_iter()
+            .find_map(|name| {
+                let release = Release::parse(&name, mode).ok()?;
+                info!("Parsed release name {:?} from .nzb or .torrent file", name);
+                Some(release)
+            })
+            .ok_or(e)
+    })
+}
@@ -0,0 +1,213 @@
+//! Custom parsers, filters and notifiers as WebAssembly modules, loaded from the plugin
+//! directory at startup.
+//!
+//! A plugin is a core module without imports, e.g. Rust built for `wasm32-unknown-unknown`. It
+//! exports its `memory` and `alloc(len: i32) -> i32`, which dessert calls to place the input
+//! in the plugin's memory, and any of:
+//!
+//! - `parse(ptr: i32, len: i32) -> i64` gets the release name and returns
+//!   `ptr << 32 | len` of a JSON release like `{"type": "movie", "name": "Name", "year": 2019}`,
+//!   or 0 to leave the name to the built in parser
+//! - `filter(ptr: i32, len: i32) -> i32` gets the file name of the release and returns 0 to
+//!   skip it
+//! - `notify(ptr: i32, len: i32)` gets the summary of a run as JSON
+//!
+//! Plugins are tried in the order of their file names
+
+use crate::config;
+use crate::release::Release;
+use crate::summary::Summary;
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use wasmtime::{Engine, Instance, Memory, Module, Store, Val};
+
+static PLUGINS: OnceLock<Vec<Plugin>> = OnceLock::new();
+
+struct Plugin {
+    name: String,
+    instance: Mutex<(Store<()>, Instance)>,
+}
+
+/// Loads the plugins of the plugin directory, the configured one or else
+/// `$XDG_CONFIG_HOME/dessert/plugins` if it exists. Only the first call loads them
+pub fn load(plugins: &config::Plugins) -> Result<()> {
+    let directory = match &plugins.directory {
+        Some(directory) => directory.clone(),
+        None => match crate::dirs::plugin_dir().filter(|directory| directory.is_dir()) {
+            Some(directory) => directory,
+            None => return Ok(()),
+        },
+    };
+
+    let mut files = fs::read_dir(&directory)
+        .with_context(|| format!("Failed to read plugin directory {:?}", directory))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wasm")
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let engine = Engine::default();
+    let loaded = files
+        .iter()
+        .map(|file| Plugin::load(&engine, file))
+        .collect::<Result<Vec<_>>>()?;
+    for plugin in &loaded {
+        info!("Loaded plugin {:?}", plugin.name);
+    }
+    let _ = PLUGINS.set(loaded);
+
+    Ok(())
+}
+
+fn plugins() -> &'static [Plugin] {
+    PLUGINS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// The release of the first plugin that recognises the name
+pub fn parse(name: &str) -> Option<Release> {
+    plugins().iter().find_map(|plugin| {
+        let parsed = plugin.call_for_output("parse", name.as_bytes())?;
+        let release = parsed.and_then(|json| {
+            serde_json::from_slice(&json).context("Failed to deserialize release")
+        });
+        match release {
+            Ok(release) => {
+                info!("Parsed by plugin {:?}", plugin.name);
+                Some(release)
+            }
+            Err(e) => {
+                error!("Plugin {:?} failed to parse: {e:#}", plugin.name);
+                None
+            }
+        }
+    })
+}
+
+/// Whether every plugin with a filter processes the release
+pub fn selects(release: &Path) -> bool {
+    let name = release.file_name().unwrap_or_default().to_string_lossy();
+    plugins()
+        .iter()
+        .all(|plugin| match plugin.call("filter", name.as_bytes()) {
+            None => true,
+            Some(Ok(selected)) => selected != 0,
+            Some(Err(e)) => {
+                error!("Plugin {:?} failed to filter: {e:#}", plugin.name);
+                true
+            }
+        })
+}
+
+/// Sends the summary to every plugin with a notifier
+pub fn notify(summary: &Summary) {
+    let json = serde_json::json!({
+        "found": summary.found,
+        "imported": summary.imported,
+        "skipped": summary.skipped,
+        "failed": summary.failed,
+        "bytes": summary.bytes,
+        "elapsed_secs": summary.elapsed.as_secs_f64(),
+    })
+    .to_string();
+
+    for plugin in plugins() {
+        if let Some(Err(e)) = plugin.call("notify", json.as_bytes()) {
+            error!("Plugin {:?} failed to notify: {e:#}", plugin.name);
+        }
+    }
+}
+
+impl Plugin {
+    fn load(engine: &Engine, file: &Path) -> Result<Plugin> {
+        let module = Module::from_file(engine, file)
+            .with_context(|| format!("Failed to compile plugin {:?}", file))?;
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, &module, &[])
+            .with_context(|| format!("Failed to instantiate plugin {:?}", file))?;
+
+        Ok(Plugin {
+            name: file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            instance: Mutex::new((store, instance)),
+        })
+    }
+
+    /// Calls the export with the input, or is `None` if the plugin doesn't export it. Exports
+    /// without a result give 0
+    fn call(&self, export: &str, input: &[u8]) -> Option<Result<i64>> {
+        let (store, instance) = &mut *self.instance.lock().unwrap();
+        call(store, instance, export, input)
+    }
+
+    /// Calls an export returning `ptr << 32 | len` of its output, or 0 for none
+    fn call_for_output(&self, export: &str, input: &[u8]) -> Option<Result<Vec<u8>>> {
+        let (store, instance) = &mut *self.instance.lock().unwrap();
+        match call(store, instance, export, input)? {
+            Ok(0) => None,
+            Ok(packed) => Some(read(store, instance, packed)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn call(
+    store: &mut Store<()>,
+    instance: &Instance,
+    export: &str,
+    input: &[u8],
+) -> Option<Result<i64>> {
+    let function = instance.get_func(&mut *store, export)?;
+
+    let result = write(store, instance, input).and_then(|(ptr, len)| {
+        let mut results = vec![Val::I64(0); function.ty(&*store).results().len()];
+        function
+            .call(&mut *store, &[ptr.into(), len.into()], &mut results)
+            .with_context(|| format!("Failed to call {export}"))?;
+        Ok(match results.first() {
+            Some(Val::I64(result)) => *result,
+            Some(Val::I32(result)) => *result as i64,
+            _ => 0,
+        })
+    });
+
+    Some(result)
+}
+
+fn memory(store: &mut Store<()>, instance: &Instance) -> Result<Memory> {
+    instance
+        .get_memory(&mut *store, "memory")
+        .ok_or(anyhow!("Plugin doesn't export its memory"))
+}
+
+fn write(store: &mut Store<()>, instance: &Instance, input: &[u8]) -> Result<(i32, i32)> {
+    let len = i32::try_from(input.len()).context("Input too large for plugin")?;
+    let ptr = instance
+        .get_typed_func::<i32, i32>(&mut *store, "alloc")
+        .context("Plugin doesn't export alloc")?
+        .call(&mut *store, len)
+        .context("Failed to allocate plugin memory")?;
+    memory(store, instance)?
+        .write(&mut *store, ptr as u32 as usize, input)
+        .context("Failed to write to plugin memory")?;
+
+    Ok((ptr, len))
+}
+
+fn read(store: &mut Store<()>, instance: &Instance, packed: i64) -> Result<Vec<u8>> {
+    let (ptr, len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+    let mut output = vec![0; len];
+    memory(store, instance)?
+        .read(&*store, ptr, &mut output)
+        .context("Failed to read from plugin memory")?;
+
+    Ok(output)
+}
@@ -0,0 +1,161 @@
+use crate::daemon::{self, Processed, Retention, ScanCache, SpaceAlert};
+use crate::digest::Digest;
+use crate::systemd::{self, Watchdog};
+use crate::web::Web;
+use anyhow::{anyhow, Result};
+use clap::CommandFactory;
+use dessert::config::Reloader;
+use dessert::{metrics, shutdown, status};
+use dessert::{Job, Options};
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(clap::Args, Debug)]
+pub struct PollArgs {
+    #[clap(flatten)]
+    job: Job,
+
+    /// Seconds between scans of the source directory
+    #[clap(long, default_value_t = 60)]
+    interval: u64,
+
+    /// Address to serve Prometheus metrics on at /metrics, e.g. 127.0.0.1:9898
+    #[clap(long)]
+    metrics_listen: Option<String>,
+
+    /// Address to serve a web dashboard on, e.g. 0.0.0.0:8081, with the queue, running jobs,
+    /// history with the logs of the jobs and a form to process a path right away
+    #[clap(long)]
+    web_listen: Option<String>,
+
+    /// Process releases again that an earlier run marked as done with a .dessert-done file
+    #[clap(long)]
+    force: bool,
+
+    /// Cron schedule for sending a digest of the jobs since the last one, e.g. "0 9 * * 1" for
+    /// every Monday morning
+    #[clap(long)]
+    digest_schedule: Option<String>,
+
+    /// Only send the digest, instead of a notification for every job
+    #[clap(long, requires = "digest_schedule")]
+    digest_only: bool,
+
+    /// Send an email when the destination has less free space than this, e.g. "100G"
+    #[clap(long, value_parser = dessert::space::parse_size)]
+    space_alert: Option<u64>,
+
+    /// How often to repeat the --space-alert email while the free space stays below it
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1d")]
+    space_alert_interval: Duration,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+pub fn poll(args: &PollArgs, log_file: File) -> Result<()> {
+    let root = &args.job.source_directory;
+    if let Some(listen) = &args.metrics_listen {
+        metrics::serve(listen)?;
+    }
+    args.options.drop_privileges()?;
+    if !root.is_dir() {
+        return Err(anyhow!("Source directory is not a directory"));
+    }
+    shutdown::install()?;
+    info!("Polling {:?} every {} seconds", root, args.interval);
+    systemd::ready();
+    let mut watchdog = Watchdog::new();
+    let mut reloader = Reloader::new(crate::Cli::command())?;
+
+    let mut sizes = HashMap::<PathBuf, u64>::new();
+    let mut processed = Processed::new(
+        root,
+        args.options.jobs,
+        log_file,
+        args.force,
+        !args.digest_only,
+    )?;
+    let mut digest = args
+        .digest_schedule
+        .as_deref()
+        .map(Digest::new)
+        .transpose()?;
+    let mut retention = Retention::new();
+    let mut space_alert = args
+        .space_alert
+        .map(|threshold| SpaceAlert::new(threshold, args.space_alert_interval));
+    let web = args
+        .web_listen
+        .as_deref()
+        .map(|listen| {
+            Web::serve(
+                listen,
+                dessert::history::database(args.options.history_database.as_deref())?,
+            )
+        })
+        .transpose()?;
+    let mut cache = ScanCache::new();
+
+    while !shutdown::requested() {
+        match daemon::releases(root) {
+            Ok(releases) => {
+                let mut current = HashMap::new();
+                cache.retain(&releases);
+
+                for release in releases {
+                    if processed.contains(&release) || cache.unchanged(root, &release) {
+                        continue;
+                    }
+
+                    let size = daemon::size_of(root, &release);
+                    let stable = sizes.get(&release) == Some(&size);
+                    current.insert(release.clone(), size);
+
+                    if !stable {
+                        continue;
+                    }
+                    match daemon::is_release(&release) {
+                        true => {
+                            info!("Processing {:?}", release);
+                            processed.process(
+                                &release,
+                                &args.job.destination_directory,
+                                &args.options,
+                            );
+                        }
+                        false => cache.ignore(root, &release),
+                    }
+                }
+
+                sizes = current;
+                status::scanned();
+            }
+            Err(e) => error!("{e}"),
+        }
+
+        let next_scan = Instant::now() + Duration::from_secs(args.interval);
+        while !shutdown::requested() && Instant::now() < next_scan {
+            watchdog.sleep(Duration::from_secs(1));
+            status::heartbeat();
+            reloader.check();
+            if let Some(digest) = &mut digest {
+                digest.check(&args.options);
+            }
+            retention.check(args.options.dry_run);
+            if let Some(space_alert) = &mut space_alert {
+                space_alert.check(&args.job.destination_directory, &args.options);
+            }
+            for release in web.iter().flat_map(Web::requested) {
+                processed.process(&release, &args.job.destination_directory, &args.options);
+            }
+        }
+    }
+
+    systemd::stopping();
+    processed.finish();
+    shutdown::finish()
+}
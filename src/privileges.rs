@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+
+/// Switches the process to the user and its groups, so files are created with its ownership
+#[cfg(unix)]
+pub fn drop_to(name: &str) -> Result<()> {
+    use anyhow::Context;
+    use log::info;
+    use nix::unistd::{self, User};
+
+    let user = User::from_name(name)
+        .context("Failed to look up user")?
+        .ok_or(anyhow!("User {name} does not exist"))?;
+
+    if unistd::geteuid() == user.uid {
+        return Ok(());
+    }
+
+    #[cfg(not(target_vendor = "apple"))]
+    unistd::initgroups(
+        &std::ffi::CString::new(name).context("Invalid user name")?,
+        user.gid,
+    )
+    .context("Failed to set supplementary groups")?;
+    unistd::setgid(user.gid).context("Failed to switch group")?;
+    unistd::setuid(user.uid).context("Failed to switch user")?;
+    info!("Dropped privileges to user {name}");
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn drop_to(_name: &str) -> Result<()> {
+    Err(anyhow!("Running as another user is only supported on Unix"))
+}
@@ -0,0 +1,88 @@
+use crate::naming::Naming;
+use crate::{release, remux};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Settings bundled under a name in the `[profile.<name>]` tables of the config file
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Naming templates used instead of the ones from `[naming]`
+    pub naming: Option<Naming>,
+
+    /// Destination used instead of the job's destination directory
+    pub destination_directory: Option<PathBuf>,
+
+    /// How release names are parsed
+    #[serde(default)]
+    pub parse: release::Mode,
+
+    /// The language of release names, casing their titles by its rules instead of English
+    /// ones, or `off` to only capitalize every word
+    #[serde(default)]
+    pub locale: release::Locale,
+
+    /// Download client categories that select this profile
+    #[serde(default)]
+    pub categories: Vec<String>,
+
+    /// Container MKVs are remuxed into, instead of the one of `--remux`
+    pub remux: Option<remux::Container>,
+}
+
+/// Routing of a download client category in the `[category.<name>]` tables of the config
+/// file, e.g. `movies-uhd` to its own destination with the profile of movies
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Category {
+    /// Profile used unless one is given with `--profile`
+    pub profile: Option<String>,
+
+    /// Destination used instead of the profile's and the job's destination directory
+    pub destination_directory: Option<PathBuf>,
+}
+
+impl Profile {
+    /// Picks the profile by name, or else the first one claiming the category
+    pub fn select<'a>(
+        profiles: &'a BTreeMap<String, Profile>,
+        name: Option<&str>,
+        category: Option<&str>,
+    ) -> Result<Option<(&'a str, &'a Profile)>> {
+        if let Some(name) = name {
+            return profiles
+                .get_key_value(name)
+                .map(|(name, profile)| Some((name.as_str(), profile)))
+                .ok_or(anyhow!("No profile named {name:?} in config file"));
+        }
+
+        Ok(category.and_then(|category| {
+            profiles
+                .iter()
+                .find(|(_, profile)| profile.categories.iter().any(|c| c == category))
+                .map(|(name, profile)| (name.as_str(), profile))
+        }))
+    }
+}
+
+/// The category routing a job, the given one or else the first of the tags that a
+/// `[category]` table or a profile lists. Download clients pass an empty category for none
+pub fn routed_category<'a>(
+    category: Option<&'a str>,
+    tags: &'a [String],
+    categories: &BTreeMap<String, Category>,
+    profiles: &BTreeMap<String, Profile>,
+) -> Option<&'a str> {
+    category
+        .filter(|category| !category.is_empty())
+        .or_else(|| {
+            tags.iter().map(|tag| tag.trim()).find(|tag| {
+                categories.contains_key(*tag)
+                    || profiles
+                        .values()
+                        .any(|profile| profile.categories.iter().any(|c| c == tag))
+            })
+        })
+}
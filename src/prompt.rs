@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+static TERMINAL: Mutex<()> = Mutex::new(());
+
+pub enum Answer {
+    Accept,
+    Rename(String),
+    Skip,
+}
+
+/// Asks whether to use the proposed destination name, one job at a time
+pub fn confirm(source: &Path, file_name: &str) -> Result<Answer> {
+    let _terminal = TERMINAL.lock().unwrap();
+    let stdin = io::stdin();
+    let mut stderr = io::stderr();
+
+    loop {
+        write!(
+            stderr,
+            "{}\n  -> {file_name}\nImport? [Y]es, [e]dit name, [s]kip: ",
+            source.display()
+        )
+        .context("Failed to write prompt")?;
+        stderr.flush().context("Failed to write prompt")?;
+
+        let answer = read_line(&stdin)?;
+        match answer.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(Answer::Accept),
+            "s" | "skip" | "n" | "no" => return Ok(Answer::Skip),
+            "e" | "edit" => {
+                write!(stderr, "New name [{file_name}]: ").context("Failed to write prompt")?;
+                stderr.flush().context("Failed to write prompt")?;
+
+                let name = read_line(&stdin)?;
+                let name = name.trim();
+                return Ok(if name.is_empty() {
+                    Answer::Accept
+                } else {
+                    Answer::Rename(name.to_string())
+                });
+            }
+            _ => writeln!(stderr, "Please answer y, e or s").context("Failed to write prompt")?,
+        }
+    }
+}
+
+/// Asks a free form question, an empty answer picks the default if there is one
+pub fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    let prompt = match default {
+        Some(default) if !default.is_empty() => format!("{question} [{default}]: "),
+        _ => format!("{question}: "),
+    };
+
+    loop {
+        match (answer(&prompt)?.as_str(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => eprintln!("Please give an answer"),
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Asks a yes or no question
+pub fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+    let prompt = match default {
+        true => format!("{question} [Y/n]: "),
+        false => format!("{question} [y/N]: "),
+    };
+
+    loop {
+        match answer(&prompt)?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => eprintln!("Please answer y or n"),
+        }
+    }
+}
+
+fn answer(prompt: &str) -> Result<String> {
+    let _terminal = TERMINAL.lock().unwrap();
+    let mut stderr = io::stderr();
+    write!(stderr, "{prompt}").context("Failed to write prompt")?;
+    stderr.flush().context("Failed to write prompt")?;
+
+    let mut answer = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+    if read == 0 {
+        return Err(anyhow!("No answer given to {:?}", prompt.trim_end()));
+    }
+
+    Ok(answer.trim().to_string())
+}
+
+fn read_line(stdin: &io::Stdin) -> Result<String> {
+    let mut line = String::new();
+    let read = stdin
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read answer")?;
+
+    if read == 0 {
+        Ok("s".to_string())
+    } else {
+        Ok(line)
+    }
+}
@@ -0,0 +1,87 @@
+use crate::history::History;
+use crate::parser;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Video qualities ranked for `--quality-upgrades`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quality {
+    /// Qualities from best to worst, as release names give them, e.g. `1080p`
+    pub ranking: Vec<String>,
+}
+
+impl Default for Quality {
+    fn default() -> Quality {
+        Quality {
+            ranking: ["2160p", "1080p", "720p", "576p", "480p"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Whether a release replaces the files of the library it would replace
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    /// Nothing to replace, or nothing whose quality is ranked
+    Import,
+    /// Better than everything it replaces
+    Upgrade { from: String, to: String },
+    /// Not better than something it replaces, or of a quality that isn't ranked
+    Keep {
+        existing: String,
+        new: Option<String>,
+    },
+}
+
+impl Quality {
+    /// The place of the quality in the ranking, lower is better. `4k` is `2160p`
+    fn rank(&self, quality: &str) -> Option<usize> {
+        let quality = match quality.to_lowercase().as_str() {
+            "4k" => "2160p".to_string(),
+            quality => quality.to_string(),
+        };
+        self.ranking
+            .iter()
+            .position(|ranked| ranked.to_lowercase() == quality)
+    }
+
+    /// Decides on the release of the quality given the qualities of the files it replaces
+    pub fn decide(&self, new: Option<&str>, existing: &[Option<String>]) -> Decision {
+        let best = existing
+            .iter()
+            .flatten()
+            .filter_map(|quality| self.rank(quality).map(|rank| (rank, quality)))
+            .min();
+        let Some((best_rank, best)) = best else {
+            return Decision::Import;
+        };
+
+        match new.and_then(|new| self.rank(new).map(|rank| (rank, new))) {
+            Some((rank, new)) if rank < best_rank => Decision::Upgrade {
+                from: best.clone(),
+                to: new.to_string(),
+            },
+            new => Decision::Keep {
+                existing: best.clone(),
+                new: new.map(|(_, new)| new.to_string()),
+            },
+        }
+    }
+}
+
+/// The quality of a file of the library, by the name of the release it was imported from
+/// when the history has it, or else by its own name
+pub fn of_file(history: Option<&History>, file: &Path) -> Option<String> {
+    let source = history
+        .and_then(|history| history.imported(file).ok().flatten())
+        .and_then(|entry| {
+            entry
+                .source_directory
+                .file_name()
+                .and_then(|name| parser::quality(&name.to_string_lossy()))
+        });
+
+    source.or_else(|| parser::quality(&file.file_name()?.to_string_lossy()))
+}
@@ -0,0 +1,103 @@
+use crate::exit::{self, Code};
+use crate::{cleanup, pipeline};
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether the failure is the source's own, like an archive that doesn't extract or a name that
+/// doesn't parse, rather than one a later run could get past like a full or locked destination
+pub fn is_for(error: &anyhow::Error) -> bool {
+    matches!(
+        exit::failure_code(error),
+        Some(Code::NoArchive | Code::Parse | Code::Extraction)
+    )
+}
+
+/// Moves the source of a failed job into the quarantine directory, next to a
+/// `<name>.failure.txt` report of what went wrong, and returns where it was moved. Of a source
+/// holding other releases, or a file that is one volume or part of its archive, only the files
+/// of the archive are moved, into a directory named after it
+pub fn quarantine(
+    source: &Path,
+    source_file: Option<&Path>,
+    directory: &Path,
+    error: &anyhow::Error,
+    log_file: Option<&Path>,
+) -> Result<PathBuf> {
+    let files = match source_file {
+        Some(source_file) => cleanup::targets(source, source_file)?,
+        None if source.is_dir() && pipeline::is_shared(source) => Vec::new(),
+        None => vec![source.to_path_buf()],
+    };
+    let name = match files.as_slice() {
+        [] => {
+            return Err(anyhow!(
+                "Not quarantining {:?}, it holds other releases and no archive of its own",
+                source
+            ))
+        }
+        [file] => file
+            .file_name()
+            .ok_or(anyhow!("{:?} has no name to quarantine it by", file))?
+            .to_string_lossy()
+            .into_owned(),
+        _ => cleanup::archive_name(source_file.unwrap_or(source))?.to_string(),
+    };
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create quarantine directory {:?}", directory))?;
+
+    // A release that failed before keeps its report, the new one is numbered
+    let quarantined = (1..)
+        .map(|number| match number {
+            1 => name.clone(),
+            number => format!("{name} ({number})"),
+        })
+        .map(|name| directory.join(name))
+        .find(|quarantined| !quarantined.exists() && !report_path(quarantined).exists())
+        .expect("some numbered name is free");
+
+    match files.as_slice() {
+        [file] => rename(file, &quarantined)?,
+        files => {
+            fs::create_dir(&quarantined).with_context(|| {
+                format!("Failed to create quarantine directory {:?}", quarantined)
+            })?;
+            for file in files {
+                rename(
+                    file,
+                    &quarantined.join(file.file_name().unwrap_or_default()),
+                )?;
+            }
+        }
+    }
+
+    let mut report = format!(
+        "Source: {}\nFailed: {}\nError: {error:#}\n",
+        source.display(),
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+    );
+    if let Some(log_file) = log_file {
+        report.push_str(&format!("Log: {}\n", log_file.display()));
+    }
+    let report_file = report_path(&quarantined);
+    fs::write(&report_file, report)
+        .with_context(|| format!("Failed to write quarantine report {:?}", report_file))?;
+
+    Ok(quarantined)
+}
+
+/// Copying a release across filesystems could take as long as the import, so it isn't
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to).with_context(|| {
+        format!(
+            "Failed to move {:?} to {:?}, the quarantine directory must be on the same \
+             filesystem as the source",
+            from, to
+        )
+    })
+}
+
+fn report_path(quarantined: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.failure.txt", quarantined.display()))
+}
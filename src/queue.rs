@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, LazyLock, Mutex};
+use std::thread;
+
+static DESTINATIONS: LazyLock<(Mutex<HashSet<PathBuf>>, Condvar)> = LazyLock::new(Default::default);
+
+/// Runs tasks on their own threads, with at most `limit` of them running at once
+pub struct Pool {
+    limit: usize,
+    running: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl Pool {
+    pub fn new(limit: usize) -> Pool {
+        Pool {
+            limit: limit.max(1),
+            running: Arc::default(),
+        }
+    }
+
+    /// Starts the task once a slot is free, blocking the caller until then
+    pub fn spawn(&self, task: impl FnOnce() + Send + 'static) {
+        let (count, changed) = &*self.running;
+        let mut running = changed
+            .wait_while(count.lock().unwrap(), |running| *running >= self.limit)
+            .unwrap();
+        *running += 1;
+        drop(running);
+
+        let slots = self.running.clone();
+        thread::spawn(move || {
+            task();
+
+            let (count, changed) = &*slots;
+            *count.lock().unwrap() -= 1;
+            changed.notify_all();
+        });
+    }
+
+    /// Blocks until every spawned task has finished
+    pub fn wait(&self) {
+        let (count, changed) = &*self.running;
+        let _running = changed
+            .wait_while(count.lock().unwrap(), |running| *running > 0)
+            .unwrap();
+    }
+}
+
+/// Runs `f` on every item with at most `limit` items in flight, keeping the results in order
+pub fn run_all<T: Send, R: Send>(limit: usize, items: Vec<T>, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let count = items.len();
+    let items = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, count.max(1)) {
+            scope.spawn(|| loop {
+                let Some((index, item)) = items.lock().unwrap().next() else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Holds exclusive access to a destination path until dropped
+pub struct DestinationGuard(PathBuf);
+
+/// Waits until no other job in this process is writing to the destination path, or to one
+/// differing only in case on case-insensitive destinations
+pub fn lock_destination(path: &Path, case_insensitive: bool) -> DestinationGuard {
+    let path = match case_insensitive {
+        true => PathBuf::from(path.to_string_lossy().to_lowercase()),
+        false => path.to_path_buf(),
+    };
+    let (paths, released) = &*DESTINATIONS;
+    let mut paths = released
+        .wait_while(paths.lock().unwrap(), |paths| paths.contains(&path))
+        .unwrap();
+    paths.insert(path.clone());
+
+    DestinationGuard(path)
+}
+
+impl Drop for DestinationGuard {
+    fn drop(&mut self) {
+        let (paths, released) = &*DESTINATIONS;
+        paths.lock().unwrap().remove(&self.0);
+        released.notify_all();
+    }
+}
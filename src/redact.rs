@@ -0,0 +1,85 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::{LazyLock, RwLock};
+
+const MASK: &str = "[REDACTED]";
+
+/// Secret values from the config, masked wherever they appear
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Things that look like credentials: Mailgun keys, bearer tokens and `key=value` pairs named
+/// like secrets
+static PATTERNS: LazyLock<[(Regex, &str); 3]> = LazyLock::new(|| {
+    [
+        (Regex::new(r"\bkey-[0-9A-Za-z]{32}\b").unwrap(), MASK),
+        (
+            Regex::new(r"(?i)\b(bearer|basic)\s+[A-Za-z0-9._~+/-]+=*").unwrap(),
+            "$1 [REDACTED]",
+        ),
+        (
+            Regex::new(
+                r#"(?i)\b((?:api[_-]?key|token|password|passwd|secret|authorization)["']?\s*[:=]\s*["']?)[^\s"'&,;]+"#,
+            )
+            .unwrap(),
+            "${1}[REDACTED]",
+        ),
+    ]
+});
+
+/// Masks the value from now on, short values are ignored as they would mask too much
+pub fn register(secret: &str) {
+    if secret.len() < 4 {
+        return;
+    }
+
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+        // Longer secrets first, so one containing another is masked whole
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+}
+
+/// The text with registered secrets and credential lookalikes masked
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let mut text = Cow::Borrowed(text);
+
+    for secret in SECRETS.read().unwrap().iter() {
+        if text.contains(secret.as_str()) {
+            text = Cow::Owned(text.replace(secret.as_str(), MASK));
+        }
+    }
+    for (pattern, replacement) in PATTERNS.iter() {
+        if pattern.is_match(&text) {
+            text = Cow::Owned(pattern.replace_all(&text, *replacement).into_owned());
+        }
+    }
+
+    text
+}
+
+/// Passes records on to the inner logger with their messages redacted
+pub struct Logger(pub Box<dyn log::Log>);
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        let message = record.args().to_string();
+        self.0.log(
+            &log::Record::builder()
+                .args(format_args!("{}", redact(&message)))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
@@ -0,0 +1,124 @@
+use crate::parser::{self, Kind, ParsedRelease};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub use crate::parser::{Locale, Mode};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Release {
+    Episode {
+        name: String,
+        season: u32,
+        episode: u32,
+    },
+    Movie {
+        name: String,
+        year: u32,
+    },
+    Album {
+        artist: String,
+        name: String,
+        year: u32,
+    },
+}
+
+impl Release {
+    pub fn parse(file_name: &str, mode: Mode) -> Result<Release> {
+        parser::parse(file_name, mode)
+            .map(Release::from)
+            .ok_or(anyhow!(
+                "Failed to get destination file name from rar file stem"
+            ))
+    }
+
+    /// Parses the file name with the names of the directories it is in as hints, see
+    /// [`parser::parse_with_hints`]
+    pub fn parse_with_hints(file_name: &str, directories: &[&str], mode: Mode) -> Result<Release> {
+        parser::parse_with_hints(file_name, directories, mode)
+            .map(Release::from)
+            .ok_or(anyhow!(
+                "Failed to get destination file name from rar file stem or its directories"
+            ))
+    }
+
+    /// The release with its name cased by the rules of the locale, see [`parser::recase`].
+    /// Albums keep their names as written
+    pub fn recased(self, locale: Locale) -> Release {
+        match self {
+            album @ Release::Album { .. } => album,
+            release => {
+                let name = parser::recase(release.name(), locale);
+                release.with_name(name)
+            }
+        }
+    }
+
+    /// The release with another name
+    pub fn with_name(self, name: String) -> Release {
+        match self {
+            Release::Episode {
+                season, episode, ..
+            } => Release::Episode {
+                name,
+                season,
+                episode,
+            },
+            Release::Movie { year, .. } => Release::Movie { name, year },
+            Release::Album { artist, year, .. } => Release::Album { artist, name, year },
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Release::Episode { name, .. }
+            | Release::Movie { name, .. }
+            | Release::Album { name, .. } => name,
+        }
+    }
+
+    /// Whether both are the same episode or movie, with names differing only in case
+    pub fn is_same(&self, other: &Release) -> bool {
+        let same_name = self.name().to_lowercase() == other.name().to_lowercase();
+        match (self, other) {
+            (
+                Release::Episode {
+                    season, episode, ..
+                },
+                Release::Episode {
+                    season: other_season,
+                    episode: other_episode,
+                    ..
+                },
+            ) => same_name && season == other_season && episode == other_episode,
+            (
+                Release::Movie { year, .. },
+                Release::Movie {
+                    year: other_year, ..
+                },
+            ) => same_name && year == other_year,
+            _ => false,
+        }
+    }
+}
+
+impl From<ParsedRelease> for Release {
+    fn from(release: ParsedRelease) -> Release {
+        match release.kind {
+            Kind::Episode => Release::Episode {
+                name: release.title,
+                season: release.season.unwrap_or(1),
+                episode: release.episode.unwrap_or_default(),
+            },
+            Kind::Movie => Release::Movie {
+                name: release.title,
+                year: release.year.unwrap_or_default(),
+            },
+            Kind::Album => Release::Album {
+                artist: release.artist.unwrap_or_default(),
+                name: release.title,
+                year: release.year.unwrap_or_default(),
+            },
+        }
+    }
+}
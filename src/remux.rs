@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A container Matroska videos are remuxed into after extraction
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    /// MP4, for devices that can't play MKV
+    Mp4,
+}
+
+impl Container {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+        }
+    }
+
+    fn arguments(&self) -> &'static [&'static str] {
+        match self {
+            // The index first, so playback starts before the whole file is read
+            Container::Mp4 => &["-movflags", "+faststart"],
+        }
+    }
+}
+
+/// Whether the video is one [`remux`] remuxes
+pub fn is_remuxed(video: &Path) -> bool {
+    video
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mkv"))
+}
+
+/// Remuxes the main video, the first of the files, into the container with ffmpeg when it is
+/// an MKV. The streams are copied without re-encoding, leaving out the subtitles that MP4
+/// can't hold, and the remuxed file replaces the video with the extension of the container
+pub fn remux(mut files: Vec<PathBuf>, container: Container, ffmpeg: &Path) -> Result<Vec<PathBuf>> {
+    let Some(video) = files.first().filter(|video| is_remuxed(video)).cloned() else {
+        return Ok(files);
+    };
+    let remuxed = video.with_extension(container.extension());
+    let partial = video.with_extension(format!("remuxing.{}", container.extension()));
+
+    let output = Command::new(ffmpeg)
+        .args(["-nostdin", "-y", "-loglevel", "error", "-i"])
+        .arg(&video)
+        .args(["-map", "0:v", "-map", "0:a?", "-c", "copy"])
+        .args(container.arguments())
+        .arg(&partial)
+        .output()
+        .with_context(|| format!("Failed to start {:?}", ffmpeg))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "Failed to remux {:?}, ffmpeg exited with {}: {}",
+            video,
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    fs::rename(&partial, &remuxed)
+        .with_context(|| format!("Failed to rename remuxed {:?}", partial))?;
+    fs::remove_file(&video).with_context(|| format!("Failed to delete remuxed {:?}", video))?;
+    info!("Remuxed {:?} to {:?}", video, remuxed);
+
+    files[0] = remuxed;
+    Ok(files)
+}
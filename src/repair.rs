@@ -0,0 +1,51 @@
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The `.rev` recovery volumes next to the rar file
+pub fn recovery_volumes(rar_file: &Path) -> Vec<PathBuf> {
+    let Some(entries) = rar_file
+        .parent()
+        .and_then(|directory| directory.read_dir().ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut volumes = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("rev"))
+        })
+        .collect::<Vec<_>>();
+    volumes.sort();
+
+    volumes
+}
+
+/// Reconstructs the missing and broken volumes of the rar file from its recovery volumes with
+/// the `rc` command of rar, as the unrar library can't
+pub fn reconstruct(rar_file: &Path, rar: &Path) -> Result<()> {
+    let output = Command::new(rar)
+        .args(["rc", "-y", "--"])
+        .arg(rar_file)
+        .output()
+        .with_context(|| format!("Failed to start {:?}", rar))?;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!("rar: {line}");
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "Failed to reconstruct the volumes of {:?}, rar exited with {}: {}",
+            rar_file,
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    info!("Reconstructed the volumes of {:?}", rar_file);
+    Ok(())
+}
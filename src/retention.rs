@@ -0,0 +1,144 @@
+use crate::config;
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(clap::Args, Debug)]
+pub struct CleanupArgs {
+    /// Show what would be deleted, without deleting anything
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// A directory, e.g. a folder of recent additions, whose files are deleted once they are older
+/// than `max_age_days` and the library has them too
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Retention {
+    pub directory: PathBuf,
+
+    /// The main library, which must have a file of the same size at the same path below it
+    /// before one is deleted
+    pub library: PathBuf,
+
+    pub max_age_days: u64,
+}
+
+impl Retention {
+    /// Deletes the expired files, then the directories left empty, and returns how many files
+    /// were deleted
+    pub fn apply(&self, dry_run: bool) -> Result<usize> {
+        if !self.library.is_dir() {
+            return Err(anyhow!(
+                "Library {:?} of retention directory {:?} is not a directory",
+                self.library,
+                self.directory
+            ));
+        }
+
+        let directory = self.directory.canonicalize().with_context(|| {
+            format!("Failed to resolve retention directory {:?}", self.directory)
+        })?;
+        let library = self
+            .library
+            .canonicalize()
+            .context("Failed to resolve library directory")?;
+        if library.starts_with(&directory) || directory.starts_with(&library) {
+            return Err(anyhow!(
+                "Retention directory {:?} and its library can't be inside each other",
+                self.directory
+            ));
+        }
+
+        let max_age = Duration::from_secs(self.max_age_days * 24 * 60 * 60);
+        self.apply_below(&self.directory, max_age, dry_run)
+    }
+
+    fn apply_below(&self, directory: &Path, max_age: Duration, dry_run: bool) -> Result<usize> {
+        let mut deleted = 0;
+
+        let entries = fs::read_dir(directory)
+            .with_context(|| format!("Failed to read retention directory {:?}", directory))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                deleted += self.apply_below(&path, max_age, dry_run)?;
+                if !dry_run && fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_none())
+                {
+                    fs::remove_dir(&path)
+                        .with_context(|| format!("Failed to delete {:?}", path))?;
+                }
+                continue;
+            }
+            if !metadata.is_file() || added(&metadata).elapsed().unwrap_or_default() < max_age {
+                continue;
+            }
+
+            if !self.in_library(&path, metadata.len()) {
+                info!("Keeping {:?}, the library doesn't have it", path);
+                continue;
+            }
+            if dry_run {
+                info!("Would delete expired file: {:?}", path);
+            } else {
+                fs::remove_file(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+                info!("Deleted expired file: {:?}", path);
+            }
+            deleted += 1;
+        }
+
+        Ok(deleted)
+    }
+
+    fn in_library(&self, path: &Path, size: u64) -> bool {
+        path.strip_prefix(&self.directory)
+            .ok()
+            .and_then(|relative| self.library.join(relative).metadata().ok())
+            .is_some_and(|metadata| metadata.is_file() && metadata.len() == size)
+    }
+}
+
+/// When the file was added to the directory, the later of its creation and modification
+/// times, as imports keep the modification time of their source
+fn added(metadata: &fs::Metadata) -> SystemTime {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    metadata
+        .created()
+        .map_or(modified, |created| created.max(modified))
+}
+
+/// Applies every retention policy of the config file, logging the ones that fail
+pub fn apply_all(dry_run: bool) -> usize {
+    config::current()
+        .retention
+        .iter()
+        .map(|retention| match retention.apply(dry_run) {
+            Ok(deleted) => deleted,
+            Err(e) => {
+                error!("{e:#}");
+                0
+            }
+        })
+        .sum()
+}
+
+pub fn cleanup(args: &CleanupArgs) -> Result<()> {
+    if config::current().retention.is_empty() {
+        return Err(anyhow!("No [[retention]] tables in the config file"));
+    }
+
+    let deleted = apply_all(args.dry_run);
+    match args.dry_run {
+        true => println!("Would delete {deleted} expired files"),
+        false => println!("Deleted {deleted} expired files"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anyhow::{anyhow, Result};
+use dessert::history::{self, History};
+use dessert::summary::Summary;
+use dessert::{exit, output, Job, Options, Pipeline};
+use log::info;
+use std::fs::File;
+use std::time::Instant;
+
+#[derive(clap::Args, Debug)]
+pub struct RetryArgs {
+    /// The id of the job to retry, as shown by `dessert history`
+    #[clap(required_unless_present = "all_failed")]
+    id: Option<i64>,
+
+    /// Retry every source whose latest job failed
+    #[clap(long, conflicts_with = "id")]
+    all_failed: bool,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+pub fn retry(args: &RetryArgs, mut log_file: File) -> Result<()> {
+    args.options.drop_privileges()?;
+    let history_database = history::database(args.options.history_database.as_deref())?;
+    let history = History::open(&history_database)?;
+
+    let entries = match args.id {
+        Some(id) => vec![history
+            .get(id)?
+            .ok_or(anyhow!("No job with id {id} in the history"))?],
+        None => history.unresolved_failures()?,
+    };
+
+    let jobs = entries
+        .into_iter()
+        .map(|entry| {
+            info!("Retrying job {}: {:?}", entry.id, entry.source_directory);
+            Job {
+                source_directory: entry.source_directory,
+                destination_directory: entry.destination_directory,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if jobs.is_empty() {
+        println!("Nothing to retry");
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let pipeline = Pipeline::new(args.options.clone());
+    let (outcomes, details) = pipeline.run_all(jobs);
+    let summary = Summary::new(&outcomes, started.elapsed());
+    summary.report();
+    output::print(&outcomes, &details);
+    pipeline.notify(&summary, &mut log_file)?;
+    exit::check(&outcomes)
+}
@@ -0,0 +1,88 @@
+use crate::naming;
+use crate::release::Release;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Overrides for releases whose parsed title matches a regex, from the `[[rule]]` tables of the config file
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Regex matched against the parsed title, e.g. `"^Doctor Who"`
+    #[serde(rename = "match")]
+    pub pattern: String,
+
+    /// Title used instead of the parsed one
+    pub title: Option<String>,
+
+    /// Destination used instead of the job's destination directory
+    pub destination_directory: Option<PathBuf>,
+
+    /// Naming template used instead of the one from `[naming]`
+    pub template: Option<String>,
+
+    /// Added to the parsed season number of episodes
+    #[serde(default)]
+    pub season_offset: i64,
+
+    /// Added to the parsed episode number of episodes
+    #[serde(default)]
+    pub episode_offset: i64,
+}
+
+impl Rule {
+    /// The first rule matching the release's parsed title
+    pub fn find<'a>(rules: &'a [Rule], release: &Release) -> Result<Option<&'a Rule>> {
+        for rule in rules {
+            let pattern = Regex::new(&rule.pattern)
+                .with_context(|| format!("Failed to compile rule regex {:?}", rule.pattern))?;
+
+            if pattern.is_match(release.name()) {
+                return Ok(Some(rule));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn apply(&self, release: Release) -> Result<Release> {
+        match release {
+            Release::Episode {
+                name,
+                season,
+                episode,
+            } => Ok(Release::Episode {
+                name: self.title.clone().unwrap_or(name),
+                season: offset(season, self.season_offset).ok_or(anyhow!(
+                    "Season offset of rule {:?} is out of range",
+                    self.pattern
+                ))?,
+                episode: offset(episode, self.episode_offset).ok_or(anyhow!(
+                    "Episode offset of rule {:?} is out of range",
+                    self.pattern
+                ))?,
+            }),
+            Release::Movie { name, year } => Ok(Release::Movie {
+                name: self.title.clone().unwrap_or(name),
+                year,
+            }),
+            Release::Album { artist, name, year } => Ok(Release::Album {
+                artist,
+                name: self.title.clone().unwrap_or(name),
+                year,
+            }),
+        }
+    }
+
+    /// The destination file name, when the rule has its own naming template
+    pub fn file_name(&self, release: &Release) -> Option<Result<String>> {
+        self.template
+            .as_deref()
+            .map(|template| naming::render(template, release))
+    }
+}
+
+fn offset(number: u32, offset: i64) -> Option<u32> {
+    u32::try_from(i64::from(number) + offset).ok()
+}
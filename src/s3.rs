@@ -0,0 +1,332 @@
+use crate::backend::{self, Backend, S3};
+use crate::{shutdown, timeout};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use reqwest::blocking::{Body, Response};
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Files larger than this are uploaded in parts of this size, so no more than a part of a file
+/// is held in memory
+const PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The payload hash of requests whose bodies are streamed instead of hashed up front
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// A client of the bucket of an [`S3`] backend
+pub struct Client {
+    http: reqwest::blocking::Client,
+    config: S3,
+}
+
+impl Client {
+    /// Checks that the bucket exists and that the credentials may use it
+    pub fn connect(config: &S3) -> Result<Client> {
+        let client = Client {
+            // Uploads of large parts over slow links take long, so only connecting is timed
+            http: reqwest::blocking::Client::builder()
+                .connect_timeout(Duration::from_secs(30))
+                .timeout(None)
+                .build()
+                .context("Failed to create HTTP client")?,
+            config: config.clone(),
+        };
+        client.request(Method::HEAD, "", &[], Body::from(Vec::new()))?;
+        debug!("Connected to bucket {}", config.bucket);
+
+        Ok(client)
+    }
+
+    /// Uploads the file in one request, or in parts when it is large
+    fn upload(&self, file: &Path, key: &str) -> Result<()> {
+        let size = file
+            .metadata()
+            .with_context(|| format!("Failed to read metadata of {:?}", file))?
+            .len();
+        if size <= PART_SIZE {
+            let source = File::open(file).with_context(|| format!("Failed to open {:?}", file))?;
+            self.request(Method::PUT, key, &[], Body::sized(source, size))?;
+        } else {
+            self.upload_parts(file, key)?;
+        }
+        info!("Uploaded {:?} to s3://{}/{key}", file, self.config.bucket);
+
+        Ok(())
+    }
+
+    /// Uploads the file by a multipart upload, aborting it if a part fails so the bucket doesn't
+    /// keep the parts
+    fn upload_parts(&self, file: &Path, key: &str) -> Result<()> {
+        let response = self.request(
+            Method::POST,
+            key,
+            &[("uploads", "")],
+            Body::from(Vec::new()),
+        )?;
+        let upload_id = tag(&response.text()?, "UploadId")
+            .ok_or(anyhow!("S3 started the upload without an upload id"))?;
+
+        let result = self.send_parts(file, key, &upload_id);
+        if result.is_err() {
+            let aborted = self.request(
+                Method::DELETE,
+                key,
+                &[("uploadId", &upload_id)],
+                Body::from(Vec::new()),
+            );
+            if let Err(e) = aborted {
+                warn!("Failed to abort the upload of {key}: {e:#}");
+            }
+        }
+
+        result
+    }
+
+    fn send_parts(&self, file: &Path, key: &str, upload_id: &str) -> Result<()> {
+        let mut source = File::open(file).with_context(|| format!("Failed to open {:?}", file))?;
+        let mut parts = String::new();
+        for number in 1.. {
+            if shutdown::aborted() {
+                return Err(anyhow!("Upload aborted by shutdown"));
+            }
+            if let Some(expired) = timeout::expired() {
+                return Err(anyhow!(
+                    "Upload timed out after {}",
+                    humantime::format_duration(expired)
+                ));
+            }
+
+            let mut part = Vec::with_capacity(PART_SIZE as usize);
+            (&mut source)
+                .take(PART_SIZE)
+                .read_to_end(&mut part)
+                .with_context(|| format!("Failed to read {:?}", file))?;
+            if part.is_empty() {
+                break;
+            }
+
+            let number = number.to_string();
+            let response = self.request(
+                Method::PUT,
+                key,
+                &[("partNumber", &number), ("uploadId", upload_id)],
+                Body::from(part),
+            )?;
+            let etag = response
+                .headers()
+                .get("ETag")
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or(anyhow!("S3 took part {number} without an ETag"))?;
+            parts.push_str(&format!(
+                "<Part><PartNumber>{number}</PartNumber><ETag>{etag}</ETag></Part>"
+            ));
+            debug!("Uploaded part {number} of {key}");
+        }
+
+        let response = self.request(
+            Method::POST,
+            key,
+            &[("uploadId", upload_id)],
+            Body::from(format!(
+                "<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>"
+            )),
+        )?;
+        // Completing can fail after S3 has answered with a success, as it keeps the connection
+        // alive while it assembles the parts
+        let body = response.text()?;
+        if body.contains("<Error>") {
+            return Err(anyhow!(
+                "Failed to complete the upload of s3://{}/{key}: {}",
+                self.config.bucket,
+                tag(&body, "Message").unwrap_or(body)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Sends a request for the key of the bucket, or for the bucket itself with an empty key,
+    /// signed with the credentials
+    fn request(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Body,
+    ) -> Result<Response> {
+        let target = match key {
+            "" => format!("bucket {}", self.config.bucket),
+            key => format!("s3://{}/{key}", self.config.bucket),
+        };
+        let endpoint = Url::parse(&self.config.endpoint).context("Invalid S3 endpoint")?;
+        let mut host = endpoint
+            .host_str()
+            .ok_or(anyhow!("The S3 endpoint has no host"))?
+            .to_string();
+        let mut path = endpoint.path().trim_end_matches('/').to_string();
+        if self.config.path_style {
+            path.push('/');
+            path.push_str(&encode(&self.config.bucket));
+        } else {
+            host = format!("{}.{host}", self.config.bucket);
+        }
+        if let Some(port) = endpoint.port() {
+            host.push_str(&format!(":{port}"));
+        }
+        if !key.is_empty() {
+            for segment in key.split('/') {
+                path.push('/');
+                path.push_str(&encode(segment));
+            }
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+
+        let mut query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+            .collect::<Vec<_>>();
+        query.sort();
+        let query = query.join("&");
+        let url = match query.as_str() {
+            "" => format!("{}://{host}{path}", endpoint.scheme()),
+            query => format!("{}://{host}{path}?{query}", endpoint.scheme()),
+        };
+
+        let date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let authorization =
+            authorization(&self.config, method.as_str(), &host, &path, &query, &date);
+        debug!("{method} {url}");
+        let response = self
+            .http
+            .request(method, url)
+            .header("x-amz-date", &date)
+            .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .header("Authorization", authorization)
+            .body(body)
+            .send()
+            .with_context(|| format!("Failed to reach S3 for {target}"))?;
+        debug!("S3 responded with {}", response.status());
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().unwrap_or_default();
+            return Err(match tag(&body, "Message") {
+                Some(message) => anyhow!("S3 refused {target}: {status} {message}"),
+                None => anyhow!("S3 refused {target}: {status}"),
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// The key of a file of the destination directory, its path below it after the prefix
+    fn key(&self, destination_directory: &Path, file: &Path) -> Result<String> {
+        let relative = file
+            .strip_prefix(destination_directory)
+            .map_err(|_| anyhow!("{:?} is not in the destination directory", file))?;
+
+        let mut key = self.config.prefix.trim_matches('/').to_string();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => {
+                    if !key.is_empty() {
+                        key.push('/');
+                    }
+                    key.push_str(&name.to_string_lossy());
+                }
+                _ => return Err(anyhow!("Unexpected path {:?} to upload", relative)),
+            }
+        }
+
+        Ok(key)
+    }
+}
+
+impl Backend for Client {
+    fn store(&self, destination_directory: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        files
+            .iter()
+            .map(|file| {
+                let key = self.key(destination_directory, file)?;
+                self.upload(file, &key)?;
+                Ok(PathBuf::from(format!("s3://{}/{key}", self.config.bucket)))
+            })
+            .collect()
+    }
+
+    fn release(&self, destination_directory: &Path, files: &[PathBuf]) -> Result<()> {
+        backend::remove_staged(destination_directory, files)
+    }
+}
+
+/// The `Authorization` header of a request by AWS Signature Version 4, signing the host and
+/// `x-amz-*` headers with the streamed payload left unsigned
+fn authorization(
+    config: &S3,
+    method: &str,
+    host: &str,
+    path: &str,
+    query: &str,
+    date: &str,
+) -> String {
+    let day = &date[..8];
+    let scope = format!("{day}/{}/s3/aws4_request", config.region);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{method}\n{path}\n{query}\nhost:{host}\nx-amz-content-sha256:{UNSIGNED_PAYLOAD}\n\
+         x-amz-date:{date}\n\n{signed_headers}\n{UNSIGNED_PAYLOAD}"
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac(format!("AWS4{}", config.secret_access_key).as_bytes(), day);
+    for part in [config.region.as_str(), "s3", "aws4_request"] {
+        key = hmac(&key, part);
+    }
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={}",
+        config.access_key_id,
+        hex(&hmac(&key, &string_to_sign))
+    )
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent encodes all but the unreserved characters, as signing expects
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The text of the first element of the XML, like the `UploadId` of a multipart upload
+fn tag(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+
+    Some(xml[start..end].to_string())
+}
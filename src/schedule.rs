@@ -0,0 +1,45 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeDelta};
+use std::str::FromStr;
+
+/// A cron schedule, accepting the classic five fields as well as cron's seconds field
+pub struct Schedule {
+    schedule: cron::Schedule,
+    next: Option<DateTime<Local>>,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule> {
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {expression}")
+        } else {
+            expression.to_string()
+        };
+
+        let schedule = cron::Schedule::from_str(&expression)
+            .with_context(|| format!("Failed to parse schedule {expression:?}"))?;
+        let next = schedule.upcoming(Local).next();
+
+        Ok(Schedule { schedule, next })
+    }
+
+    /// The last time the schedule fired before now, looking back a month at most
+    pub fn previous(&self) -> Option<DateTime<Local>> {
+        let now = Local::now();
+        self.schedule
+            .after(&(now - TimeDelta::days(31)))
+            .take_while(|time| *time <= now)
+            .last()
+    }
+
+    /// Returns true once for every time the schedule has fired since the last call
+    pub fn due(&mut self) -> bool {
+        match self.next {
+            Some(next) if next <= Local::now() => {
+                self.next = self.schedule.upcoming(Local).next();
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{hooks, redact};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+
+/// The fields holding secrets, by config file section
+const SECRETS: [(&str, &str); 6] = [
+    ("email", "api_key"),
+    ("mqtt", "password"),
+    ("bazarr", "api_key"),
+    ("tmdb", "api_key"),
+    ("sftp", "passphrase"),
+    ("s3", "secret_access_key"),
+];
+
+/// Resolves the secrets of every section of a config file, see [`resolve`]
+pub fn resolve_config(config: &mut toml::Table) -> Result<()> {
+    for (section, field) in SECRETS {
+        if let Some(toml::Value::Table(table)) = config.get_mut(section) {
+            resolve(table, field)
+                .with_context(|| format!("Failed to resolve {section}.{field}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces `<field>_file` with the contents of the file, or `<field>_cmd` with the output of
+/// the command, as `<field>`. The secret is registered for redaction either way
+pub fn resolve(table: &mut toml::Table, field: &str) -> Result<()> {
+    let file = table.remove(&format!("{field}_file"));
+    let cmd = table.remove(&format!("{field}_cmd"));
+
+    let secret = match (file, cmd) {
+        (None, None) => {
+            if let Some(toml::Value::String(secret)) = table.get(field) {
+                redact::register(secret);
+            }
+            return Ok(());
+        }
+        (Some(_), Some(_)) => {
+            return Err(anyhow!("Expected only one of {field}_file and {field}_cmd"))
+        }
+        _ if table.contains_key(field) => {
+            return Err(anyhow!(
+                "Expected only one of {field}, {field}_file and {field}_cmd"
+            ))
+        }
+        (Some(toml::Value::String(path)), None) => fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read secret file {path:?}"))?,
+        (None, Some(toml::Value::String(command))) => run(&command)?,
+        _ => return Err(anyhow!("Expected a string for {field}_file or {field}_cmd")),
+    };
+
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    redact::register(secret);
+    table.insert(field.to_string(), toml::Value::String(secret.to_string()));
+
+    Ok(())
+}
+
+fn run(command: &str) -> Result<String> {
+    let output = hooks::shell(command)
+        .output()
+        .with_context(|| format!("Failed to start secret command: {command}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => anyhow!("{command} exited with {}", output.status),
+            stderr => anyhow!("{command} exited with {}: {stderr}", output.status),
+        });
+    }
+
+    String::from_utf8(output.stdout).context("Secret command printed invalid UTF-8")
+}
@@ -0,0 +1,319 @@
+use crate::systemd;
+use anyhow::{anyhow, Context, Result};
+use clap::CommandFactory;
+use dessert::config::Reloader;
+use dessert::queue::Pool;
+use dessert::summary::Summary;
+use dessert::{context, metrics, shutdown, status, DessertError, Job, Options, Pipeline};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// The address to listen on
+    #[clap(short, long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Destination used when a job doesn't specify one
+    #[clap(short, long)]
+    destination_directory: Option<PathBuf>,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+#[derive(Deserialize)]
+struct JobRequest {
+    source_directory: PathBuf,
+    destination_directory: Option<PathBuf>,
+    write_nfo: Option<bool>,
+    write_checksum: Option<bool>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    /// Not selected by the filter, or skipped by the pipeline like known content
+    Skipped,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Succeeded => "succeeded",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct JobRecord {
+    id: u64,
+    status: Status,
+    source_directory: PathBuf,
+    destination_directory: PathBuf,
+    file_name: Option<String>,
+    files: Vec<PathBuf>,
+    error: Option<String>,
+}
+
+type Jobs = Arc<Mutex<HashMap<u64, JobRecord>>>;
+
+pub fn serve(args: &ServeArgs, log_file: File) -> Result<()> {
+    let server = Server::http(&args.listen)
+        .map_err(|e| anyhow!("Failed to listen on {}: {e}", args.listen))?;
+    info!("Listening on {}", args.listen);
+    args.options.drop_privileges()?;
+    shutdown::install()?;
+    let mut reloader = Reloader::new(crate::Cli::command())?;
+
+    let jobs: Jobs = Arc::default();
+    let (sender, receiver) = mpsc::channel();
+
+    let worker = thread::spawn({
+        let jobs = jobs.clone();
+        let limit = args.options.jobs;
+        move || work(receiver, jobs, limit, log_file)
+    });
+
+    let mut next_id = 1;
+    while !shutdown::requested() {
+        reloader.check();
+        status::heartbeat();
+        let mut request = match server.recv_timeout(Duration::from_secs(1)) {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Failed to receive request: {e}");
+                continue;
+            }
+        };
+
+        debug!("{} {}", request.method(), request.url());
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let query = query.to_string();
+        let segments = path
+            .trim_matches('/')
+            .split('/')
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        let response = match (request.method(), segments.as_slice()) {
+            (Method::Post, [jobs_segment]) if jobs_segment == "jobs" => {
+                match create_job(&mut request, args, next_id) {
+                    Ok((job, options)) => {
+                        let record = JobRecord {
+                            id: next_id,
+                            status: Status::Queued,
+                            source_directory: job.source_directory.clone(),
+                            destination_directory: job.destination_directory.clone(),
+                            file_name: None,
+                            files: Vec::new(),
+                            error: None,
+                        };
+                        jobs.lock().unwrap().insert(next_id, record.clone());
+                        sender
+                            .send((next_id, job, options))
+                            .context("Failed to queue job")?;
+                        metrics::job_queued();
+                        next_id += 1;
+                        json_response(202, &record)
+                    }
+                    Err(e) => error_response(400, &e.to_string()),
+                }
+            }
+            (Method::Get, [jobs_segment, id]) if jobs_segment == "jobs" => {
+                match id
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|id| jobs.lock().unwrap().get(&id).cloned())
+                {
+                    Some(record) => json_response(200, &record),
+                    None => error_response(404, "Job not found"),
+                }
+            }
+            (Method::Get, [jobs_segment]) if jobs_segment == "jobs" => list_jobs(&jobs, &query),
+            (Method::Get, [metrics_segment]) if metrics_segment == "metrics" => metrics::response(),
+            (Method::Get, [health_segment]) if health_segment == "healthz" => {
+                metrics::health_response()
+            }
+            _ => error_response(404, "Not found"),
+        };
+
+        if let Err(e) = request.respond(response) {
+            error!("Failed to send response: {e}");
+        }
+    }
+
+    systemd::stopping();
+    drop(sender);
+    if worker.join().is_err() {
+        error!("Job worker panicked");
+    }
+    shutdown::finish()
+}
+
+fn create_job(request: &mut Request, args: &ServeArgs, id: u64) -> Result<(Job, Options)> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .context("Failed to read request body")?;
+
+    let job_request =
+        serde_json::from_str::<JobRequest>(&body).context("Failed to parse job request")?;
+
+    let destination_directory = job_request
+        .destination_directory
+        .or(args.destination_directory.clone())
+        .ok_or(anyhow!("Missing destination directory"))?;
+
+    let mut options = args.options.clone();
+    if let Some(write_nfo) = job_request.write_nfo {
+        options.write_nfo = write_nfo;
+    }
+    if let Some(write_checksum) = job_request.write_checksum {
+        options.write_checksum = write_checksum;
+    }
+
+    let job = options.map_source(Job {
+        source_directory: job_request.source_directory,
+        destination_directory,
+    })?;
+
+    info!(
+        "Queued job {id}: {:?} -> {:?}",
+        job.source_directory, job.destination_directory
+    );
+
+    Ok((job, options))
+}
+
+fn work(receiver: Receiver<(u64, Job, Options)>, jobs: Jobs, limit: usize, log_file: File) {
+    let pool = Pool::new(limit);
+    let log_file = Arc::new(Mutex::new(log_file));
+    let serving = Instant::now();
+    let totals = Arc::<Mutex<Summary>>::default();
+
+    for (id, job, options) in receiver {
+        if shutdown::requested() {
+            info!("Dropped queued job {id} because of shutdown");
+            metrics::job_cancelled();
+            update(&jobs, id, |record| {
+                record.status = Status::Failed;
+                record.error = Some("Cancelled by shutdown".to_string());
+            });
+            continue;
+        }
+
+        let jobs = jobs.clone();
+        let log_file = log_file.clone();
+        let totals = totals.clone();
+        let pipeline = Pipeline::new(options);
+
+        pool.spawn(move || {
+            let _job = context::enter_job(Some(id));
+            let _running = metrics::job_started();
+            update(&jobs, id, |record| record.status = Status::Running);
+            info!("Started job {id}");
+
+            let started = Instant::now();
+            let imports = match pipeline.options().selects(&job.source_directory) {
+                true => Pipeline::jobs(job)
+                    .iter()
+                    .map(|job| pipeline.run(job))
+                    .collect::<Vec<_>>(),
+                false => vec![Err(DessertError::Skipped)],
+            };
+
+            // A season pack succeeds once every episode of it did, listing all of their files
+            update(&jobs, id, |record| {
+                let errors = imports
+                    .iter()
+                    .filter_map(|import| import.as_ref().err())
+                    .filter(|e| !matches!(e, DessertError::Skipped))
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>();
+                let imported = imports.iter().flatten().collect::<Vec<_>>();
+                record.status = match (errors.is_empty(), imported.is_empty()) {
+                    (false, _) => Status::Failed,
+                    (true, true) => Status::Skipped,
+                    (true, false) => Status::Succeeded,
+                };
+                record.file_name = imported.first().map(|import| import.file_name.clone());
+                record.files = imported
+                    .iter()
+                    .flat_map(|import| import.files.clone())
+                    .collect();
+                record.error = (!errors.is_empty()).then(|| errors.join("; "));
+            });
+
+            let summary = Summary::new(&imports, started.elapsed());
+            totals.lock().unwrap().add(&summary);
+            if let Err(e) = pipeline.notify(&summary, &mut log_file.lock().unwrap()) {
+                error!("Failed to send notification for job {id}: {e}");
+            }
+        });
+    }
+
+    pool.wait();
+
+    let mut totals = totals.lock().unwrap().clone();
+    totals.elapsed = serving.elapsed();
+    totals.report();
+}
+
+/// The jobs with the status of the query, e.g. `status=failed`, oldest first
+fn list_jobs(jobs: &Jobs, query: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    let status = crate::web::parameter(query, "status");
+    if status.is_some_and(|status| {
+        !["queued", "running", "succeeded", "failed", "skipped"].contains(&status)
+    }) {
+        return error_response(400, "Unknown status");
+    }
+
+    let mut records = jobs
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|record| status.is_none_or(|status| record.status.as_str() == status))
+        .cloned()
+        .collect::<Vec<_>>();
+    records.sort_by_key(|record| record.id);
+    json_response(200, &records)
+}
+
+fn update(jobs: &Jobs, id: u64, f: impl FnOnce(&mut JobRecord)) {
+    if let Some(record) = jobs.lock().unwrap().get_mut(&id) {
+        f(record);
+    }
+}
+
+fn json_response(status: u16, body: &impl Serialize) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(serde_json::to_vec(body).unwrap_or_default())
+        .with_status_code(status)
+        .with_header(
+            "Content-Type: application/json"
+                .parse::<Header>()
+                .expect("Valid header"),
+        )
+}
+
+fn error_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, &serde_json::json!({ "error": message }))
+}
@@ -0,0 +1,176 @@
+use crate::backend::{self, Backend, Sftp};
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use ssh2::{CheckResult, KnownHostFileKind, RenameFlags, Session};
+use std::fs::File;
+use std::io;
+use std::net::TcpStream;
+use std::path::{Component, Path, PathBuf};
+
+/// A connection to the host of an [`Sftp`] backend
+pub struct Client {
+    sftp: ssh2::Sftp,
+    host: String,
+    remote_root: String,
+    // The SFTP channel is closed with the session
+    _session: Session,
+}
+
+impl Client {
+    /// Connects to the host, checking its key against the known hosts, and authenticates with
+    /// the private key
+    pub fn connect(config: &Sftp) -> Result<Client> {
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))
+            .with_context(|| format!("Failed to connect to {}:{}", config.host, config.port))?;
+        let mut session = Session::new().context("Failed to create SSH session")?;
+        session.set_tcp_stream(tcp);
+        session.handshake().context("Failed SSH handshake")?;
+
+        check_host_key(&session, config)?;
+        session
+            .userauth_pubkey_file(
+                &config.username,
+                None,
+                &config.private_key,
+                config.passphrase.as_deref(),
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to authenticate as {} with {:?}",
+                    config.username, config.private_key
+                )
+            })?;
+        let sftp = session.sftp().context("Failed to start SFTP")?;
+        debug!("Connected to {} over SFTP", config.host);
+
+        Ok(Client {
+            sftp,
+            host: config.host.clone(),
+            remote_root: config.remote_root.trim_end_matches('/').to_string(),
+            _session: session,
+        })
+    }
+
+    /// Uploads the file next to the remote path and renames it into place, so the library
+    /// never sees a partial file
+    fn upload(&self, file: &Path, remote: &Path) -> Result<()> {
+        if let Some(directory) = remote.parent() {
+            self.create_directories(directory)?;
+        }
+
+        let partial = PathBuf::from(format!("{}.partial", remote.display()));
+        let mut source = File::open(file).with_context(|| format!("Failed to open {:?}", file))?;
+        let mut target = self
+            .sftp
+            .create(&partial)
+            .with_context(|| format!("Failed to create {:?} on {}", partial, self.host))?;
+        io::copy(&mut source, &mut target)
+            .with_context(|| format!("Failed to upload {:?} to {}", file, self.host))?;
+        drop(target);
+
+        self.sftp
+            .rename(
+                &partial,
+                remote,
+                Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE),
+            )
+            .with_context(|| format!("Failed to rename {:?} on {}", partial, self.host))?;
+        info!("Uploaded {:?} to {}:{}", file, self.host, remote.display());
+
+        Ok(())
+    }
+
+    /// The path below the remote root of a file of the destination directory, with `/` between
+    /// the directories whatever the local platform
+    fn remote_path(&self, destination_directory: &Path, file: &Path) -> Result<PathBuf> {
+        let relative = file
+            .strip_prefix(destination_directory)
+            .map_err(|_| anyhow!("{:?} is not in the destination directory", file))?;
+
+        let mut remote = self.remote_root.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => {
+                    remote.push('/');
+                    remote.push_str(&name.to_string_lossy());
+                }
+                _ => return Err(anyhow!("Unexpected path {:?} to upload", relative)),
+            }
+        }
+
+        Ok(PathBuf::from(remote))
+    }
+
+    fn create_directories(&self, directory: &Path) -> Result<()> {
+        let missing = directory
+            .ancestors()
+            .take_while(|ancestor| {
+                !ancestor.as_os_str().is_empty() && self.sftp.stat(ancestor).is_err()
+            })
+            .collect::<Vec<_>>();
+
+        for directory in missing.into_iter().rev() {
+            self.sftp
+                .mkdir(directory, 0o755)
+                .with_context(|| format!("Failed to create {:?} on {}", directory, self.host))?;
+            debug!("Created {:?} on {}", directory, self.host);
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Client {
+    fn store(&self, destination_directory: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        files
+            .iter()
+            .map(|file| {
+                let remote = self.remote_path(destination_directory, file)?;
+                self.upload(file, &remote)?;
+                Ok(remote)
+            })
+            .collect()
+    }
+
+    fn release(&self, destination_directory: &Path, files: &[PathBuf]) -> Result<()> {
+        backend::remove_staged(destination_directory, files)
+    }
+}
+
+/// Refuses hosts whose key isn't in the known hosts file, as uploads would otherwise go to
+/// whoever answers
+fn check_host_key(session: &Session, config: &Sftp) -> Result<()> {
+    let known_hosts_file = match &config.known_hosts {
+        Some(known_hosts) => known_hosts.clone(),
+        None => directories::BaseDirs::new()
+            .ok_or(anyhow!(
+                "Failed to find the home directory, set sftp.known_hosts"
+            ))?
+            .home_dir()
+            .join(".ssh/known_hosts"),
+    };
+
+    let mut known_hosts = session
+        .known_hosts()
+        .context("Failed to read known hosts")?;
+    known_hosts
+        .read_file(&known_hosts_file, KnownHostFileKind::OpenSSH)
+        .with_context(|| format!("Failed to read known hosts file {:?}", known_hosts_file))?;
+    let (key, _) = session
+        .host_key()
+        .ok_or(anyhow!("{} sent no host key", config.host))?;
+
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(anyhow!(
+            "The host key of {} differs from the one in {:?}",
+            config.host,
+            known_hosts_file
+        )),
+        CheckResult::NotFound | CheckResult::Failure => Err(anyhow!(
+            "The host key of {} is not in {:?}, add it with ssh-keyscan",
+            config.host,
+            known_hosts_file
+        )),
+    }
+}
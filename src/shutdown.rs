@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+/// Handles SIGINT and SIGTERM for the daemon modes.
+///
+/// The first signal stops new jobs from starting and lets the running ones
+/// finish, a second one aborts running extractions and removes their files.
+pub fn install() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])
+            .context("Failed to install signal handler")?;
+        std::thread::spawn(move || signals.forever().for_each(|_| signalled()));
+    }
+
+    #[cfg(not(unix))]
+    ctrlc::set_handler(signalled).context("Failed to install signal handler")?;
+
+    Ok(())
+}
+
+/// Asks for a shutdown as a signal would, from a key press in the dashboard
+#[cfg(feature = "tui")]
+pub fn request() {
+    signalled();
+}
+
+fn signalled() {
+    match SIGNALS.fetch_add(1, Ordering::SeqCst) {
+        0 => info!("Shutting down once the running jobs finish, signal again to abort them"),
+        _ => info!("Aborting the running jobs"),
+    }
+}
+
+pub fn requested() -> bool {
+    SIGNALS.load(Ordering::SeqCst) > 0
+}
+
+pub fn aborted() -> bool {
+    SIGNALS.load(Ordering::SeqCst) > 1
+}
+
+/// The result a daemon exits with once it has shut down
+pub fn finish() -> Result<()> {
+    if aborted() {
+        Err(anyhow!("Shut down with running jobs aborted"))
+    } else {
+        info!("Shut down");
+        Ok(())
+    }
+}
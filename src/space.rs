@@ -0,0 +1,200 @@
+use crate::summary::format_bytes;
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What happens when the destination has less free space than `--min-free-space`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum LowSpace {
+    /// Refuse the import
+    #[default]
+    Refuse,
+    /// Delete the oldest items of `--expendable-directory` until there is enough, refusing the
+    /// import if there still isn't
+    Free,
+}
+
+/// Parses a size in bytes, or with a binary unit like `50G`, `100 MB` or `1.5TiB`
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid size {size:?}"))?;
+    let unit = unit.trim().to_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let multiplier = match unit {
+        "" => 1u64,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return Err(format!("Unknown unit of size {size:?}")),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Bytes available to the process on the filesystem of the path
+#[cfg(unix)]
+pub fn available(path: &Path) -> io::Result<u64> {
+    let stats = nix::sys::statvfs::statvfs(path)?;
+
+    Ok(stats.blocks_available() as u64 * stats.fragment_size() as u64)
+}
+
+/// Bytes available to the process on the filesystem of the path
+#[cfg(windows)]
+pub fn available(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    let mut available = 0;
+    // SAFETY: The path is NUL terminated and the other arguments may be null
+    match unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    } {
+        0 => Err(io::Error::last_os_error()),
+        _ => Ok(available),
+    }
+}
+
+/// Makes sure the destination has `min_free` bytes available before an import, freeing space
+/// in the expendable directory with [`LowSpace::Free`]. An expendable directory that is, holds
+/// or is inside the destination or the source is refused, as freeing space would delete them
+pub fn ensure(
+    destination: &Path,
+    source: &Path,
+    min_free: u64,
+    low_space: LowSpace,
+    expendable: Option<&Path>,
+) -> Result<()> {
+    if let (LowSpace::Free, Some(expendable)) = (low_space, expendable) {
+        for (name, directory) in [("destination", destination), ("source", source)] {
+            if overlaps(expendable, directory) {
+                return Err(anyhow!(
+                    "Refusing to free space, the expendable directory {:?} overlaps the {name} \
+                     {:?}",
+                    expendable,
+                    directory
+                ));
+            }
+        }
+    }
+
+    let mut free =
+        available(destination).context("Failed to get free space of destination directory")?;
+    if free >= min_free {
+        return Ok(());
+    }
+
+    if let (LowSpace::Free, Some(expendable)) = (low_space, expendable) {
+        // Deleting from another filesystem frees nothing on the destination
+        if !same_filesystem(expendable, destination)
+            .context("Failed to compare filesystems of expendable and destination directory")?
+        {
+            return Err(anyhow!(
+                "Refusing to import, only {} free on the destination, less than the {} of \
+                 --min-free-space, and the expendable directory {:?} is on another filesystem",
+                format_bytes(free),
+                format_bytes(min_free),
+                expendable
+            ));
+        }
+
+        for item in oldest_first(expendable)? {
+            if item.is_dir() {
+                fs::remove_dir_all(&item)
+            } else {
+                fs::remove_file(&item)
+            }
+            .with_context(|| format!("Failed to delete expendable {:?}", item))?;
+            warn!(
+                "Deleted expendable {:?} to free space on the destination",
+                item
+            );
+
+            let freed = available(destination)
+                .context("Failed to get free space of destination directory")?;
+            if freed >= min_free {
+                return Ok(());
+            }
+            if freed <= free {
+                warn!(
+                    "Deleting {:?} freed no space on the destination, stopping",
+                    item
+                );
+                break;
+            }
+            free = freed;
+        }
+    }
+
+    Err(anyhow!(
+        "Refusing to import, only {} free on the destination, less than the {} of \
+         --min-free-space",
+        format_bytes(free),
+        format_bytes(min_free)
+    ))
+}
+
+/// Whether one of the directories is or is inside the other, resolving them where they exist
+pub fn overlaps(directory: &Path, other: &Path) -> bool {
+    let directory = directory.canonicalize().unwrap_or(directory.to_path_buf());
+    let other = other.canonicalize().unwrap_or(other.to_path_buf());
+
+    directory.starts_with(&other) || other.starts_with(&directory)
+}
+
+/// Whether the paths are on the same filesystem
+#[cfg(unix)]
+fn same_filesystem(path: &Path, other: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(fs::metadata(path)?.dev() == fs::metadata(other)?.dev())
+}
+
+/// Whether the paths are on the same filesystem, taken for granted on Windows, where
+/// [`ensure`] stops once a deletion frees nothing instead
+#[cfg(windows)]
+fn same_filesystem(_: &Path, _: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// The files and directories directly in the directory, the least recently modified first
+fn oldest_first(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut items = fs::read_dir(directory)
+        .with_context(|| format!("Failed to read expendable directory {:?}", directory))?
+        .flatten()
+        .map(|entry| {
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, entry.path())
+        })
+        .collect::<Vec<_>>();
+    items.sort();
+
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
@@ -0,0 +1,75 @@
+use crate::email::Message;
+use crate::{dirs, metrics};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{error, info};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Held while the spool is sent, so concurrent notifications don't send an email twice
+static SENDING: Mutex<()> = Mutex::new(());
+
+/// Keeps the email in the spool until [`send`] gets it through
+pub fn save(message: &Message) -> Result<PathBuf> {
+    let spool = dirs::spool_dir()?;
+    let path = spool.join(format!(
+        "{}-{}.json",
+        Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+        std::process::id()
+    ));
+
+    // Written aside and renamed, so a half written email is never sent
+    let mut file = tempfile::NamedTempFile::new_in(&spool).context("Failed to spool email")?;
+    file.write_all(&serde_json::to_vec(message)?)
+        .context("Failed to spool email")?;
+    file.persist(&path).context("Failed to spool email")?;
+
+    Ok(path)
+}
+
+/// Sends the spooled emails, the oldest first, deleting each once it is sent. Stops at the
+/// first that fails, leaving it and the rest for the next time
+pub fn send(send: impl Fn(&Message) -> Result<()>) -> Result<()> {
+    let _sending = SENDING.lock().unwrap_or_else(|e| e.into_inner());
+
+    for path in pending()? {
+        let message = match fs::read(&path)
+            .context("Failed to read spooled email")
+            .and_then(|json| serde_json::from_slice::<Message>(&json).map_err(Into::into))
+        {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Dropping unreadable spooled email {:?}: {e:#}", path);
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+        };
+
+        if let Err(e) = send(&message) {
+            metrics::notification_failed();
+            return Err(e.context(format!("Failed to send {:?}", message.subject)));
+        }
+        fs::remove_file(&path).with_context(|| format!("Failed to delete sent {:?}", path))?;
+        info!("Sent email {:?}", message.subject);
+    }
+
+    Ok(())
+}
+
+/// The spooled emails, the oldest first
+fn pending() -> Result<Vec<PathBuf>> {
+    let mut pending = fs::read_dir(dirs::spool_dir()?)
+        .context("Failed to read spool directory")?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect::<Vec<_>>();
+    pending.sort();
+
+    Ok(pending)
+}
@@ -0,0 +1,261 @@
+use crate::events::{Event, Observer};
+use chrono::{DateTime, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::SharedLogger;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// What each running job is doing, by job id, for dashboards
+static JOBS: LazyLock<Mutex<BTreeMap<u64, Job>>> = LazyLock::new(Mutex::default);
+
+/// Releases a daemon has seen but not started yet
+static QUEUE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// The latest log lines, kept while a dashboard shows them
+static TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static TAILING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the terminal is taken over by a dashboard, silencing console logging
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+const TAIL_LINES: usize = 200;
+
+/// How a daemon is doing, for health checks
+static HEALTH: Mutex<Health> = Mutex::new(Health {
+    config_error: None,
+    heartbeat: None,
+    watching: None,
+    last_scan: None,
+});
+
+/// How long the loop of a daemon may go without a heartbeat before it is unhealthy
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct Health {
+    /// Why the config file failed to reload, while the previous one is kept
+    pub config_error: Option<String>,
+    /// When the loop of the daemon last went around
+    pub heartbeat: Option<Instant>,
+    /// Whether the filesystem watcher is running, for daemons that watch
+    pub watching: Option<bool>,
+    /// When the source directory was last scanned
+    pub last_scan: Option<DateTime<Utc>>,
+}
+
+impl Health {
+    pub fn healthy(&self) -> bool {
+        self.config_error.is_none()
+            && self.watching != Some(false)
+            && self
+                .heartbeat
+                .is_none_or(|heartbeat| heartbeat.elapsed() < HEARTBEAT_TIMEOUT)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "status": if self.healthy() { "ok" } else { "unhealthy" },
+            "config_loaded": self.config_error.is_none(),
+            "config_error": self.config_error,
+            "watching": self.watching,
+            "last_scan": self.last_scan.map(|last_scan| last_scan.to_rfc3339()),
+            "heartbeat_seconds": self.heartbeat.map(|heartbeat| heartbeat.elapsed().as_secs()),
+        })
+    }
+}
+
+pub fn health() -> Health {
+    HEALTH.lock().unwrap().clone()
+}
+
+pub fn heartbeat() {
+    HEALTH.lock().unwrap().heartbeat = Some(Instant::now());
+}
+
+pub fn scanned() {
+    HEALTH.lock().unwrap().last_scan = Some(Utc::now());
+}
+
+pub fn set_watching(watching: bool) {
+    HEALTH.lock().unwrap().watching = Some(watching);
+}
+
+pub fn set_config_error(error: Option<String>) {
+    HEALTH.lock().unwrap().config_error = error;
+}
+
+#[derive(Clone)]
+pub struct Job {
+    pub source: Option<PathBuf>,
+    pub phase: Option<&'static str>,
+    pub started: Instant,
+    /// How far extraction has come, while extracting
+    pub progress: Option<Progress>,
+}
+
+#[derive(Clone)]
+pub struct Progress {
+    /// Bytes written to the destination so far
+    pub bytes: u64,
+    /// Unpacked size of the archive
+    pub total: u64,
+}
+
+/// Keeps what each running job is doing from the events of the pipelines observed, for
+/// [`jobs`]
+pub struct Registry;
+
+impl Observer for Registry {
+    fn observe(&self, event: &Event) {
+        match event {
+            Event::JobStarted { job, source } => {
+                JOBS.lock().unwrap().insert(
+                    *job,
+                    Job {
+                        source: Some(source.clone()),
+                        phase: None,
+                        started: Instant::now(),
+                        progress: None,
+                    },
+                );
+            }
+            Event::Phase { job, phase } => update(*job, |status| {
+                status.phase = Some(phase);
+                status.progress = None;
+            }),
+            Event::Progress { job, bytes, total } => update(*job, |status| {
+                status.progress = Some(Progress {
+                    bytes: *bytes,
+                    total: *total,
+                })
+            }),
+            Event::EntryExtracted { .. } => {}
+            Event::JobFinished { job, .. } => {
+                JOBS.lock().unwrap().remove(job);
+            }
+        }
+    }
+}
+
+fn update(job: u64, f: impl FnOnce(&mut Job)) {
+    if let Some(status) = JOBS.lock().unwrap().get_mut(&job) {
+        f(status);
+    }
+}
+
+/// The running jobs, oldest first
+pub fn jobs() -> Vec<Job> {
+    JOBS.lock().unwrap().values().cloned().collect()
+}
+
+pub fn set_queue(releases: Vec<PathBuf>) {
+    *QUEUE.lock().unwrap() = releases;
+}
+
+pub fn queue() -> Vec<PathBuf> {
+    QUEUE.lock().unwrap().clone()
+}
+
+/// Starts keeping the latest log lines and silences the console, for a dashboard
+pub fn take_terminal() {
+    TAILING.store(true, Ordering::Relaxed);
+    MUTED.store(true, Ordering::Relaxed);
+}
+
+/// Starts keeping the latest log lines without silencing the console, for a web dashboard
+pub fn keep_tail() {
+    TAILING.store(true, Ordering::Relaxed);
+}
+
+pub fn release_terminal() {
+    TAILING.store(false, Ordering::Relaxed);
+    MUTED.store(false, Ordering::Relaxed);
+}
+
+pub fn tail() -> Vec<String> {
+    TAIL.lock().unwrap().iter().cloned().collect()
+}
+
+/// Keeps the latest log lines while a dashboard shows them
+pub struct TailLogger {
+    level: LevelFilter,
+}
+
+impl TailLogger {
+    pub fn new(level: LevelFilter) -> Box<TailLogger> {
+        Box::new(TailLogger { level })
+    }
+}
+
+impl Log for TailLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && TAILING.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut tail = TAIL.lock().unwrap();
+            if tail.len() == TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(format!(
+                "{} [{}] {}",
+                chrono::Local::now().format("%H:%M:%S"),
+                record.level(),
+                record.args()
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for TailLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Logs to the console unless a dashboard has taken over the terminal
+pub struct ConsoleLogger(pub Box<dyn SharedLogger>);
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        !MUTED.load(Ordering::Relaxed) && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !MUTED.load(Ordering::Relaxed) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+impl SharedLogger for ConsoleLogger {
+    fn level(&self) -> LevelFilter {
+        self.0.level()
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        self.0.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::remux;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Codecs of the subtitle tracks that can be converted to SubRip, the bitmap ones like PGS
+/// can't be
+const TEXT_CODECS: [&str; 6] = ["subrip", "ass", "ssa", "mov_text", "webvtt", "text"];
+
+#[derive(Deserialize)]
+struct Probe {
+    #[serde(default)]
+    streams: Vec<Stream>,
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    index: u32,
+    codec_name: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    disposition: HashMap<String, u8>,
+}
+
+/// Extracts the text subtitle tracks embedded in an MKV video to `.srt` files next to it with
+/// ffmpeg, named by their language like `Name.eng.srt` or `Name.eng.forced.srt`. Files that
+/// already exist, like subtitles from the archive, are kept
+pub fn extract(video: &Path, ffmpeg: &Path) -> Result<Vec<PathBuf>> {
+    if !remux::is_remuxed(video) {
+        return Ok(Vec::new());
+    }
+
+    let output = Command::new(ffprobe(ffmpeg))
+        .args(["-v", "error", "-of", "json"])
+        .args(["-select_streams", "s", "-show_streams"])
+        .arg(video)
+        .output()
+        .with_context(|| format!("Failed to start {:?}", ffprobe(ffmpeg)))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to probe {:?}, ffprobe exited with {}: {}",
+            video,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let probe: Probe =
+        serde_json::from_slice(&output.stdout).context("Failed to parse ffprobe output")?;
+
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    let mut names = HashMap::<String, u32>::new();
+    let mut command = Command::new(ffmpeg);
+    command
+        .args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(video);
+    let mut subtitles = Vec::new();
+    for stream in probe.streams {
+        let codec = stream.codec_name.unwrap_or_default();
+        if !TEXT_CODECS.contains(&codec.as_str()) {
+            debug!("Skipping {codec} subtitle track {}", stream.index);
+            continue;
+        }
+
+        let language = stream
+            .tags
+            .get("language")
+            .map(String::as_str)
+            .unwrap_or("und");
+        let mut name = match stream.disposition.get("forced") {
+            Some(1) => format!("{language}.forced"),
+            _ => language.to_string(),
+        };
+        // Further tracks of a language, like commentary, get numbered
+        let count = names.entry(name.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            name = format!("{name}.{count}");
+        }
+
+        let subtitle = video.with_file_name(format!("{stem}.{name}.srt"));
+        if subtitle.exists() {
+            info!("Keeping existing subtitle file: {:?}", subtitle);
+            continue;
+        }
+        command
+            .args(["-map", &format!("0:{}", stream.index), "-c:s", "srt"])
+            .arg(&subtitle);
+        subtitles.push(subtitle);
+    }
+    if subtitles.is_empty() {
+        debug!("No text subtitle tracks to extract from {:?}", video);
+        return Ok(subtitles);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to start {:?}", ffmpeg))?;
+    if !output.status.success() {
+        for subtitle in &subtitles {
+            let _ = std::fs::remove_file(subtitle);
+        }
+        return Err(anyhow!(
+            "Failed to extract subtitles from {:?}, ffmpeg exited with {}: {}",
+            video,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    for subtitle in &subtitles {
+        info!("Extracted subtitle file: {:?}", subtitle);
+    }
+
+    Ok(subtitles)
+}
+
+/// The ffprobe next to the ffmpeg executable, or the one on the path
+fn ffprobe(ffmpeg: &Path) -> PathBuf {
+    match ffmpeg.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.join("ffprobe"),
+        _ => PathBuf::from("ffprobe"),
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::DessertError;
+use crate::Outcome;
+use log::info;
+use std::fmt;
+use std::time::Duration;
+
+/// The error of a job that was skipped, counted apart from failures
+#[derive(Debug)]
+pub struct Skipped;
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Skipped")
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+/// Totals of a run, reported at its end and included in notifications
+#[derive(Default, Debug, Clone)]
+pub struct Summary {
+    pub found: usize,
+    /// File names of the imported releases
+    pub imported: Vec<String>,
+    pub skipped: usize,
+    pub failed: usize,
+    /// File names of the imported releases with the notes of their `.nfo` files
+    pub release_notes: Vec<(String, String)>,
+    /// File names of the imported releases with the URLs of their posters
+    pub artwork: Vec<(String, String)>,
+    /// File names of the imported releases that replaced a worse quality, with both qualities
+    pub upgrades: Vec<(String, String, String)>,
+    /// File names of the imported releases whose volumes were reconstructed from recovery
+    /// volumes
+    pub repaired: Vec<String>,
+    /// File names of the imported releases with the statistics of their imports
+    pub stats: Vec<(String, Stats)>,
+    /// Size of the imported files
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    pub fn new(outcomes: &[Outcome], elapsed: Duration) -> Summary {
+        let mut summary = Summary {
+            found: outcomes.len(),
+            elapsed,
+            ..Default::default()
+        };
+
+        for outcome in outcomes {
+            match outcome {
+                Ok(import) => {
+                    summary.imported.push(import.file_name.clone());
+                    if let Some(notes) = &import.release_notes {
+                        summary
+                            .release_notes
+                            .push((import.file_name.clone(), notes.clone()));
+                    }
+                    if let Some(artwork) = &import.artwork {
+                        summary
+                            .artwork
+                            .push((import.file_name.clone(), artwork.clone()));
+                    }
+                    if let Some((from, to)) = &import.upgrade {
+                        summary
+                            .upgrades
+                            .push((import.file_name.clone(), from.clone(), to.clone()));
+                    }
+                    if import.repaired {
+                        summary.repaired.push(import.file_name.clone());
+                    }
+                    summary.stats.push((import.file_name.clone(), import.stats));
+                    summary.bytes += import.bytes;
+                }
+                Err(DessertError::Skipped) => summary.skipped += 1,
+                Err(_) => summary.failed += 1,
+            }
+        }
+
+        summary
+    }
+
+    /// Adds the counts of another run, keeping the elapsed time
+    pub fn add(&mut self, other: &Summary) {
+        self.found += other.found;
+        self.imported.extend(other.imported.iter().cloned());
+        self.release_notes
+            .extend(other.release_notes.iter().cloned());
+        self.artwork.extend(other.artwork.iter().cloned());
+        self.upgrades.extend(other.upgrades.iter().cloned());
+        self.repaired.extend(other.repaired.iter().cloned());
+        self.stats.extend(other.stats.iter().cloned());
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+        self.bytes += other.bytes;
+    }
+
+    /// The summary as an aligned table of one row per total
+    pub fn table(&self) -> String {
+        [
+            ("Releases found", self.found.to_string()),
+            ("Imported", self.imported.len().to_string()),
+            ("Skipped", self.skipped.to_string()),
+            ("Failed", self.failed.to_string()),
+            ("Total size", format_bytes(self.bytes)),
+            ("Elapsed", format!("{:.1}s", self.elapsed.as_secs_f64())),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{name:<16}{value}\n"))
+        .collect()
+    }
+
+    /// The average bytes per second the imports were extracted at, weighed by their sizes
+    pub fn throughput(&self) -> Option<u64> {
+        let (bytes, extraction) =
+            self.stats
+                .iter()
+                .fold((0, Duration::ZERO), |(bytes, extraction), (_, stats)| {
+                    (bytes + stats.unpacked_size, extraction + stats.extraction)
+                });
+
+        rate(bytes, extraction)
+    }
+
+    /// Logs the summary and prints it to stdout
+    pub fn report(&self) {
+        let throughput = match self.throughput() {
+            Some(throughput) => format!(", extracted at {}/s", format_bytes(throughput)),
+            None => String::new(),
+        };
+        info!(
+            "Summary: {} found, {} imported, {} skipped, {} failed, {} in {:.1}s{throughput}",
+            self.found,
+            self.imported.len(),
+            self.skipped,
+            self.failed,
+            format_bytes(self.bytes),
+            self.elapsed.as_secs_f64()
+        );
+        crate::output::text(&self.table());
+    }
+}
+
+/// How large an import was and how long it took, to spot disks or networks slowing down
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// The size of the archive volumes or source file
+    pub archive_size: u64,
+    /// The size of the imported files
+    pub unpacked_size: u64,
+    /// How long extracting took
+    pub extraction: Duration,
+    /// How long verifying the imported files took before deleting the source, with
+    /// --delete-source
+    pub verification: Option<Duration>,
+}
+
+impl Stats {
+    /// The bytes per second the import was extracted at
+    pub fn throughput(&self) -> Option<u64> {
+        rate(self.unpacked_size, self.extraction)
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} archive, {} unpacked in {:.1}s",
+            format_bytes(self.archive_size),
+            format_bytes(self.unpacked_size),
+            self.extraction.as_secs_f64()
+        )?;
+        if let Some(throughput) = self.throughput() {
+            write!(f, " at {}/s", format_bytes(throughput))?;
+        }
+        if let Some(verification) = self.verification {
+            write!(f, ", verified in {:.1}s", verification.as_secs_f64())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn rate(bytes: u64, duration: Duration) -> Option<u64> {
+    match duration.as_secs_f64() {
+        seconds if seconds > 0.0 && bytes > 0 => Some((bytes as f64 / seconds) as u64),
+        _ => None,
+    }
+}
+
+/// The size in the largest binary unit it has one of, e.g. `1.5 GiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Context, Result};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Checks that the path is still inside the root once its symlinks are resolved, so a crafted
+/// release can't make dessert read or write files elsewhere. Paths that don't exist yet are
+/// checked by their existing ancestors
+pub fn check_inside(root: &Path, path: &Path) -> Result<()> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {:?}", root))?;
+    let resolved = resolve(path)?;
+
+    match resolved.starts_with(&root) {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Refusing to follow {path:?} to {resolved:?} outside {root:?}, pass --follow-symlinks to allow it"
+        )),
+    }
+}
+
+fn resolve(path: &Path) -> Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(resolved) => Ok(resolved),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if path.is_symlink() {
+                return Err(anyhow!(
+                    "Refusing to write through dangling symlink {path:?}"
+                ));
+            }
+            match (path.parent(), path.file_name()) {
+                (Some(parent), Some(name)) => Ok(resolve(parent)?.join(name)),
+                _ => Err(anyhow!("Failed to resolve {:?}", path)),
+            }
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to resolve {:?}", path)),
+    }
+}
@@ -0,0 +1,156 @@
+use crate::context;
+use anyhow::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Deserialize;
+use simplelog::SharedLogger;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum System {
+    /// The systemd journal, with the job and phase as `DESSERT_JOB` and `DESSERT_PHASE` fields
+    Journald,
+
+    /// The local syslog daemon, through `/dev/log`
+    Syslog,
+}
+
+/// Sends records to the system log, at the priority matching their level
+pub struct SystemLogger {
+    level: LevelFilter,
+    system: System,
+    unit: Option<String>,
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+impl SystemLogger {
+    #[cfg(unix)]
+    pub fn new(
+        level: LevelFilter,
+        system: System,
+        unit: Option<String>,
+    ) -> Result<Box<SystemLogger>> {
+        use anyhow::Context;
+
+        let path = match system {
+            System::Journald => "/run/systemd/journal/socket",
+            System::Syslog => "/dev/log",
+        };
+        let socket = std::os::unix::net::UnixDatagram::unbound()
+            .context("Failed to create system log socket")?;
+        socket
+            .connect(path)
+            .with_context(|| format!("Failed to connect to {path}"))?;
+
+        Ok(Box::new(SystemLogger {
+            level,
+            system,
+            unit,
+            socket,
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(_: LevelFilter, _: System, _: Option<String>) -> Result<Box<SystemLogger>> {
+        Err(anyhow::anyhow!(
+            "Logging to the system log is only supported on Unix"
+        ))
+    }
+
+    fn journal_entry(&self, record: &Record) -> Vec<u8> {
+        let current = context::current();
+        let mut entry = Vec::new();
+
+        field(&mut entry, "MESSAGE", &record.args().to_string());
+        field(
+            &mut entry,
+            "PRIORITY",
+            &priority(record.level()).to_string(),
+        );
+        field(&mut entry, "SYSLOG_IDENTIFIER", "dessert");
+        field(&mut entry, "SYSLOG_PID", &std::process::id().to_string());
+        field(&mut entry, "CODE_MODULE", record.target());
+        if let Some(unit) = &self.unit {
+            field(&mut entry, "UNIT", unit);
+        }
+        if let Some(job) = current.job {
+            field(&mut entry, "DESSERT_JOB", &job.to_string());
+        }
+        if let Some(phase) = current.phase {
+            field(&mut entry, "DESSERT_PHASE", phase);
+        }
+
+        entry
+    }
+
+    fn syslog_message(&self, record: &Record) -> Vec<u8> {
+        // The daemon facility, 3, in the upper bits of the priority value
+        format!(
+            "<{}>dessert[{}]: {}",
+            3 * 8 + priority(record.level()),
+            std::process::id(),
+            record.args()
+        )
+        .into_bytes()
+    }
+}
+
+impl Log for SystemLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = match self.system {
+            System::Journald => self.journal_entry(record),
+            System::Syslog => self.syslog_message(record),
+        };
+        #[cfg(unix)]
+        let _ = self.socket.send(&message);
+        #[cfg(not(unix))]
+        let _ = message;
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for SystemLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// The syslog severity of the level, which the journal uses as well
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Appends a field in the journal's native protocol, length prefixed if the value spans lines
+fn field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Context, Result};
+#[cfg(unix)]
+use log::error;
+use log::info;
+#[cfg(unix)]
+use sd_notify::NotifyState;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(clap::Subcommand, Debug)]
+pub enum SystemdCommand {
+    /// Write a systemd unit file for one of the daemon modes
+    Install(InstallArgs),
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Mode {
+    Watch,
+    Poll,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InstallArgs {
+    /// The daemon mode the service runs
+    #[clap(long, value_enum, default_value_t = Mode::Watch)]
+    mode: Mode,
+
+    #[clap(short, long)]
+    source_directory: PathBuf,
+
+    #[clap(short, long)]
+    destination_directory: PathBuf,
+
+    /// Directory the unit file is written to
+    #[clap(long, default_value = "/etc/systemd/system")]
+    unit_directory: PathBuf,
+
+    /// Name of the unit, without the .service suffix
+    #[clap(long, default_value = "dessert")]
+    name: String,
+
+    /// User the service runs as
+    #[clap(long)]
+    user: Option<String>,
+
+    /// Seconds systemd waits for a watchdog ping before restarting the service.
+    /// Pings pause while a job runs, so leave room for the longest extraction
+    #[clap(long, default_value_t = 600)]
+    watchdog_seconds: u64,
+
+    /// Additional arguments passed to the daemon, after `--`
+    #[clap(last = true)]
+    args: Vec<String>,
+}
+
+pub fn run(command: &SystemdCommand) -> Result<()> {
+    match command {
+        SystemdCommand::Install(args) => install(args),
+    }
+}
+
+fn install(args: &InstallArgs) -> Result<()> {
+    let executable = std::env::current_exe().context("Failed to locate dessert executable")?;
+    let source_directory =
+        fs::canonicalize(&args.source_directory).context("Failed to resolve source directory")?;
+    let destination_directory = fs::canonicalize(&args.destination_directory)
+        .context("Failed to resolve destination directory")?;
+
+    let mode = match args.mode {
+        Mode::Watch => "watch",
+        Mode::Poll => "poll",
+    };
+
+    let mut command = vec![
+        executable.to_string_lossy().to_string(),
+        mode.to_string(),
+        "--source-directory".to_string(),
+        source_directory.to_string_lossy().to_string(),
+        "--destination-directory".to_string(),
+        destination_directory.to_string_lossy().to_string(),
+    ];
+    command.extend(args.args.iter().cloned());
+
+    let user = args
+        .user
+        .as_ref()
+        .map(|user| format!("User={user}\n"))
+        .unwrap_or_default();
+
+    let unit = format!(
+        r"[Unit]
+Description=Dessert post-processing ({mode})
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+ExecStart={}
+WatchdogSec={}
+Restart=on-failure
+{user}
+[Install]
+WantedBy=multi-user.target
+",
+        command
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+        args.watchdog_seconds,
+    );
+
+    let unit_file = args.unit_directory.join(format!("{}.service", args.name));
+    if !args.unit_directory.is_dir() {
+        return Err(anyhow!("Unit directory is not a directory"));
+    }
+    fs::write(&unit_file, unit).context("Failed to write unit file")?;
+
+    println!("Wrote {}", unit_file.display());
+    println!(
+        "Enable it with: systemctl daemon-reload && systemctl enable --now {}",
+        args.name
+    );
+
+    Ok(())
+}
+
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "\"'\\;$%".contains(c)) {
+        format!(
+            "\"{}\"",
+            arg.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "$$")
+                .replace('%', "%%")
+        )
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Tells systemd the daemon has finished starting up
+pub fn ready() {
+    #[cfg(unix)]
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        error!("Failed to notify systemd about readiness: {e}");
+    }
+}
+
+/// Tells systemd the daemon is shutting down
+pub fn stopping() {
+    #[cfg(unix)]
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Stopping]) {
+        error!("Failed to notify systemd about stopping: {e}");
+    }
+}
+
+/// Sends watchdog pings to systemd when the service has a watchdog configured
+pub struct Watchdog {
+    interval: Option<Duration>,
+    last_ping: Instant,
+}
+
+impl Watchdog {
+    pub fn new() -> Watchdog {
+        #[cfg(unix)]
+        let interval = {
+            let mut usec = 0;
+            sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec) / 2)
+        };
+        #[cfg(not(unix))]
+        let interval = None;
+
+        if let Some(interval) = interval {
+            info!("Pinging the systemd watchdog every {:?}", interval);
+        }
+
+        Watchdog {
+            interval,
+            last_ping: Instant::now(),
+        }
+    }
+
+    /// Pings the watchdog if half of its timeout has passed since the last ping
+    pub fn ping(&mut self) {
+        if let Some(interval) = self.interval {
+            if self.last_ping.elapsed() >= interval {
+                #[cfg(unix)]
+                if let Err(e) = sd_notify::notify(false, &[NotifyState::Watchdog]) {
+                    error!("Failed to ping systemd watchdog: {e}");
+                }
+                self.last_ping = Instant::now();
+            }
+        }
+    }
+
+    /// Sleeps for the duration while keeping the watchdog fed
+    pub fn sleep(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+
+        while let Some(remaining) = until.checked_duration_since(Instant::now()) {
+            self.ping();
+            thread::sleep(remaining.min(Duration::from_secs(1)));
+        }
+    }
+}
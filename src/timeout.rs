@@ -0,0 +1,86 @@
+use crate::exit;
+use log::error;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long what timed out gets to stop before dessert exits, as a job stuck in a wedged mount
+/// or in the unrar library never sees that it timed out
+pub const GRACE: Duration = Duration::from_secs(30);
+
+/// The timeout in milliseconds of the run once it has passed, or 0
+static RUN_EXPIRED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// When the job of the thread times out, with its timeout
+    static JOB_DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// The timeout of the run or of the job of the thread, once it has passed. Extracting and
+/// uploading stop when it has
+pub fn expired() -> Option<Duration> {
+    let job = JOB_DEADLINE
+        .get()
+        .filter(|(deadline, _)| Instant::now() >= *deadline)
+        .map(|(_, timeout)| timeout);
+
+    match RUN_EXPIRED.load(Ordering::SeqCst) {
+        0 => job,
+        run => Some(Duration::from_millis(run)),
+    }
+}
+
+/// Times out the run, aborting its running jobs and failing the ones that haven't started
+pub fn expire_run(timeout: Duration) {
+    error!(
+        "Timed out after {}, aborting the running jobs",
+        humantime::format_duration(timeout)
+    );
+    RUN_EXPIRED.store((timeout.as_millis() as u64).max(1), Ordering::SeqCst);
+}
+
+/// Times out the job of the thread after the timeout, until dropped
+pub struct JobDeadline(());
+
+impl JobDeadline {
+    pub fn start(timeout: Duration) -> JobDeadline {
+        JOB_DEADLINE.set(Some((Instant::now() + timeout, timeout)));
+        JobDeadline(())
+    }
+}
+
+impl Drop for JobDeadline {
+    fn drop(&mut self) {
+        JOB_DEADLINE.set(None);
+    }
+}
+
+/// Runs `expire` on its own thread once the timeout has passed, unless dropped before
+pub struct Watchdog {
+    _cancel: Sender<()>,
+}
+
+impl Watchdog {
+    pub fn start(timeout: Duration, expire: impl FnOnce() + Send + 'static) -> Watchdog {
+        let (cancel, cancelled) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                expire();
+            }
+        });
+
+        Watchdog { _cancel: cancel }
+    }
+}
+
+/// Exits with the timeout code, for what is still running [`GRACE`] after timing out
+pub fn exit(what: &str) -> ! {
+    error!(
+        "{what} is still running {} after timing out, exiting",
+        humantime::format_duration(GRACE)
+    );
+    log::logger().flush();
+    std::process::exit(exit::Code::Timeout as i32)
+}
@@ -0,0 +1,115 @@
+use crate::release::Release;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Client {
+    /// TMDB API key
+    api_key: String,
+
+    /// TMDB API base path
+    #[serde(default = "default_api_base_path")]
+    api_base_path: String,
+
+    /// Base path of the images, with the size they are shown in
+    #[serde(default = "default_image_base_path")]
+    image_base_path: String,
+}
+
+fn default_api_base_path() -> String {
+    "https://api.themoviedb.org/3".to_string()
+}
+
+fn default_image_base_path() -> String {
+    "https://image.tmdb.org/t/p/w500".to_string()
+}
+
+#[derive(Deserialize)]
+struct Results {
+    results: Vec<Found>,
+}
+
+#[derive(Deserialize)]
+struct Found {
+    id: u64,
+    poster_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Episode {
+    still_path: Option<String>,
+}
+
+impl Client {
+    /// The URL of the poster of a movie, or of the still of an episode with the poster of its
+    /// show as fallback. `None` if TMDB has neither, or for albums
+    pub fn artwork(&self, release: &Release) -> Result<Option<String>> {
+        let path = match release {
+            Release::Movie { name, year } => {
+                let mut query = vec![("query", name.clone())];
+                if *year != 0 {
+                    query.push(("year", year.to_string()));
+                }
+                self.get::<Results>("search/movie", &query)?
+                    .results
+                    .into_iter()
+                    .next()
+                    .and_then(|movie| movie.poster_path)
+            }
+            Release::Episode {
+                name,
+                season,
+                episode,
+            } => {
+                let Some(show) = self
+                    .get::<Results>("search/tv", &[("query", name.clone())])?
+                    .results
+                    .into_iter()
+                    .next()
+                else {
+                    return Ok(None);
+                };
+                let still = self
+                    .get::<Episode>(
+                        &format!("tv/{}/season/{season}/episode/{episode}", show.id),
+                        &[],
+                    )
+                    .map(|episode| episode.still_path)
+                    .unwrap_or_else(|e| {
+                        debug!("No TMDB episode details: {e:#}");
+                        None
+                    });
+                still.or(show.poster_path)
+            }
+            // TMDB has no music
+            Release::Album { .. } => return Ok(None),
+        };
+
+        Ok(path.map(|path| format!("{}{path}", self.image_base_path.trim_end_matches('/'))))
+    }
+
+    /// Checks that TMDB answers and accepts the API key
+    pub fn check(&self) -> Result<()> {
+        self.get::<serde_json::Value>("configuration", &[])
+            .map(|_| ())
+    }
+
+    fn get<T: DeserializeOwned>(&self, endpoint: &str, query: &[(&str, String)]) -> Result<T> {
+        let url = format!("{}/{endpoint}", self.api_base_path.trim_end_matches('/'));
+        debug!("GET {url} with {query:?}");
+        let response = reqwest::blocking::Client::new()
+            .get(url)
+            .query(&[("api_key", &self.api_key)])
+            .query(query)
+            .send()
+            .context("Failed to query TMDB")?;
+        debug!("TMDB responded with {}", response.status());
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to query TMDB: {}", response.text()?));
+        }
+        serde_json::from_str(&response.text()?).context("Failed to parse TMDB response")
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, error};
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where the spans of every job are exported to, over OTLP with HTTP and JSON
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Base URL of the collector, spans are posted to `<endpoint>/v1/traces`
+    endpoint: String,
+
+    /// The `service.name` the spans are reported under
+    #[serde(default = "default_service_name")]
+    service_name: String,
+}
+
+fn default_service_name() -> String {
+    "dessert".to_string()
+}
+
+/// The spans of one job: a root span with a child span per phase
+pub struct Trace {
+    job: u64,
+    trace_id: String,
+    root: Span,
+    phases: Vec<Span>,
+}
+
+struct Span {
+    span_id: String,
+    name: String,
+    start: SystemTime,
+    end: Option<SystemTime>,
+    error: Option<String>,
+}
+
+impl Span {
+    fn start(name: &str) -> Span {
+        Span {
+            span_id: random_hex(1),
+            name: name.to_string(),
+            start: SystemTime::now(),
+            end: None,
+            error: None,
+        }
+    }
+
+    fn end(&mut self) {
+        self.end.get_or_insert_with(SystemTime::now);
+    }
+}
+
+impl Trace {
+    pub fn start(job: u64) -> Trace {
+        Trace {
+            job,
+            trace_id: random_hex(2),
+            root: Span::start("job"),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Ends the span of the previous phase and starts one for the next
+    pub fn phase(&mut self, phase: &str) {
+        if let Some(previous) = self.phases.last_mut() {
+            previous.end();
+        }
+        self.phases.push(Span::start(phase));
+    }
+
+    /// Marks the job, and the phase it failed in, as failed
+    pub fn fail(&mut self, error: &str) {
+        if let Some(phase) = self.phases.last_mut() {
+            phase.error = Some(error.to_string());
+        }
+        self.root.error = Some(error.to_string());
+    }
+
+    /// Ends the spans and sends them to the collector
+    pub fn finish(mut self, config: &Config) {
+        for span in self.phases.iter_mut() {
+            span.end();
+        }
+        self.root.end();
+
+        if let Err(e) = self.export(config) {
+            error!("Failed to export trace: {e:#}");
+        }
+    }
+
+    fn export(&self, config: &Config) -> Result<()> {
+        let spans = std::iter::once(self.span_json(&self.root, None))
+            .chain(
+                self.phases
+                    .iter()
+                    .map(|span| self.span_json(span, Some(&self.root.span_id))),
+            )
+            .collect::<Vec<_>>();
+        let body = serde_json::json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{
+                        "key": "service.name",
+                        "value": { "stringValue": config.service_name },
+                    }],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "dessert" },
+                    "spans": spans,
+                }],
+            }],
+        });
+
+        let url = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+        debug!("POST {url} with {} spans", spans.len());
+        // The job waits for the export, so a slow collector must not hold it up for long
+        let response = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .context("Failed to create HTTP client")?
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .context("Failed to send spans")?;
+
+        if !response.status().is_success() {
+            Err(anyhow!("Failed to send spans: {}", response.text()?))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn span_json(&self, span: &Span, parent: Option<&str>) -> serde_json::Value {
+        let status = match &span.error {
+            // 2 is STATUS_CODE_ERROR, 1 STATUS_CODE_OK
+            Some(error) => serde_json::json!({ "code": 2, "message": error }),
+            None => serde_json::json!({ "code": 1 }),
+        };
+
+        serde_json::json!({
+            "traceId": self.trace_id,
+            "spanId": span.span_id,
+            "parentSpanId": parent.unwrap_or_default(),
+            "name": span.name,
+            // SPAN_KIND_INTERNAL
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(span.end.unwrap_or(span.start)),
+            "attributes": [{
+                "key": "dessert.job",
+                "value": { "intValue": self.job.to_string() },
+            }],
+            "status": status,
+        })
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Hex of `words` random 64 bit numbers, from the randomly keyed std hasher
+fn random_hex(words: usize) -> String {
+    (0..words)
+        .map(|_| format!("{:016x}", RandomState::new().build_hasher().finish()))
+        .collect()
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::history::{Entry, History, Outcome};
+use dessert::{shutdown, status};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, LineGauge, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const HISTORY_ROWS: usize = 10;
+
+/// A live view of a daemon in the terminal, with its queue, running jobs, recent history and
+/// the tail of its log. It takes over the terminal until dropped
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+    pub fn start(title: String, history_database: PathBuf) -> Result<Dashboard> {
+        if !io::stdout().is_terminal() {
+            return Err(anyhow!("The dashboard needs a terminal"));
+        }
+
+        status::take_terminal();
+        let terminal = ratatui::try_init().context("Failed to set up the terminal")?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || run(terminal, &title, &history_database, &stop)
+        });
+
+        Ok(Dashboard {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        ratatui::restore();
+        status::release_terminal();
+    }
+}
+
+fn run(mut terminal: DefaultTerminal, title: &str, history_database: &Path, stop: &AtomicBool) {
+    let mut history = Ok(Vec::new());
+    let mut history_read: Option<Instant> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        if history_read.is_none_or(|read| read.elapsed() > Duration::from_secs(2)) {
+            history = History::open(history_database)
+                .and_then(|history| history.list(None, None, HISTORY_ROWS))
+                .map_err(|e| e.to_string());
+            history_read = Some(Instant::now());
+        }
+
+        let _ = terminal.draw(|frame| draw(frame, title, &history));
+
+        if event::poll(Duration::from_millis(250)).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                let quit = key.code == KeyCode::Char('q')
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL));
+                if key.kind == KeyEventKind::Press && quit {
+                    shutdown::request();
+                }
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, title: &str, history: &Result<Vec<Entry>, String>) {
+    let jobs = status::jobs();
+    let [header, running, lists, log] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(jobs.len().max(1) as u16 + 2),
+        Constraint::Length(HISTORY_ROWS as u16 + 2),
+        Constraint::Min(3),
+    ])
+    .areas(frame.area());
+    let [queue, recent] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(lists);
+
+    let state = match (shutdown::aborted(), shutdown::requested()) {
+        (true, _) => "aborting".red(),
+        (_, true) => "shutting down once the running jobs finish".yellow(),
+        _ => "q to quit".dim(),
+    };
+    frame.render_widget(
+        Line::from(vec![
+            "dessert ".bold(),
+            Span::raw(title),
+            "  ".into(),
+            state,
+        ]),
+        header,
+    );
+
+    draw_jobs(frame, running, &jobs);
+    draw_queue(frame, queue);
+    draw_history(frame, recent, history);
+
+    let block = Block::bordered().title("Log");
+    let lines = status::tail();
+    let shown = lines.len().saturating_sub(block.inner(log).height as usize);
+    frame.render_widget(
+        Paragraph::new(
+            lines[shown..]
+                .iter()
+                .map(|line| Line::raw(line.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .block(block),
+        log,
+    );
+}
+
+fn draw_jobs(frame: &mut Frame, area: Rect, jobs: &[status::Job]) {
+    let block = Block::bordered().title(format!("Running ({})", jobs.len()));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if jobs.is_empty() {
+        frame.render_widget(Line::from("Idle".dim()), inner);
+        return;
+    }
+
+    let rows = Layout::vertical(jobs.iter().map(|_| Constraint::Length(1))).split(inner);
+    for (job, row) in jobs.iter().zip(rows.iter()) {
+        let ratio = match &job.progress {
+            Some(progress) if progress.total > 0 => {
+                (progress.bytes as f64 / progress.total as f64).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        };
+        let label = format!(
+            "{:<9} {:>5}s  {}",
+            job.phase.unwrap_or("starting"),
+            job.started.elapsed().as_secs(),
+            job.source.as_deref().map(name).unwrap_or_default(),
+        );
+        frame.render_widget(
+            LineGauge::default()
+                .filled_style(Style::new().fg(Color::Green))
+                .label(label)
+                .ratio(ratio),
+            *row,
+        );
+    }
+}
+
+fn draw_queue(frame: &mut Frame, area: Rect) {
+    let queue = status::queue();
+    frame.render_widget(
+        List::new(queue.iter().map(|release| ListItem::new(name(release))))
+            .block(Block::bordered().title(format!("Queue ({})", queue.len()))),
+        area,
+    );
+}
+
+fn draw_history(frame: &mut Frame, area: Rect, history: &Result<Vec<Entry>, String>) {
+    let block = Block::bordered().title("Recent");
+    let entries = match history {
+        Ok(entries) => entries,
+        Err(e) => {
+            frame.render_widget(Paragraph::new(e.as_str().red()).block(block), area);
+            return;
+        }
+    };
+
+    let items = entries.iter().map(|entry| {
+        let outcome = format!("{:<9}", entry.outcome.as_str());
+        let outcome = match entry.outcome {
+            Outcome::Succeeded => outcome.green(),
+            Outcome::Failed => outcome.red(),
+            Outcome::Undone => outcome.yellow(),
+        };
+        ListItem::new(Line::from(vec![
+            Span::raw(format!(
+                "{}  ",
+                entry
+                    .started_at
+                    .with_timezone(&chrono::Local)
+                    .format("%m-%d %H:%M")
+            )),
+            outcome,
+            Span::raw(format!(
+                "  {}",
+                entry
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| name(&entry.source_directory))
+            )),
+        ]))
+    });
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,130 @@
+use crate::daemon;
+use anyhow::{anyhow, Context, Result};
+use dessert::copy;
+use dessert::history::{self, History, Outcome};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct UndoArgs {
+    /// The id of the import to undo, as shown by `dessert history`
+    id: i64,
+
+    /// SQLite database jobs were recorded in, defaults to one in the data directory
+    #[clap(long)]
+    history_database: Option<PathBuf>,
+
+    /// Move the files of the import below this directory instead of deleting them
+    #[clap(long, value_name = "DIRECTORY")]
+    trash: Option<PathBuf>,
+
+    /// Show what would be removed, without removing anything
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// Removes the files an import wrote to the destination and the done marker daemons left in its
+/// source, and marks it as undone in the history, so the next run imports its source again
+/// instead of skipping it as known content. Files it replaced or uploaded to a remote library
+/// are not brought back
+pub fn undo(args: &UndoArgs) -> Result<()> {
+    let history = History::open(&history::database(args.history_database.as_deref())?)?;
+    let entry = history
+        .get(args.id)?
+        .ok_or(anyhow!("No job with id {} in the history", args.id))?;
+    match entry.outcome {
+        Outcome::Succeeded => {}
+        Outcome::Failed => return Err(anyhow!("Job {} failed, it imported nothing", args.id)),
+        Outcome::Undone => return Err(anyhow!("Job {} is already undone", args.id)),
+    }
+
+    for file in &entry.files {
+        if !file.is_file() {
+            warn!("{:?} no longer exists, leaving it", file);
+            continue;
+        }
+        if args.dry_run {
+            match &args.trash {
+                Some(trash) => info!("Would move {:?} to {:?}", file, trash),
+                None => info!("Would delete {:?}", file),
+            }
+            continue;
+        }
+
+        match &args.trash {
+            Some(trash) => {
+                let moved = trash.join(
+                    file.strip_prefix(&entry.destination_directory)
+                        .unwrap_or(Path::new(file.file_name().unwrap_or_default())),
+                );
+                move_file(file, &moved)?;
+                info!("Moved {:?} to {:?}", file, moved);
+            }
+            None => {
+                fs::remove_file(file).with_context(|| format!("Failed to delete {:?}", file))?;
+                info!("Deleted {:?}", file);
+            }
+        }
+        remove_empty_directories(file, &entry.destination_directory);
+    }
+
+    // Daemons skip releases marked as done, so they pick the source up again
+    let marker = daemon::done_marker(&entry.source_directory);
+    if marker.is_file() {
+        match args.dry_run {
+            true => info!("Would delete {:?}", marker),
+            false => {
+                fs::remove_file(&marker)
+                    .with_context(|| format!("Failed to delete {:?}", marker))?;
+                info!("Deleted {:?}", marker);
+            }
+        }
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+    history.undo(entry.id)?;
+    println!(
+        "Undid job {}: {} <- {}",
+        entry.id,
+        entry.name.as_deref().unwrap_or("-"),
+        entry.source_directory.display()
+    );
+
+    Ok(())
+}
+
+/// Renames the file, or copies and deletes it when the trash is on another filesystem
+fn move_file(file: &Path, moved: &Path) -> Result<()> {
+    if let Some(directory) = moved.parent() {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create directory {:?}", directory))?;
+    }
+    if moved.exists() {
+        return Err(anyhow!("{:?} is already in the trash", moved));
+    }
+
+    if fs::rename(file, moved).is_err() {
+        copy::copy(file, moved, copy::DEFAULT_BUFFER_SIZE)
+            .with_context(|| format!("Failed to move {:?} to {:?}", file, moved))?;
+        fs::remove_file(file).with_context(|| format!("Failed to delete {:?}", file))?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the directories the file leaves empty, like the season directory of an episode,
+/// below the destination directory
+fn remove_empty_directories(file: &Path, destination_directory: &Path) {
+    for directory in file.ancestors().skip(1) {
+        if directory == destination_directory
+            || !directory.starts_with(destination_directory)
+            || fs::remove_dir(directory).is_err()
+        {
+            break;
+        }
+        info!("Deleted empty directory {:?}", directory);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::daemon::{self, Processed, Retention, ScanCache, SpaceAlert};
+use crate::digest::Digest;
+use crate::schedule::Schedule;
+use crate::systemd::{self, Watchdog};
+use crate::web::Web;
+use anyhow::{anyhow, Context, Result};
+use clap::CommandFactory;
+use dessert::config::Reloader;
+use dessert::{metrics, shutdown, status};
+use dessert::{Job, Options};
+use log::{error, info};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+#[derive(clap::Args, Debug)]
+pub struct WatchArgs {
+    #[clap(flatten)]
+    job: Job,
+
+    /// Seconds without filesystem activity before a release is considered complete
+    #[clap(long, default_value_t = 10)]
+    settle_seconds: u64,
+
+    /// Cron schedule for sweeping the whole source directory, e.g. "*/10 * * * *"
+    #[clap(long)]
+    scan_schedule: Option<String>,
+
+    /// Address to serve Prometheus metrics on at /metrics, e.g. 127.0.0.1:9898
+    #[clap(long)]
+    metrics_listen: Option<String>,
+
+    /// Show a live dashboard of the queue, running jobs, recent history and log
+    #[cfg(feature = "tui")]
+    #[clap(long)]
+    tui: bool,
+
+    /// Address to serve a web dashboard on, e.g. 0.0.0.0:8081, with the queue, running jobs,
+    /// history with the logs of the jobs and a form to process a path right away
+    #[clap(long)]
+    web_listen: Option<String>,
+
+    /// Process releases again that an earlier run marked as done with a .dessert-done file
+    #[clap(long)]
+    force: bool,
+
+    /// Cron schedule for sending a digest of the jobs since the last one, e.g. "0 9 * * 1" for
+    /// every Monday morning
+    #[clap(long)]
+    digest_schedule: Option<String>,
+
+    /// Only send the digest, instead of a notification for every job
+    #[clap(long, requires = "digest_schedule")]
+    digest_only: bool,
+
+    /// Send an email when the destination has less free space than this, e.g. "100G"
+    #[clap(long, value_parser = dessert::space::parse_size)]
+    space_alert: Option<u64>,
+
+    /// How often to repeat the --space-alert email while the free space stays below it
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1d")]
+    space_alert_interval: Duration,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+pub fn watch(args: &WatchArgs, log_file: File) -> Result<()> {
+    let root = &args.job.source_directory;
+    if let Some(listen) = &args.metrics_listen {
+        metrics::serve(listen)?;
+    }
+    args.options.drop_privileges()?;
+    if !root.is_dir() {
+        return Err(anyhow!("Source directory is not a directory"));
+    }
+
+    shutdown::install()?;
+
+    let mut schedule = args
+        .scan_schedule
+        .as_deref()
+        .map(Schedule::parse)
+        .transpose()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).context("Failed to create filesystem watcher")?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .context("Failed to watch source directory")?;
+    info!("Watching {:?}", root);
+    status::set_watching(true);
+    systemd::ready();
+    let mut watchdog = Watchdog::new();
+    let mut reloader = Reloader::new(crate::Cli::command())?;
+
+    let settle = Duration::from_secs(args.settle_seconds);
+    let mut pending = HashMap::<PathBuf, Instant>::new();
+    let mut processed = Processed::new(
+        root,
+        args.options.jobs,
+        log_file,
+        args.force,
+        !args.digest_only,
+    )?;
+    let mut digest = args
+        .digest_schedule
+        .as_deref()
+        .map(Digest::new)
+        .transpose()?;
+    let mut retention = Retention::new();
+    let mut space_alert = args
+        .space_alert
+        .map(|threshold| SpaceAlert::new(threshold, args.space_alert_interval));
+    let web = args
+        .web_listen
+        .as_deref()
+        .map(|listen| {
+            Web::serve(
+                listen,
+                dessert::history::database(args.options.history_database.as_deref())?,
+            )
+        })
+        .transpose()?;
+    let mut cache = ScanCache::new();
+
+    #[cfg(feature = "tui")]
+    let dashboard = match args.tui {
+        true => Some(crate::tui::Dashboard::start(
+            format!("watching {}", root.display()),
+            dessert::history::database(args.options.history_database.as_deref())?,
+        )?),
+        false => None,
+    };
+
+    while !shutdown::requested() {
+        watchdog.ping();
+        status::heartbeat();
+        reloader.check();
+        if let Some(digest) = &mut digest {
+            digest.check(&args.options);
+        }
+        retention.check(args.options.dry_run);
+        if let Some(space_alert) = &mut space_alert {
+            space_alert.check(&args.job.destination_directory, &args.options);
+        }
+        for release in web.iter().flat_map(Web::requested) {
+            processed.process(&release, &args.job.destination_directory, &args.options);
+        }
+
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(Ok(event)) if !event.kind.is_access() => {
+                for path in event.paths {
+                    if let Some(release) = daemon::release_for(root, &path) {
+                        if !processed.contains(&release) {
+                            pending.insert(release, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Filesystem watcher error: {e}"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                status::set_watching(false);
+                return Err(anyhow!("Filesystem watcher stopped"));
+            }
+        }
+
+        if schedule.as_mut().is_some_and(Schedule::due) {
+            info!("Sweeping {:?}", root);
+            match daemon::releases(root) {
+                Ok(releases) => {
+                    status::scanned();
+                    cache.retain(&releases);
+                    for release in releases {
+                        if processed.contains(&release)
+                            || pending.contains_key(&release)
+                            || cache.unchanged(root, &release)
+                        {
+                            continue;
+                        }
+                        match daemon::is_release(&release) {
+                            true => {
+                                info!("Processing {:?}", release);
+                                processed.process(
+                                    &release,
+                                    &args.job.destination_directory,
+                                    &args.options,
+                                );
+                            }
+                            false => cache.ignore(root, &release),
+                        }
+                    }
+                }
+                Err(e) => error!("{e}"),
+            }
+        }
+
+        let settled = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= settle)
+            .map(|(release, _)| release.clone())
+            .collect::<Vec<_>>();
+
+        for release in settled {
+            pending.remove(&release);
+
+            if daemon::is_release(&release) {
+                info!("Processing {:?}", release);
+                processed.process(&release, &args.job.destination_directory, &args.options);
+            }
+        }
+        status::set_queue(pending.keys().cloned().collect());
+    }
+
+    // The totals are reported to the terminal once it is given back
+    #[cfg(feature = "tui")]
+    drop(dashboard);
+    systemd::stopping();
+    processed.finish();
+    shutdown::finish()
+}
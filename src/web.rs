@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Context, Result};
+use dessert::history::{History, Outcome};
+use dessert::{metrics, status};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const PAGE: &str = include_str!("web.html");
+
+/// Jobs listed in the history of the dashboard
+const HISTORY_ROWS: usize = 50;
+
+/// A dashboard of a daemon in the browser, with its queue, running jobs, history with the logs
+/// of the jobs, and a form to process a path right away
+pub struct Web {
+    requested: Receiver<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct ProcessRequest {
+    path: PathBuf,
+}
+
+impl Web {
+    pub fn serve(listen: &str, history_database: PathBuf) -> Result<Web> {
+        let server =
+            Server::http(listen).map_err(|e| anyhow!("Failed to listen on {listen}: {e}"))?;
+        info!("Serving the web dashboard on {listen}");
+        status::keep_tail();
+
+        let (sender, requested) = mpsc::channel();
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let response = respond(&mut request, &history_database, &sender);
+                if let Err(e) = request.respond(response) {
+                    error!("Failed to send response: {e}");
+                }
+            }
+        });
+
+        Ok(Web { requested })
+    }
+
+    /// The paths submitted to be processed since the last call
+    pub fn requested(&self) -> Vec<PathBuf> {
+        self.requested.try_iter().collect()
+    }
+}
+
+fn respond(
+    request: &mut Request,
+    history_database: &Path,
+    sender: &Sender<PathBuf>,
+) -> Response<Cursor<Vec<u8>>> {
+    let (url, query) = request
+        .url()
+        .split_once('?')
+        .map(|(url, query)| (url.to_string(), query.to_string()))
+        .unwrap_or((request.url().to_string(), String::new()));
+    let segments = url
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    let result = match (request.method(), segments.as_slice()) {
+        (Method::Get, []) => Ok(text(200, "text/html; charset=utf-8", PAGE.to_string())),
+        (Method::Get, ["api", "status"]) => Ok(json_response(200, &current())),
+        (Method::Get, ["api", "history"]) => history(history_database),
+        (Method::Get, ["api", "history", id, "log"]) => log(history_database, id),
+        (Method::Post, ["api", "process"]) => process(request, sender),
+        (Method::Get, ["healthz"]) => Ok(metrics::health_response()),
+        (Method::Get, ["jobs"]) => jobs(history_database, &query),
+        _ => Ok(error_response(404, "Not found")),
+    };
+
+    result.unwrap_or_else(|e| error_response(500, &format!("{e:#}")))
+}
+
+/// The queue and the running jobs with the tail of the log
+fn current() -> serde_json::Value {
+    let jobs = status::jobs()
+        .into_iter()
+        .map(|job| {
+            json!({
+                "source": job.source,
+                "phase": job.phase,
+                "elapsed_seconds": job.started.elapsed().as_secs(),
+                "bytes": job.progress.as_ref().map(|progress| progress.bytes),
+                "total": job.progress.as_ref().map(|progress| progress.total),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "queue": status::queue(),
+        "jobs": jobs,
+        "log": status::tail(),
+    })
+}
+
+fn history(history_database: &Path) -> Result<Response<Cursor<Vec<u8>>>> {
+    let entries = History::open(history_database)?
+        .list(None, None, HISTORY_ROWS)?
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "id": entry.id,
+                "started_at": entry.started_at.to_rfc3339(),
+                "source_directory": entry.source_directory,
+                "name": entry.name,
+                "outcome": entry.outcome.as_str(),
+                "error": entry.error,
+                "upgrade": entry.upgrade,
+                "duration_seconds": entry.duration.as_secs_f64(),
+                "log": entry.log_file.is_some(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json_response(200, &entries))
+}
+
+/// The jobs with the status of the query, `queued`, `running`, `succeeded` or `failed`, or
+/// else the running ones and the latest of the history. `limit` caps those of the history
+fn jobs(history_database: &Path, query: &str) -> Result<Response<Cursor<Vec<u8>>>> {
+    let status = parameter(query, "status");
+    let limit = match parameter(query, "limit").map(str::parse) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return Ok(error_response(400, "Invalid limit")),
+        None => HISTORY_ROWS,
+    };
+
+    let mut jobs = Vec::new();
+    if status.is_none_or(|status| status == "queued") {
+        jobs.extend(
+            status::queue()
+                .into_iter()
+                .map(|source| json!({ "status": "queued", "source_directory": source })),
+        );
+    }
+    if status.is_none_or(|status| status == "running") {
+        jobs.extend(status::jobs().into_iter().map(|job| {
+            json!({
+                "status": "running",
+                "source_directory": job.source,
+                "phase": job.phase,
+                "elapsed_seconds": job.started.elapsed().as_secs(),
+            })
+        }));
+    }
+    let outcome = match status {
+        None => None,
+        Some("succeeded") => Some(Outcome::Succeeded),
+        Some("failed") => Some(Outcome::Failed),
+        Some("undone") => Some(Outcome::Undone),
+        Some("queued" | "running") => return Ok(json_response(200, &jobs)),
+        Some(_) => return Ok(error_response(400, "Unknown status")),
+    };
+    jobs.extend(
+        History::open(history_database)?
+            .list(outcome, None, limit)?
+            .into_iter()
+            .map(|entry| {
+                json!({
+                    "status": entry.outcome.as_str(),
+                    "id": entry.id,
+                    "started_at": entry.started_at.to_rfc3339(),
+                    "source_directory": entry.source_directory,
+                    "destination_directory": entry.destination_directory,
+                    "file_name": entry.name,
+                    "files": entry.files,
+                    "error": entry.error,
+                    "duration_seconds": entry.duration.as_secs_f64(),
+                })
+            }),
+    );
+
+    Ok(json_response(200, &jobs))
+}
+
+/// The value of the parameter in the query of a URL, like `failed` of `status=failed`
+pub fn parameter<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// The log file of a job of the history
+fn log(history_database: &Path, id: &str) -> Result<Response<Cursor<Vec<u8>>>> {
+    let Some(entry) = id
+        .parse()
+        .ok()
+        .map(|id| History::open(history_database)?.get(id))
+        .transpose()?
+        .flatten()
+    else {
+        return Ok(error_response(404, "Job not found"));
+    };
+    let Some(log_file) = entry.log_file else {
+        return Ok(error_response(404, "The job has no log file"));
+    };
+
+    let log = fs::read_to_string(&log_file)
+        .with_context(|| format!("Failed to read log file {:?}", log_file))?;
+    Ok(text(200, "text/plain; charset=utf-8", log))
+}
+
+fn process(request: &mut Request, sender: &Sender<PathBuf>) -> Result<Response<Cursor<Vec<u8>>>> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .context("Failed to read request body")?;
+    let Ok(process) = serde_json::from_str::<ProcessRequest>(&body) else {
+        return Ok(error_response(400, "Expected a JSON body with a path"));
+    };
+    if !process.path.exists() {
+        return Ok(error_response(400, "The path does not exist"));
+    }
+
+    info!("Requested from the web dashboard: {:?}", process.path);
+    sender
+        .send(process.path.clone())
+        .context("Failed to queue path")?;
+    Ok(json_response(202, &json!({ "path": process.path })))
+}
+
+fn text(status: u16, content_type: &str, body: String) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(body.into_bytes())
+        .with_status_code(status)
+        .with_header(
+            format!("Content-Type: {content_type}")
+                .parse::<Header>()
+                .expect("Valid header"),
+        )
+}
+
+fn json_response(status: u16, body: &impl serde::Serialize) -> Response<Cursor<Vec<u8>>> {
+    text(
+        status,
+        "application/json",
+        serde_json::to_string(body).unwrap_or_default(),
+    )
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, &json!({ "error": message }))
+}